@@ -1,4 +1,5 @@
 use core::str::FromStr;
+use std::path::PathBuf;
 #[cfg(feature = "tracing-subscriber")]
 use tracing_subscriber::EnvFilter;
 
@@ -52,6 +53,22 @@ pub enum Action {
 
     /// Push buttons
     Button(ButtonArgs),
+
+    /// Take screenshot
+    Screenshot(ScreenshotArgs),
+
+    /// Control session recording
+    Record(RecordArgs),
+
+    /// Read text screen
+    Screen(ScreenArgs),
+
+    /// Host power state
+    Power(PowerArgs),
+
+    /// Play session record
+    #[cfg(feature = "record")]
+    Play(PlayArgs),
 }
 
 /// Show status
@@ -76,3 +93,117 @@ pub struct ButtonArgs {
     #[argp(positional, default = "\"power\".into()")]
     pub button: String,
 }
+
+/// Take screenshot
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "screenshot")]
+pub struct ScreenshotArgs {
+    /// Output file
+    #[argp(option, short = 'o')]
+    pub output: PathBuf,
+    /// Image format (png, jpeg or webp, by default from output file extension)
+    #[argp(option, short = 'f')]
+    pub format: Option<String>,
+    /// Scale to width
+    #[argp(option, short = 'w')]
+    pub width: Option<u32>,
+    /// Scale to height
+    #[argp(option)]
+    pub height: Option<u32>,
+    /// Crop region (x,y,width,height)
+    #[argp(option, short = 'c')]
+    pub crop: Option<String>,
+    /// JPEG quality (1-100)
+    #[argp(option, short = 'q')]
+    pub quality: Option<u8>,
+}
+
+/// Control session recording
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "record")]
+pub struct RecordArgs {
+    /// Stop recording
+    #[argp(switch, short = 's')]
+    pub stop: bool,
+}
+
+/// Play session record
+#[cfg(feature = "record")]
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "play")]
+pub struct PlayArgs {
+    /// Play in real time
+    #[argp(switch, short = 'r')]
+    pub realtime: bool,
+    /// Start from time (milliseconds)
+    #[argp(option, short = 's', default = "0")]
+    pub seek: u64,
+    /// Extract frames to directory
+    #[argp(option, short = 'o')]
+    pub frames: Option<PathBuf>,
+    /// Record file
+    #[argp(positional)]
+    pub file: PathBuf,
+}
+
+/// Read text screen
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "screen")]
+pub struct ScreenArgs {
+    #[argp(subcommand)]
+    pub action: ScreenAction,
+}
+
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand)]
+pub enum ScreenAction {
+    /// Print text on screen
+    Text(ScreenTextArgs),
+
+    /// Wait for text on screen
+    Wait(ScreenWaitArgs),
+}
+
+/// Print text on screen
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "text")]
+pub struct ScreenTextArgs {}
+
+/// Wait for text on screen
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "wait")]
+pub struct ScreenWaitArgs {
+    /// Specify timeout (milliseconds)
+    #[argp(option, short = 't', default = "30000")]
+    pub timeout: u32,
+    /// Regular expression to match
+    #[argp(positional)]
+    pub regex: String,
+}
+
+/// Host power state
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "power")]
+pub struct PowerArgs {
+    #[argp(subcommand)]
+    pub action: PowerAction,
+}
+
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand)]
+pub enum PowerAction {
+    /// Wait for power state
+    Wait(PowerWaitArgs),
+}
+
+/// Wait for power state
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "wait")]
+pub struct PowerWaitArgs {
+    /// Specify timeout (milliseconds)
+    #[argp(option, short = 't', default = "300000")]
+    pub timeout: u32,
+    /// Power state (off, standby, booting, on or hung)
+    #[argp(positional)]
+    pub state: String,
+}
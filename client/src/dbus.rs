@@ -1,4 +1,7 @@
-use crate::{ButtonId, LedId, DBusAddr, Result, GenericClient, ClientEvent, Stream, log};
+use crate::{
+    ButtonId, LedId, LedMode, LedPattern, PowerState, SensorKind, SensorReading, SensorStatus,
+    DBusAddr, Result, GenericClient, ClientEvent, ScreenshotOptions, Stream, log,
+};
 use zbus::{proxy, Connection, proxy::Proxy};
 use std::{collections::HashMap, sync::{atomic::{Ordering, AtomicBool}, Arc}};
 use tokio::sync::broadcast::{channel, Sender};
@@ -30,6 +33,95 @@ pub trait Led {
     /// Current state
     #[zbus(property)]
     fn state(&self) -> zbus::Result<bool>;
+
+    /// Derived behavior
+    #[zbus(property)]
+    fn mode(&self) -> zbus::Result<LedMode>;
+
+    /// Blinking period in milliseconds
+    #[zbus(property)]
+    fn period(&self) -> zbus::Result<u32>;
+}
+
+/// Sensor monitoring interface
+#[proxy(interface = "org.ukvm.Sensor", default_service = "org.ukvm.Control")]
+pub trait Sensor {
+    /// Measured quantity
+    #[zbus(property)]
+    fn kind(&self) -> zbus::Result<SensorKind>;
+
+    /// Current value
+    #[zbus(property)]
+    fn value(&self) -> zbus::Result<f64>;
+
+    /// Value state
+    #[zbus(property)]
+    fn status(&self) -> zbus::Result<SensorStatus>;
+}
+
+/// Session recording interface
+#[proxy(
+    interface = "org.ukvm.Recorder",
+    default_service = "org.ukvm.Control",
+    default_path = "/org/ukvm/recorder"
+)]
+pub trait Recorder {
+    /// Path of current record
+    #[zbus(property)]
+    fn path(&self) -> zbus::Result<String>;
+
+    /// Start recording
+    fn start(&self) -> zbus::Result<String>;
+
+    /// Stop recording
+    fn stop(&self) -> zbus::Result<String>;
+}
+
+/// Video capturing interface
+#[proxy(
+    interface = "org.ukvm.Video",
+    default_service = "org.ukvm.Control",
+    default_path = "/org/ukvm/video"
+)]
+pub trait Video {
+    /// Make screenshot of latest frame
+    fn screenshot(
+        &self,
+        format: &str,
+        width: u32,
+        height: u32,
+        crop: &str,
+        quality: u8,
+    ) -> zbus::Result<Vec<u8>>;
+}
+
+/// Host power state interface
+#[proxy(
+    interface = "org.ukvm.Power",
+    default_service = "org.ukvm.Control",
+    default_path = "/org/ukvm/power"
+)]
+pub trait Power {
+    /// Host power state
+    #[zbus(property)]
+    fn state(&self) -> zbus::Result<PowerState>;
+
+    /// Wait until host reaches power state
+    fn wait(&self, state: PowerState, timeout: u32) -> zbus::Result<()>;
+}
+
+/// Text screen recognition interface
+#[proxy(
+    interface = "org.ukvm.Screen",
+    default_service = "org.ukvm.Control",
+    default_path = "/org/ukvm/screen"
+)]
+pub trait Screen {
+    /// Recognize text on screen
+    fn text(&self) -> zbus::Result<String>;
+
+    /// Wait until text on screen matches regular expression
+    fn wait(&self, regex: &str, timeout: u32) -> zbus::Result<String>;
 }
 
 struct Button {
@@ -45,6 +137,11 @@ struct Led {
 pub struct DBusClient {
     buttons: HashMap<ButtonId, Button>,
     leds: HashMap<LedId, Led>,
+    sensors: HashMap<String, SensorProxy<'static>>,
+    power: PowerProxy<'static>,
+    video: VideoProxy<'static>,
+    recorder: RecorderProxy<'static>,
+    screen: ScreenProxy<'static>,
     events: Sender<ClientEvent>,
 }
 
@@ -62,6 +159,10 @@ impl DBusClient {
                                          "org.ukvm.Control",
                                          "/org/ukvm/led").await.unwrap_or_default();
 
+        let sensor_names = Self::list_nodes(&connection, "org.ukvm.Control", "/org/ukvm/sensor")
+            .await
+            .unwrap_or_default();
+
         let (sender, _) = channel(16);
         let events = sender.clone();
 
@@ -103,7 +204,34 @@ impl DBusClient {
             }
         }
 
-        Ok(Self { buttons, leds, events })
+        let mut sensors = HashMap::new();
+
+        for name in sensor_names {
+            log::info!("Add sensor {name}");
+
+            let proxy = SensorProxy::builder(&connection)
+                .path(format!("/org/ukvm/sensor/{name}"))?
+                .build()
+                .await?;
+
+            sensors.insert(name, proxy);
+        }
+
+        let power = PowerProxy::new(&connection).await?;
+        let video = VideoProxy::new(&connection).await?;
+        let recorder = RecorderProxy::new(&connection).await?;
+        let screen = ScreenProxy::new(&connection).await?;
+
+        Ok(Self {
+            buttons,
+            leds,
+            sensors,
+            power,
+            video,
+            recorder,
+            screen,
+            events,
+        })
     }
 
     async fn list_nodes(connection: &Connection, destination: impl AsRef<str>, path: impl AsRef<str>) -> Result<Vec<String>> {
@@ -128,12 +256,10 @@ impl DBusClient {
             match reader.read_event_into(&mut buf) {
                 Err(e) => Err(format!("XML error at {}: {:?}", reader.buffer_position(), e))?,
                 Ok(Event::Eof) => break,
-                Ok(Event::Start(e) | Event::Empty(e)) => {
-                    if e.name().as_ref() == b"node" {
-                        if let Ok(Some(name)) = e.try_get_attribute(b"name") {
-                            if let Ok(name) = core::str::from_utf8(&name.value) {
-                                nodes.push(name.into());
-                            }
+                Ok(Event::Start(e) | Event::Empty(e)) if e.name().as_ref() == b"node" => {
+                    if let Ok(Some(name)) = e.try_get_attribute(b"name") {
+                        if let Ok(name) = core::str::from_utf8(&name.value) {
+                            nodes.push(name.into());
                         }
                     }
                 }
@@ -170,7 +296,74 @@ impl GenericClient for DBusClient {
         Ok(led.state.load(Ordering::SeqCst))
     }
 
+    async fn led_pattern(&self, id: LedId) -> Result<LedPattern> {
+        let led = self.leds.get(&id).ok_or_else(|| format!("No LED {id}"))?;
+        Ok(LedPattern {
+            mode: led.proxy.mode().await?,
+            period: led.proxy.period().await?,
+        })
+    }
+
+    fn sensors(&self) -> Vec<String> {
+        self.sensors.keys().cloned().collect()
+    }
+
+    async fn sensor_reading(&self, name: &str) -> Result<SensorReading> {
+        let sensor = self
+            .sensors
+            .get(name)
+            .ok_or_else(|| format!("No sensor {name}"))?;
+        let value = sensor.value().await?;
+        Ok(SensorReading {
+            kind: sensor.kind().await?,
+            value: if value.is_nan() { None } else { Some(value) },
+            status: sensor.status().await?,
+        })
+    }
+
     fn events(&self) -> Box<dyn Stream<Item = ClientEvent> + 'static> {
         Box::new(BroadcastStream::new(self.events.subscribe()).filter_map(|res| res.ok()))
     }
+
+    async fn power_state(&self) -> Result<PowerState> {
+        Ok(self.power.state().await?)
+    }
+
+    async fn wait_power(&self, state: PowerState, timeout: u32) -> Result<()> {
+        Ok(self.power.wait(state, timeout).await?)
+    }
+
+    async fn screenshot(&self, options: &ScreenshotOptions) -> Result<Vec<u8>> {
+        Ok(self
+            .video
+            .screenshot(
+                &options.format,
+                options.width.unwrap_or_default(),
+                options.height.unwrap_or_default(),
+                options.crop.as_deref().unwrap_or_default(),
+                options.quality.unwrap_or_default(),
+            )
+            .await?)
+    }
+
+    async fn recording(&self) -> Result<Option<String>> {
+        let path = self.recorder.path().await?;
+        Ok(if path.is_empty() { None } else { Some(path) })
+    }
+
+    async fn start_recording(&self) -> Result<String> {
+        Ok(self.recorder.start().await?)
+    }
+
+    async fn stop_recording(&self) -> Result<String> {
+        Ok(self.recorder.stop().await?)
+    }
+
+    async fn screen_text(&self) -> Result<String> {
+        Ok(self.screen.text().await?)
+    }
+
+    async fn wait_screen_text(&self, regex: &str, timeout: u32) -> Result<String> {
+        Ok(self.screen.wait(regex, timeout).await?)
+    }
 }
@@ -5,7 +5,9 @@ mod dbus;
 
 pub use tracing as log;
 
-pub use ukvm_core::{ButtonId, LedId};
+pub use ukvm_core::{
+    ButtonId, LedId, LedMode, LedPattern, PowerState, SensorKind, SensorReading, SensorStatus,
+};
 
 use futures_util::stream::Stream;
 
@@ -21,11 +23,29 @@ pub use ukvm_core::{HttpAddr, SocketInput, SocketOutput};
 #[cfg(feature = "dbus")]
 pub use dbus::DBusClient;
 
+#[cfg(feature = "record")]
+pub use ukvm_core::record::{RecordData, RecordEvent, RecordReader};
+
 //#[cfg(feature = "http")]
 //pub use http::{HttpConn};
 
 pub use result::{Error, Result};
 
+/// Screenshot options
+#[derive(Clone, Debug, Default)]
+pub struct ScreenshotOptions {
+    /// Image format (png, jpeg or webp)
+    pub format: String,
+    /// Scale to width
+    pub width: Option<u32>,
+    /// Scale to height
+    pub height: Option<u32>,
+    /// Crop region as x,y,width,height
+    pub crop: Option<String>,
+    /// JPEG quality
+    pub quality: Option<u8>,
+}
+
 #[derive(Clone, Debug)]
 pub enum ClientEvent {
     Button { id: ButtonId, state: bool },
@@ -40,8 +60,24 @@ trait GenericClient {
 
     fn leds(&self) -> Vec<LedId>;
     fn led_state(&self, id: LedId) -> Result<bool>;
+    async fn led_pattern(&self, id: LedId) -> Result<LedPattern>;
+
+    fn sensors(&self) -> Vec<String>;
+    async fn sensor_reading(&self, name: &str) -> Result<SensorReading>;
+
+    async fn power_state(&self) -> Result<PowerState>;
+    async fn wait_power(&self, state: PowerState, timeout: u32) -> Result<()>;
 
     fn events(&self) -> Box<dyn Stream<Item = ClientEvent> + 'static>;
+
+    async fn screenshot(&self, options: &ScreenshotOptions) -> Result<Vec<u8>>;
+
+    async fn recording(&self) -> Result<Option<String>>;
+    async fn start_recording(&self) -> Result<String>;
+    async fn stop_recording(&self) -> Result<String>;
+
+    async fn screen_text(&self) -> Result<String>;
+    async fn wait_screen_text(&self, regex: &str, timeout: u32) -> Result<String>;
 }
 
 pub struct Client {
@@ -80,7 +116,65 @@ impl Client {
         self.inner.led_state(id)
     }
 
+    /// Get derived LED state (blinking, activity)
+    pub async fn led_pattern(&self, id: LedId) -> Result<LedPattern> {
+        self.inner.led_pattern(id).await
+    }
+
+    pub fn sensors(&self) -> Vec<String> {
+        self.inner.sensors()
+    }
+
+    /// Get current sensor value
+    pub async fn sensor_reading(&self, name: &str) -> Result<SensorReading> {
+        self.inner.sensor_reading(name).await
+    }
+
+    /// Get host power state
+    pub async fn power_state(&self) -> Result<PowerState> {
+        self.inner.power_state().await
+    }
+
+    /// Wait until host reaches power state
+    ///
+    /// Timeout is in milliseconds.
+    pub async fn wait_power(&self, state: PowerState, timeout: u32) -> Result<()> {
+        self.inner.wait_power(state, timeout).await
+    }
+
     pub fn events(&self) -> Box<dyn Stream<Item = ClientEvent> + 'static> {
         self.inner.events()
     }
+
+    /// Make screenshot of latest video frame
+    pub async fn screenshot(&self, options: &ScreenshotOptions) -> Result<Vec<u8>> {
+        self.inner.screenshot(options).await
+    }
+
+    /// Get path of current session record
+    pub async fn recording(&self) -> Result<Option<String>> {
+        self.inner.recording().await
+    }
+
+    /// Start session recording
+    pub async fn start_recording(&self) -> Result<String> {
+        self.inner.start_recording().await
+    }
+
+    /// Stop session recording
+    pub async fn stop_recording(&self) -> Result<String> {
+        self.inner.stop_recording().await
+    }
+
+    /// Recognize text on screen
+    pub async fn screen_text(&self) -> Result<String> {
+        self.inner.screen_text().await
+    }
+
+    /// Wait until text on screen matches regular expression
+    ///
+    /// Timeout is in milliseconds. Returns matched text.
+    pub async fn wait_screen_text(&self, regex: &str, timeout: u32) -> Result<String> {
+        self.inner.wait_screen_text(regex, timeout).await
+    }
 }
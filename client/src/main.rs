@@ -1,7 +1,13 @@
 mod args;
 
-use args::{Args, Action, ButtonArgs};
-use ukvmc::{Result, Client, Addr, ButtonId};
+#[cfg(feature = "record")]
+mod play;
+
+use args::{
+    Args, Action, ButtonArgs, PowerAction, PowerArgs, PowerWaitArgs, RecordArgs, ScreenAction,
+    ScreenArgs, ScreenWaitArgs, ScreenshotArgs,
+};
+use ukvmc::{Result, Client, Addr, ButtonId, PowerState, ScreenshotOptions};
 
 #[cfg_attr(not(feature = "multi-thread"), tokio::main(flavor = "current_thread"))]
 #[cfg_attr(feature = "multi-thread", tokio::main)]
@@ -30,6 +36,12 @@ async fn main() -> Result<()> {
         registry.init();
     }
 
+    // playing doesn't need connection
+    #[cfg(feature = "record")]
+    if let Action::Play(args) = &args.action {
+        return play::play(args).await;
+    }
+
     let client = Client::open(&args.uri).await?;
 
     match args.action {
@@ -52,8 +64,29 @@ async fn main() -> Result<()> {
                     "off"
                 };
                 print!(" {id}:{state}");
+                if let Ok(pattern) = client.led_pattern(id).await {
+                    print!("({pattern})");
+                }
             }
             println!();
+            let mut sensors = client.sensors();
+            if !sensors.is_empty() {
+                sensors.sort();
+                print!("Sensors:");
+                for name in sensors {
+                    match client.sensor_reading(&name).await {
+                        Ok(reading) => print!(" {name}:{reading}"),
+                        Err(_) => print!(" {name}:?"),
+                    }
+                }
+                println!();
+            }
+            if let Ok(state) = client.power_state().await {
+                println!("Power: {state}");
+            }
+            if let Ok(Some(path)) = client.recording().await {
+                println!("Recording: {path}");
+            }
         }
         Action::Button(ButtonArgs { press, release, delay, button }) => {
             let id = button.parse::<ButtonId>().map_err(|_| format!("Unknown button {button}"))?;
@@ -73,6 +106,65 @@ async fn main() -> Result<()> {
                 client.set_button_state(id, false).await?;
             }
         }
+        Action::Screenshot(ScreenshotArgs {
+            output,
+            format,
+            width,
+            height,
+            crop,
+            quality,
+        }) => {
+            let format = match format {
+                Some(format) => format,
+                None => match output
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(str::to_lowercase)
+                {
+                    Some(ext) if ext == "jpg" => "jpeg".into(),
+                    Some(ext) => ext,
+                    None => "png".into(),
+                },
+            };
+            let options = ScreenshotOptions {
+                format,
+                width,
+                height,
+                crop,
+                quality,
+            };
+            let image = client.screenshot(&options).await?;
+            std::fs::write(&output, image)?;
+            println!("Saved {}", output.display());
+        }
+        Action::Record(RecordArgs { stop }) => {
+            if stop {
+                let path = client.stop_recording().await?;
+                println!("Recorded {path}");
+            } else {
+                let path = client.start_recording().await?;
+                println!("Recording {path}");
+            }
+        }
+        Action::Screen(ScreenArgs { action }) => match action {
+            ScreenAction::Text(_) => {
+                print!("{}", client.screen_text().await?);
+            }
+            ScreenAction::Wait(ScreenWaitArgs { timeout, regex }) => {
+                println!("{}", client.wait_screen_text(&regex, timeout).await?);
+            }
+        },
+        Action::Power(PowerArgs { action }) => match action {
+            PowerAction::Wait(PowerWaitArgs { timeout, state }) => {
+                let state = state
+                    .parse::<PowerState>()
+                    .map_err(|_| format!("Unknown power state {state}"))?;
+                client.wait_power(state, timeout).await?;
+                println!("Power: {state}");
+            }
+        },
+        #[cfg(feature = "record")]
+        Action::Play(_) => {}
     }
 
     Ok(())
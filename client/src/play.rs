@@ -0,0 +1,98 @@
+use crate::args::PlayArgs;
+use core::time::Duration;
+use std::{
+    fs::{create_dir_all, write, File},
+    io::{BufReader, ErrorKind},
+};
+use tokio::time::{sleep_until, Instant};
+use ukvmc::{log, RecordData, RecordEvent, RecordReader, Result};
+
+/// Print record events and extract frames
+pub async fn play(args: &PlayArgs) -> Result<()> {
+    let mut reader = RecordReader::new(BufReader::new(File::open(&args.file)?))?;
+
+    let start_time = reader.start_time();
+    println!(
+        "Recorded at {}.{:03} (UNIX time)",
+        start_time / 1000,
+        start_time % 1000
+    );
+
+    if let Some(dir) = &args.frames {
+        create_dir_all(dir)?;
+    }
+
+    let seek = Duration::from_millis(args.seek);
+    if !seek.is_zero() {
+        reader.seek(seek)?;
+    }
+
+    let started = Instant::now();
+    let mut frames = 0usize;
+    let mut events = 0usize;
+    let mut duration = Duration::ZERO;
+
+    loop {
+        let chunk = match reader.next_chunk() {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(error) if error.kind() == ErrorKind::InvalidData => {
+                // events which this client doesn't know
+                log::warn!("Skip unreadable event: {error}");
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
+
+        duration = chunk.time;
+
+        if args.realtime {
+            sleep_until(started + chunk.time.saturating_sub(seek)).await;
+        }
+
+        match chunk.data {
+            RecordData::Frame(frame) => {
+                frames += 1;
+                if let Some(dir) = &args.frames {
+                    write(
+                        dir.join(format!("{:09}.jpg", chunk.time.as_millis())),
+                        frame,
+                    )?;
+                }
+            }
+            RecordData::Event(event) => {
+                events += 1;
+                println!("{} {}", format_time(chunk.time), format_event(&event)?);
+            }
+        }
+    }
+
+    println!("{} {frames} frames, {events} events", format_time(duration));
+
+    Ok(())
+}
+
+fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!(
+        "{:02}:{:02}.{:03}",
+        secs / 60,
+        secs % 60,
+        time.subsec_millis()
+    )
+}
+
+fn format_event(event: &RecordEvent) -> Result<String> {
+    Ok(match event {
+        RecordEvent::Input { input } => format!("input {}", serde_json::to_string(input)?),
+        RecordEvent::Button { button, state } => {
+            format!(
+                "button {button} {}",
+                if *state { "pressed" } else { "released" }
+            )
+        }
+        RecordEvent::Led { led, state } => {
+            format!("led {led} {}", if *state { "on" } else { "off" })
+        }
+    })
+}
@@ -15,6 +15,10 @@ pub struct Args {
     #[argp(switch, short = 'r')]
     pub run: bool,
 
+    /// Check config and exit
+    #[argp(switch)]
+    pub check_config: bool,
+
     #[cfg(any(feature = "http", feature = "dbus"))]
     /// Service bindings
     #[argp(option, from_str_fn(FromStr::from_str))]
@@ -38,6 +42,31 @@ pub struct Args {
     #[cfg(feature = "tracing-subscriber")]
     #[argp(switch, short = 'j')]
     pub journal: bool,
+
+    /// Command to run instead of server
+    #[argp(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Server commands
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand)]
+pub enum Command {
+    /// Discover hardware
+    Probe(ProbeArgs),
+}
+
+/// Discover GPIO chips, HID gadgets and video devices
+#[derive(Debug, argp::FromArgs)]
+#[argp(subcommand, name = "probe")]
+pub struct ProbeArgs {
+    /// Root directory with dev and sys trees
+    #[argp(option, arg_name = "path", default = "\"/\".into()")]
+    pub root: PathBuf,
+
+    /// Print starter config instead of listing
+    #[argp(switch, short = 's')]
+    pub sample: bool,
 }
 
 impl Args {
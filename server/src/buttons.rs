@@ -1,23 +1,31 @@
-use crate::{log, ButtonId, Result};
-use gpiod::{Active, Bias, Chip, Drive, LineId, Options};
+use crate::{gpio::find_line, log, ButtonId, GpioLine, Result};
+use gpiod::{Active, Bias, Drive, Lines, Options, Output};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 use tokio::{spawn, sync::watch};
 
+#[cfg(feature = "relay")]
+use crate::relay::{
+    HidRelay, HidRelayConfig, HttpOutlet, HttpOutletConfig, SerialRelay, SerialRelayConfig,
+    SysfsLed, SysfsLedConfig,
+};
+
 /// Button interface
 pub struct Button {
     state_sender: watch::Sender<bool>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct ButtonConfig {
-    /// GPIO chip name
-    pub chip: String,
+    /// GPIO chip name (all chips are searched for line name when omitted)
+    #[serde(default)]
+    pub chip: Option<String>,
 
-    /// GPIO line offset
-    pub line: LineId,
+    /// GPIO line offset or name
+    #[serde(default)]
+    pub line: Option<GpioLine>,
 
-    /// GPIO line active state
+    /// Active state (low inverts relay and outlet outputs too)
     #[serde(default)]
     pub active: Active,
 
@@ -28,21 +36,130 @@ pub struct ButtonConfig {
     /// GPIO line drive
     #[serde(default)]
     pub drive: Drive,
+
+    /// Sysfs LED class device used as output
+    #[cfg(feature = "relay")]
+    #[serde(default)]
+    pub led: Option<SysfsLedConfig>,
+
+    /// USB-HID relay board
+    #[cfg(feature = "relay")]
+    #[serde(default)]
+    pub hid_relay: Option<HidRelayConfig>,
+
+    /// Serial port relay board
+    #[cfg(feature = "relay")]
+    #[serde(default)]
+    pub serial_relay: Option<SerialRelayConfig>,
+
+    /// Network PDU outlet
+    #[cfg(feature = "relay")]
+    #[serde(default)]
+    pub http: Option<HttpOutletConfig>,
+}
+
+impl ButtonConfig {
+    /// Check that exactly one output is configured
+    pub(crate) fn validate(&self) -> Result<()> {
+        #[cfg(feature = "relay")]
+        let relays = self.led.is_some() as usize
+            + self.hid_relay.is_some() as usize
+            + self.serial_relay.is_some() as usize
+            + self.http.is_some() as usize;
+        #[cfg(not(feature = "relay"))]
+        let relays = 0;
+
+        if self.line.is_some() as usize + relays != 1 {
+            return Err("Exactly one of line, led, hid_relay, serial_relay or http required")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Button output backend
+enum ButtonOutput {
+    Gpio(Lines<Output>),
+    #[cfg(feature = "relay")]
+    Led(SysfsLed),
+    #[cfg(feature = "relay")]
+    HidRelay(HidRelay),
+    #[cfg(feature = "relay")]
+    SerialRelay(SerialRelay),
+    #[cfg(feature = "relay")]
+    Http(Box<HttpOutlet>),
+}
+
+impl ButtonOutput {
+    /// Open output configured for button
+    async fn new(id: ButtonId, config: &ButtonConfig) -> Result<Self> {
+        config.validate()?;
+
+        if let Some(line) = &config.line {
+            let found = find_line(config.chip.as_deref(), line).await?;
+
+            let lines = found
+                .chip
+                .request_lines(
+                    Options::output(&[found.line])
+                        .active(config.active)
+                        .bias(config.bias)
+                        .drive(config.drive)
+                        .consumer(format!("{}-{}-button", env!("CARGO_PKG_NAME"), id)),
+                )
+                .await?;
+            return Ok(Self::Gpio(lines));
+        }
+
+        #[cfg(feature = "relay")]
+        if let Some(led) = &config.led {
+            return Ok(Self::Led(SysfsLed::new(led).await?));
+        }
+
+        #[cfg(feature = "relay")]
+        if let Some(relay) = &config.hid_relay {
+            return Ok(Self::HidRelay(HidRelay::new(relay).await?));
+        }
+
+        #[cfg(feature = "relay")]
+        if let Some(relay) = &config.serial_relay {
+            return Ok(Self::SerialRelay(SerialRelay::new(relay).await?));
+        }
+
+        #[cfg(feature = "relay")]
+        if let Some(outlet) = &config.http {
+            return Ok(Self::Http(HttpOutlet::new(outlet)?.into()));
+        }
+
+        Err("Button output required".into())
+    }
+
+    /// Apply button state
+    async fn set(&self, state: bool) -> Result<()> {
+        match self {
+            Self::Gpio(lines) => lines.set_values([state]).await?,
+            #[cfg(feature = "relay")]
+            Self::Led(led) => led.set(state).await?,
+            #[cfg(feature = "relay")]
+            Self::HidRelay(relay) => relay.set(state).await?,
+            #[cfg(feature = "relay")]
+            Self::SerialRelay(relay) => relay.set(state).await?,
+            #[cfg(feature = "relay")]
+            Self::Http(outlet) => outlet.set(state).await?,
+        }
+        Ok(())
+    }
 }
 
 impl Button {
     /// Instantiate new button
     pub async fn new(id: ButtonId, config: &ButtonConfig) -> Result<Self> {
-        let outputs = Chip::new(&config.chip)
-            .await?
-            .request_lines(
-                Options::output(&[config.line])
-                    .active(config.active)
-                    .bias(config.bias)
-                    .drive(config.drive)
-                    .consumer(format!("{}-{}-button", env!("CARGO_PKG_NAME"), id)),
-            )
-            .await?;
+        let output = ButtonOutput::new(id, config)
+            .await
+            .map_err(|error| format!("Button {id}: {error}"))?;
+
+        // GPIO lines handle active state by itself
+        let invert = config.active == Active::Low && !matches!(output, ButtonOutput::Gpio(_));
 
         //let delay = Duration::from_millis(config.delay as _);
 
@@ -55,9 +172,12 @@ impl Button {
                 while state_receiver.changed().await.is_ok() {
                     // Button state changed
                     let state = *state_receiver.borrow();
-                    if let Err(error) = outputs.set_values([state]).await {
+                    if let Err(error) = output.set(state != invert).await {
                         log::error!("Error when changing state: {}", error);
-                        break;
+                        // network and relay outputs may recover
+                        if matches!(output, ButtonOutput::Gpio(_)) {
+                            break;
+                        }
                     }
                 }
 
@@ -92,11 +212,11 @@ impl Button {
 #[educe(Deref)]
 pub struct Buttons {
     /// Buttons
-    buttons: HashMap<ButtonId, Button>,
+    buttons: HashMap<ButtonId, Arc<Button>>,
 }
 
 /// Buttons configuration
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 #[serde(transparent)]
 pub struct ButtonsConfig {
     /// Button configurations
@@ -106,12 +226,38 @@ pub struct ButtonsConfig {
 impl Buttons {
     /// Create buttons control service using specified config
     pub async fn new(config: &ButtonsConfig) -> Result<Self> {
+        Self::with_reused(config, HashMap::default()).await
+    }
+
+    /// Create buttons control service reusing already instantiated buttons
+    pub async fn with_reused(
+        config: &ButtonsConfig,
+        mut reused: HashMap<ButtonId, Arc<Button>>,
+    ) -> Result<Self> {
         let mut buttons = HashMap::default();
 
         for (id, config) in &config.buttons {
-            buttons.insert(*id, Button::new(*id, config).await?);
+            let button = if let Some(button) = reused.remove(id) {
+                button
+            } else {
+                Arc::new(Button::new(*id, config).await?)
+            };
+            buttons.insert(*id, button);
         }
 
         Ok(Self { buttons })
     }
+
+    /// Get buttons which config wasn't changed
+    pub fn unchanged(
+        &self,
+        old: &ButtonsConfig,
+        new: &ButtonsConfig,
+    ) -> HashMap<ButtonId, Arc<Button>> {
+        self.buttons
+            .iter()
+            .filter(|(id, _)| old.buttons.get(id) == new.buttons.get(id))
+            .map(|(id, button)| (*id, button.clone()))
+            .collect()
+    }
 }
@@ -0,0 +1,312 @@
+use crate::{
+    devices::device_path,
+    gpio::find_line,
+    sensors::{SensorSource, SYSFS},
+    BindAddr, ServerConfig,
+};
+use std::{collections::HashMap, path::Path};
+
+#[cfg(feature = "http")]
+use crate::HttpAddr;
+
+/// USB device controllers directory
+#[cfg(feature = "hid")]
+const UDC_CLASS: &str = "/sys/class/udc";
+
+impl ServerConfig {
+    /// Check config consistency against system
+    ///
+    /// Bindings from command-line are checked together with configured ones.
+    /// Returns found problems.
+    pub async fn check(&self, binds: &[BindAddr]) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        self.check_gpio(&mut problems).await;
+
+        let mut buttons = self.buttons.buttons.iter().collect::<Vec<_>>();
+        buttons.sort_by_key(|(id, _)| **id);
+        for (id, config) in buttons {
+            if let Err(error) = config.validate() {
+                problems.push(format!("Button {id}: {error}"));
+            }
+        }
+
+        let mut leds = self.leds.leds.iter().collect::<Vec<_>>();
+        leds.sort_by_key(|(id, _)| **id);
+        for (id, config) in leds {
+            if let Err(error) = config.validate() {
+                problems.push(format!("LED {id}: {error}"));
+            }
+        }
+
+        let mut sensors = self.sensors.sensors.iter().collect::<Vec<_>>();
+        sensors.sort_by_key(|(name, _)| *name);
+        for (name, config) in sensors {
+            if let Err(error) = SensorSource::resolve(Path::new(SYSFS), config).await {
+                problems.push(format!("Sensor {name}: {error}"));
+            }
+        }
+
+        if let Some(name) = &self.power.sensor {
+            if !self.sensors.sensors.contains_key(name) {
+                problems.push(format!("Power sensor {name} not configured"));
+            } else if self.power.on_threshold.is_none() {
+                problems.push("Power sensor requires on_threshold".into());
+            }
+        }
+
+        #[cfg(feature = "hid")]
+        self.check_hid(&mut problems);
+
+        #[cfg(feature = "video")]
+        if let Some(video) = &self.video {
+            if let Err(error) = video.validate() {
+                problems.push(format!("Video: {error}"));
+            }
+
+            let path = device_path(&video.device);
+            if !path.exists() {
+                problems.push(format!("Video device {} not found", path.display()));
+            }
+        }
+
+        let binds = binds.iter().chain(&self.binds).collect::<Vec<_>>();
+        for (index, bind) in binds.iter().enumerate() {
+            for other in &binds[..index] {
+                if binds_conflict(bind, other) {
+                    problems.push(format!("Binding {bind:?} conflicts with {other:?}"));
+                }
+            }
+        }
+
+        problems
+    }
+
+    async fn check_gpio(&self, problems: &mut Vec<String>) {
+        let lines = self
+            .buttons
+            .buttons
+            .iter()
+            .filter_map(|(id, config)| {
+                Some((format!("button {id}"), &config.chip, config.line.as_ref()?))
+            })
+            .chain(self.leds.leds.iter().filter_map(|(id, config)| {
+                Some((format!("LED {id}"), &config.chip, config.line.as_ref()?))
+            }));
+
+        let mut users = HashMap::<_, Vec<_>>::new();
+        let mut chips = Vec::new();
+
+        for (user, chip, line) in lines {
+            let key = if let (Some(chip), Some(offset)) = (chip, line.offset()) {
+                let path = device_path(chip);
+                chips.push(path.clone());
+                (path, offset)
+            } else {
+                // line names are resolved using chips
+                match find_line(chip.as_deref(), line).await {
+                    Ok(found) => (found.path, found.line),
+                    Err(error) => {
+                        problems.push(format!("GPIO line {line} of {user}: {error}"));
+                        continue;
+                    }
+                }
+            };
+            users.entry(key).or_default().push(user);
+        }
+
+        chips.sort();
+        chips.dedup();
+
+        for path in chips {
+            if !path.exists() {
+                problems.push(format!("GPIO chip {} not found", path.display()));
+            }
+        }
+
+        let mut users = users
+            .into_iter()
+            .filter(|(_, users)| users.len() > 1)
+            .collect::<Vec<_>>();
+        users.sort();
+
+        for ((path, line), mut users) in users {
+            users.sort();
+            problems.push(format!(
+                "GPIO line {line} of {} used by {}",
+                path.display(),
+                users.join(" and ")
+            ));
+        }
+    }
+
+    #[cfg(feature = "hid")]
+    fn check_hid(&self, problems: &mut Vec<String>) {
+        let Some(hid) = &self.hid else {
+            return;
+        };
+
+        let gadget = hid.gadget.as_ref();
+
+        let devices = [
+            (
+                "Keyboard",
+                &hid.keyboard,
+                gadget.map(|gadget| gadget.keyboard),
+            ),
+            ("Mouse", &hid.mouse, gadget.map(|gadget| gadget.mouse)),
+            (
+                "Relative mouse",
+                &hid.relative_mouse,
+                gadget.map(|gadget| gadget.relative_mouse),
+            ),
+        ];
+
+        for (name, device, created) in devices {
+            // gadget functions are created on start
+            if created.unwrap_or_default() {
+                continue;
+            }
+            if let Some(device) = device {
+                let path = device_path(device);
+                if !path.exists() {
+                    problems.push(format!("{name} device {} not found", path.display()));
+                }
+            }
+        }
+
+        if let Some(gadget) = gadget {
+            if !gadget.configfs.exists() {
+                problems.push(format!(
+                    "USB gadget configfs {} not found",
+                    gadget.configfs.display()
+                ));
+            }
+        }
+
+        for udc in [&hid.udc, &gadget.and_then(|gadget| gadget.udc.clone())]
+            .into_iter()
+            .flatten()
+        {
+            if !Path::new(UDC_CLASS).join(udc).exists() {
+                problems.push(format!("USB device controller {udc} not found"));
+            }
+        }
+    }
+}
+
+/// Check that bindings cannot be used together
+fn binds_conflict(a: &BindAddr, b: &BindAddr) -> bool {
+    match (a, b) {
+        #[cfg(feature = "http")]
+        (BindAddr::Http(a), BindAddr::Http(b)) => match (&a.addr, &b.addr) {
+            (HttpAddr::Addr(a), HttpAddr::Addr(b)) => {
+                a.port() == b.port()
+                    && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+            }
+            (HttpAddr::Path(a), HttpAddr::Path(b)) => a == b,
+            _ => false,
+        },
+        // service name can be owned only once per bus
+        #[cfg(feature = "dbus")]
+        (BindAddr::DBus(a), BindAddr::DBus(b)) => a == b,
+        #[allow(unreachable_patterns)]
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn check_gpio_lines() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [buttons.power]
+            chip = "/nonexistent/gpiochip9"
+            line = 1
+
+            [leds.power]
+            chip = "/nonexistent/gpiochip9"
+            line = 1
+
+            [leds.disk]
+            chip = "/nonexistent/gpiochip9"
+            line = 2
+
+            [leds.ether]
+            chip = "/nonexistent/gpiochip9"
+            line = "ETH_LED"
+            "#,
+        )
+        .unwrap();
+
+        let problems = config.check(&[]).await;
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("GPIO line \"ETH_LED\" of LED ether: "));
+        assert_eq!(
+            problems[1..],
+            [
+                "GPIO chip /nonexistent/gpiochip9 not found",
+                "GPIO line 1 of /nonexistent/gpiochip9 used by LED power and button power",
+            ]
+        );
+    }
+
+    #[cfg(feature = "relay")]
+    #[tokio::test]
+    async fn check_button_outputs() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [buttons.power]
+            http = { on = "http://pdu/on", off = "http://pdu/off" }
+
+            [buttons.reset]
+
+            [buttons.clear]
+            line = 1
+            led = { name = "relay1" }
+            "#,
+        )
+        .unwrap();
+
+        let problems = config.check(&[]).await;
+        let problems = problems
+            .iter()
+            .filter(|problem| problem.starts_with("Button "))
+            .collect::<Vec<_>>();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("Button reset: "));
+        assert!(problems[1].starts_with("Button clear: "));
+        assert!(problems[1].contains("Exactly one of line, led"));
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn check_binds() {
+        let binds = [
+            "http://0.0.0.0:8080".parse().unwrap(),
+            "http://127.0.0.1:8081".parse().unwrap(),
+            "http+unix:///run/ukvm.sock".parse().unwrap(),
+        ];
+
+        let config = ServerConfig::from_toml(
+            r#"
+            [[binds]]
+            proto = "http"
+            type = "tcp"
+            addr = "127.0.0.1:8080"
+
+            [[binds]]
+            proto = "http"
+            type = "unix"
+            addr = "/run/ukvm.sock"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.check(&binds).await.len(), 2);
+        assert!(config.check(&binds[1..2]).await.is_empty());
+    }
+}
@@ -1,10 +1,32 @@
-use crate::{log, ButtonId, DBusAddr, GracefulShutdown, LedId, Result, Server};
-use tokio::spawn;
-use zbus::{interface, Address, ConnectionBuilder};
+use crate::{
+    log, ButtonId, DBusAddr, Error, GracefulShutdown, LedId, LedMode, PowerState, Result,
+    SensorKind, SensorStatus, Server, ServerRef,
+};
+use tokio::{spawn, task::JoinHandle};
+use zbus::{interface, Address, Connection, ConnectionBuilder, Interface};
+
+/// Bus name of service
+const SERVICE_NAME: &str = "org.ukvm.Control";
+
+/// Get actual server instance for handling request
+fn upgrade(server: &ServerRef) -> zbus::fdo::Result<Server> {
+    Ok(server.upgrade()?)
+}
 
 struct Button {
     id: ButtonId,
-    server: Server,
+    server: ServerRef,
+}
+
+impl Button {
+    fn with<R>(&self, f: impl FnOnce(&crate::buttons::Button) -> R) -> zbus::fdo::Result<R> {
+        let server = upgrade(&self.server)?;
+        let button = server
+            .buttons()
+            .get(&self.id)
+            .ok_or_else(|| Error::from(format!("Button {} not found", self.id)))?;
+        Ok(f(button))
+    }
 }
 
 #[interface(name = "org.ukvm.Button")]
@@ -17,25 +39,31 @@ impl Button {
 
     /// Current state
     #[zbus(property)]
-    fn state(&self) -> bool {
-        self.server.buttons().get(&self.id).unwrap().state()
+    fn state(&self) -> zbus::fdo::Result<bool> {
+        self.with(|button| button.state())
     }
 
     /// Change state
     #[zbus(property)]
     fn set_state(&self, state: bool) -> zbus::Result<()> {
-        Ok(self
-            .server
-            .buttons()
-            .get(&self.id)
-            .unwrap()
-            .set_state(state)?)
+        Ok(self.with(|button| button.set_state(state))??)
     }
 }
 
 struct Led {
     id: LedId,
-    server: Server,
+    server: ServerRef,
+}
+
+impl Led {
+    fn with<R>(&self, f: impl FnOnce(&crate::leds::Led) -> R) -> zbus::fdo::Result<R> {
+        let server = upgrade(&self.server)?;
+        let led = server
+            .leds()
+            .get(&self.id)
+            .ok_or_else(|| Error::from(format!("LED {} not found", self.id)))?;
+        Ok(f(led))
+    }
 }
 
 #[interface(name = "org.ukvm.Led")]
@@ -48,11 +76,375 @@ impl Led {
 
     /// Current state
     #[zbus(property)]
-    fn state(&self) -> bool {
-        self.server.leds().get(&self.id).unwrap().state()
+    fn state(&self) -> zbus::fdo::Result<bool> {
+        self.with(|led| led.state())
+    }
+
+    /// Derived behavior (off, on, blinking or activity)
+    #[zbus(property)]
+    fn mode(&self) -> zbus::fdo::Result<LedMode> {
+        self.with(|led| led.pattern().mode)
+    }
+
+    /// Blinking period in milliseconds (0 when not blinking)
+    #[zbus(property)]
+    fn period(&self) -> zbus::fdo::Result<u32> {
+        self.with(|led| led.pattern().period)
+    }
+
+    /// Percent of on time over last sampling interval (-1 when sampling disabled)
+    #[zbus(property)]
+    fn duty(&self) -> zbus::fdo::Result<i16> {
+        self.with(|led| {
+            led.activity()
+                .map(|activity| activity.duty as _)
+                .unwrap_or(-1)
+        })
+    }
+
+    /// Edges over last sampling interval
+    #[zbus(property)]
+    fn interval_edges(&self) -> zbus::fdo::Result<u32> {
+        self.with(|led| {
+            led.activity()
+                .map(|activity| activity.edges)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Total number of edges
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn edges(&self) -> zbus::fdo::Result<u64> {
+        self.with(|led| led.counters().edges)
+    }
+
+    /// Total on time in milliseconds
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn on_time(&self) -> zbus::fdo::Result<u64> {
+        self.with(|led| led.counters().on_time)
+    }
+}
+
+struct Sensor {
+    name: String,
+    server: ServerRef,
+}
+
+impl Sensor {
+    fn reading(&self) -> zbus::fdo::Result<crate::SensorReading> {
+        let server = upgrade(&self.server)?;
+        let sensor = server
+            .sensors()
+            .get(&self.name)
+            .ok_or_else(|| Error::from(format!("Sensor {} not found", self.name)))?;
+        Ok(sensor.reading())
+    }
+}
+
+#[interface(name = "org.ukvm.Sensor")]
+impl Sensor {
+    /// Sensor name
+    #[zbus(property)]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Measured quantity
+    #[zbus(property)]
+    fn kind(&self) -> zbus::fdo::Result<SensorKind> {
+        Ok(self.reading()?.kind)
+    }
+
+    /// Measurement units
+    #[zbus(property)]
+    fn unit(&self) -> zbus::fdo::Result<&str> {
+        Ok(self.kind()?.unit())
+    }
+
+    /// Current value (NaN when cannot be read)
+    #[zbus(property)]
+    fn value(&self) -> zbus::fdo::Result<f64> {
+        Ok(self.reading()?.value.unwrap_or(f64::NAN))
+    }
+
+    /// Value state
+    #[zbus(property)]
+    fn status(&self) -> zbus::fdo::Result<SensorStatus> {
+        Ok(self.reading()?.status)
+    }
+}
+
+struct Power {
+    server: ServerRef,
+}
+
+#[interface(name = "org.ukvm.Power")]
+impl Power {
+    /// Host power state
+    #[zbus(property)]
+    fn state(&self) -> zbus::fdo::Result<PowerState> {
+        Ok(upgrade(&self.server)?.power().state())
+    }
+
+    /// Wait until host reaches power state
+    ///
+    /// Timeout is in milliseconds.
+    async fn wait(&self, state: PowerState, timeout: u32) -> zbus::fdo::Result<()> {
+        // server instance shouldn't be held while waiting
+        let wait = upgrade(&self.server)?
+            .wait_power(state, core::time::Duration::from_millis(timeout as _));
+        Ok(wait.await?)
     }
 }
 
+#[cfg(feature = "hid")]
+struct Hid {
+    server: ServerRef,
+}
+
+#[cfg(feature = "hid")]
+#[interface(name = "org.ukvm.Hid")]
+impl Hid {
+    /// USB host connection state
+    #[zbus(property)]
+    fn usb_state(&self) -> zbus::fdo::Result<crate::UsbState> {
+        Ok(upgrade(&self.server)?
+            .hid()
+            .map(|hid| hid.usb_state())
+            .unwrap_or_default())
+    }
+
+    /// Wake suspended host up
+    async fn wake_host(&self) -> zbus::fdo::Result<()> {
+        Ok(upgrade(&self.server)?
+            .hid()
+            .ok_or(Error::from("HID disabled"))?
+            .wake_host()
+            .await?)
+    }
+}
+
+#[cfg(feature = "video")]
+struct Video {
+    server: ServerRef,
+}
+
+#[cfg(feature = "video")]
+impl Video {
+    fn with<R: Default>(&self, f: impl FnOnce(&crate::Video) -> R) -> zbus::fdo::Result<R> {
+        Ok(upgrade(&self.server)?.video().map(f).unwrap_or_default())
+    }
+
+    fn mode(&self) -> zbus::fdo::Result<crate::VideoMode> {
+        self.with(|video| video.mode())
+    }
+}
+
+#[cfg(feature = "video")]
+#[interface(name = "org.ukvm.Video")]
+impl Video {
+    /// Frame width in pixels
+    #[zbus(property)]
+    fn width(&self) -> zbus::fdo::Result<u32> {
+        Ok(self.mode()?.width)
+    }
+
+    /// Frame height in pixels
+    #[zbus(property)]
+    fn height(&self) -> zbus::fdo::Result<u32> {
+        Ok(self.mode()?.height)
+    }
+
+    /// Frames per second
+    #[zbus(property)]
+    fn fps(&self) -> zbus::fdo::Result<u32> {
+        Ok(self.mode()?.fps)
+    }
+
+    /// Input signal state
+    #[zbus(property)]
+    fn signal(&self) -> zbus::fdo::Result<crate::VideoSignal> {
+        self.with(|video| video.signal())
+    }
+
+    /// Health state
+    #[zbus(property)]
+    fn health(&self) -> zbus::fdo::Result<crate::VideoHealth> {
+        self.with(|video| video.health())
+    }
+
+    /// Milliseconds since last captured frame (-1 when none)
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn last_frame_age(&self) -> zbus::fdo::Result<i64> {
+        Ok(self
+            .with(|video| video.last_frame_age())?
+            .map(|age| age.as_millis() as _)
+            .unwrap_or(-1))
+    }
+
+    /// Milliseconds since last frame content change (-1 when none)
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn frozen_for(&self) -> zbus::fdo::Result<i64> {
+        Ok(self
+            .with(|video| video.frozen_for())?
+            .map(|age| age.as_millis() as _)
+            .unwrap_or(-1))
+    }
+
+    /// Change capture mode
+    ///
+    /// Returns actually negotiated width, height and fps.
+    async fn set_mode(
+        &self,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> zbus::fdo::Result<(u32, u32, u32)> {
+        let mode = upgrade(&self.server)?
+            .video()
+            .ok_or(Error::from("Video disabled"))?
+            .set_mode(crate::VideoMode { width, height, fps })
+            .await?;
+        Ok((mode.width, mode.height, mode.fps))
+    }
+
+    /// Make screenshot of latest frame
+    ///
+    /// Zero width or height keeps aspect, empty crop means whole frame.
+    #[cfg(feature = "screenshot")]
+    async fn screenshot(
+        &self,
+        format: String,
+        width: u32,
+        height: u32,
+        crop: String,
+        quality: u8,
+    ) -> zbus::fdo::Result<Vec<u8>> {
+        let options = crate::ScreenshotOptions {
+            format: format
+                .parse()
+                .map_err(|_| Error::from(format!("Unknown image format {format}")))?,
+            width: (width > 0).then_some(width),
+            height: (height > 0).then_some(height),
+            crop: if crop.is_empty() {
+                None
+            } else {
+                Some(crop.try_into().map_err(Error::from)?)
+            },
+            quality: (quality > 0).then_some(quality),
+        };
+        Ok(upgrade(&self.server)?
+            .screenshot(options)
+            .await?
+            .as_ref()
+            .clone())
+    }
+}
+
+#[cfg(feature = "record")]
+struct Recorder {
+    server: ServerRef,
+}
+
+#[cfg(feature = "record")]
+#[interface(name = "org.ukvm.Recorder")]
+impl Recorder {
+    /// Path of current record (empty when not recording)
+    #[zbus(property)]
+    fn path(&self) -> zbus::fdo::Result<String> {
+        Ok(upgrade(&self.server)?
+            .recorder()
+            .and_then(|recorder| recorder.path())
+            .map(|path| path.display().to_string())
+            .unwrap_or_default())
+    }
+
+    /// Start recording
+    ///
+    /// Returns path of record file.
+    async fn start(&self) -> zbus::fdo::Result<String> {
+        Ok(upgrade(&self.server)?
+            .start_recording()
+            .await?
+            .display()
+            .to_string())
+    }
+
+    /// Stop recording
+    ///
+    /// Returns path of finished record file.
+    async fn stop(&self) -> zbus::fdo::Result<String> {
+        Ok(upgrade(&self.server)?
+            .stop_recording()
+            .await?
+            .display()
+            .to_string())
+    }
+}
+
+#[cfg(feature = "screen")]
+struct Screen {
+    server: ServerRef,
+}
+
+#[cfg(feature = "screen")]
+#[interface(name = "org.ukvm.Screen")]
+impl Screen {
+    /// Recognize text on screen
+    async fn text(&self) -> zbus::fdo::Result<String> {
+        Ok(upgrade(&self.server)?.screen_text().await?)
+    }
+
+    /// Wait until text on screen matches regular expression
+    ///
+    /// Timeout is in milliseconds. Returns matched text.
+    async fn wait(&self, regex: String, timeout: u32) -> zbus::fdo::Result<String> {
+        // server instance shouldn't be held while waiting
+        let wait = upgrade(&self.server)?
+            .wait_screen_text(&regex, core::time::Duration::from_millis(timeout as _));
+        Ok(wait.await?)
+    }
+}
+
+/// Objects registered on connection
+#[derive(Default)]
+struct Objects {
+    buttons: Vec<ButtonId>,
+    leds: Vec<LedId>,
+    sensors: Vec<String>,
+    #[cfg(feature = "hid")]
+    hid: Vec<()>,
+    #[cfg(feature = "video")]
+    video: Vec<()>,
+    #[cfg(feature = "record")]
+    recorder: Vec<()>,
+    #[cfg(feature = "screen")]
+    screen: Vec<()>,
+}
+
+/// Add and remove objects of some kind to match actual ones
+async fn update_objects<K: PartialEq, I: Interface>(
+    connection: &Connection,
+    registered: &mut Vec<K>,
+    actual: Vec<K>,
+    path: impl Fn(&K) -> String,
+    object: impl Fn(&K) -> I,
+) -> Result<()> {
+    let object_server = connection.object_server();
+
+    for key in registered.iter().filter(|key| !actual.contains(key)) {
+        object_server.remove::<I, _>(path(key)).await?;
+    }
+
+    for key in actual.iter().filter(|key| !registered.contains(key)) {
+        object_server.at(path(key), object(key)).await?;
+    }
+
+    *registered = actual;
+
+    Ok(())
+}
+
 impl Server {
     pub async fn spawn_dbus(&self, addr: &DBusAddr, gs: &GracefulShutdown) -> Result<()> {
         let gs = gs.clone();
@@ -74,29 +466,171 @@ impl Server {
             )?,
         };
 
-        let mut builder = builder.name("org.ukvm.Control")?;
+        let connection = builder.build().await?;
 
-        for id in self.buttons().keys().copied() {
-            builder = builder.serve_at(
-                format!("/org/ukvm/button/{}", id),
-                Button {
-                    id,
-                    server: self.clone(),
-                },
-            )?;
-        }
+        // objects should be in place before name is acquired
+        let mut objects = Objects::default();
+        self.update_dbus_objects(&connection, &mut objects).await?;
+        connection.request_name(SERVICE_NAME).await?;
+
+        let mut notifiers = self.notify_dbus(&connection).await?;
+
+        let mut server = self.downgrade();
+
+        spawn(async move {
+            // objects follow server instance when it reloaded
+            loop {
+                tokio::select! {
+                    _ = server.replaced() => {}
+                    _ = gs.shutdowned() => break,
+                }
 
-        for id in self.leds().keys().copied() {
-            builder = builder.serve_at(
-                format!("/org/ukvm/led/{}", id),
-                Led {
-                    id,
-                    server: self.clone(),
+                for notifier in notifiers.drain(..) {
+                    notifier.abort();
+                }
+
+                let instance = tokio::select! {
+                    instance = server.acquire() => match instance {
+                        Ok(instance) => instance,
+                        Err(_) => break,
+                    },
+                    _ = gs.shutdowned() => break,
+                };
+
+                if let Err(error) = instance
+                    .update_dbus_objects(&connection, &mut objects)
+                    .await
+                {
+                    log::error!("Error updating objects: {}", error);
+                }
+
+                match instance.notify_dbus(&connection).await {
+                    Ok(new_notifiers) => notifiers = new_notifiers,
+                    Err(error) => log::error!("Error watching objects: {}", error),
+                }
+            }
+
+            for notifier in notifiers {
+                notifier.abort();
+            }
+            drop(connection);
+            log::info!("Stopped");
+        });
+
+        log::info!("Started");
+
+        Ok(())
+    }
+
+    /// Register objects of actual subsystems
+    async fn update_dbus_objects(
+        &self,
+        connection: &Connection,
+        objects: &mut Objects,
+    ) -> Result<()> {
+        let server = self.downgrade();
+
+        update_objects(
+            connection,
+            &mut objects.buttons,
+            self.buttons().keys().copied().collect(),
+            |id| format!("/org/ukvm/button/{}", id),
+            |id| Button {
+                id: *id,
+                server: server.clone(),
+            },
+        )
+        .await?;
+
+        update_objects(
+            connection,
+            &mut objects.leds,
+            self.leds().keys().copied().collect(),
+            |id| format!("/org/ukvm/led/{}", id),
+            |id| Led {
+                id: *id,
+                server: server.clone(),
+            },
+        )
+        .await?;
+
+        update_objects(
+            connection,
+            &mut objects.sensors,
+            self.sensors().keys().cloned().collect(),
+            |name| format!("/org/ukvm/sensor/{}", name),
+            |name| Sensor {
+                name: name.clone(),
+                server: server.clone(),
+            },
+        )
+        .await?;
+
+        // always present
+        connection
+            .object_server()
+            .at(
+                "/org/ukvm/power",
+                Power {
+                    server: server.clone(),
                 },
-            )?;
-        }
+            )
+            .await?;
 
-        let connection = builder.build().await?;
+        #[cfg(feature = "hid")]
+        update_objects(
+            connection,
+            &mut objects.hid,
+            self.hid().map(|_| ()).into_iter().collect(),
+            |_| "/org/ukvm/hid".into(),
+            |_| Hid {
+                server: server.clone(),
+            },
+        )
+        .await?;
+
+        #[cfg(feature = "video")]
+        update_objects(
+            connection,
+            &mut objects.video,
+            self.video().map(|_| ()).into_iter().collect(),
+            |_| "/org/ukvm/video".into(),
+            |_| Video {
+                server: server.clone(),
+            },
+        )
+        .await?;
+
+        #[cfg(feature = "record")]
+        update_objects(
+            connection,
+            &mut objects.recorder,
+            self.recorder().map(|_| ()).into_iter().collect(),
+            |_| "/org/ukvm/recorder".into(),
+            |_| Recorder {
+                server: server.clone(),
+            },
+        )
+        .await?;
+
+        #[cfg(feature = "screen")]
+        update_objects(
+            connection,
+            &mut objects.screen,
+            self.screen().map(|_| ()).into_iter().collect(),
+            |_| "/org/ukvm/screen".into(),
+            |_| Screen {
+                server: server.clone(),
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Spawn property change notifiers
+    async fn notify_dbus(&self, connection: &Connection) -> Result<Vec<JoinHandle<()>>> {
+        let mut notifiers = Vec::new();
 
         for (id, inst) in self.buttons().iter() {
             let mut watch = inst.watch();
@@ -104,7 +638,7 @@ impl Server {
                 .object_server()
                 .interface::<_, Button>(format!("/org/ukvm/button/{}", id))
                 .await?;
-            spawn(async move {
+            notifiers.push(spawn(async move {
                 while watch.changed().await.is_ok() {
                     let sigctx = reference.signal_context();
                     let button = reference.get().await;
@@ -112,7 +646,7 @@ impl Server {
                         log::error!("Error notifying button state change: {}", error);
                     }
                 }
-            });
+            }));
         }
 
         for (id, inst) in self.leds().iter() {
@@ -121,7 +655,9 @@ impl Server {
                 .object_server()
                 .interface::<_, Led>(format!("/org/ukvm/led/{}", id))
                 .await?;
-            spawn(async move {
+            let pattern_reference = reference.clone();
+            let activity_reference = reference.clone();
+            notifiers.push(spawn(async move {
                 while watch.changed().await.is_ok() {
                     let led = reference.get().await;
                     let sigctx = reference.signal_context();
@@ -129,17 +665,165 @@ impl Server {
                         log::error!("Error notifying LED state change: {}", error);
                     }
                 }
-            });
+            }));
+
+            let mut watch = inst.watch_pattern();
+            notifiers.push(spawn(async move {
+                while watch.changed().await.is_ok() {
+                    let led = pattern_reference.get().await;
+                    let sigctx = pattern_reference.signal_context();
+                    if let Err(error) = led.mode_changed(sigctx).await {
+                        log::error!("Error notifying LED mode change: {}", error);
+                    }
+                    if let Err(error) = led.period_changed(sigctx).await {
+                        log::error!("Error notifying LED period change: {}", error);
+                    }
+                }
+            }));
+
+            let mut watch = inst.watch_activity();
+            notifiers.push(spawn(async move {
+                while watch.changed().await.is_ok() {
+                    let led = activity_reference.get().await;
+                    let sigctx = activity_reference.signal_context();
+                    if let Err(error) = led.duty_changed(sigctx).await {
+                        log::error!("Error notifying LED duty change: {}", error);
+                    }
+                    if let Err(error) = led.interval_edges_changed(sigctx).await {
+                        log::error!("Error notifying LED edges change: {}", error);
+                    }
+                }
+            }));
         }
 
-        spawn(async move {
-            let _ = gs.shutdowned().await;
-            drop(connection);
-            log::info!("Stopped");
-        });
+        for (name, inst) in self.sensors().iter() {
+            let mut watch = inst.watch();
+            let reference = connection
+                .object_server()
+                .interface::<_, Sensor>(format!("/org/ukvm/sensor/{}", name))
+                .await?;
+            notifiers.push(spawn(async move {
+                let mut status = watch.borrow().status;
+                while watch.changed().await.is_ok() {
+                    let sensor = reference.get().await;
+                    let sigctx = reference.signal_context();
+                    if let Err(error) = sensor.value_changed(sigctx).await {
+                        log::error!("Error notifying sensor value change: {}", error);
+                    }
+                    let new_status = watch.borrow().status;
+                    if new_status != status {
+                        status = new_status;
+                        if let Err(error) = sensor.status_changed(sigctx).await {
+                            log::error!("Error notifying sensor status change: {}", error);
+                        }
+                    }
+                }
+            }));
+        }
 
-        log::info!("Started");
+        {
+            let mut watch = self.power().watch();
+            let reference = connection
+                .object_server()
+                .interface::<_, Power>("/org/ukvm/power")
+                .await?;
+            notifiers.push(spawn(async move {
+                while watch.changed().await.is_ok() {
+                    let power = reference.get().await;
+                    let sigctx = reference.signal_context();
+                    if let Err(error) = power.state_changed(sigctx).await {
+                        log::error!("Error notifying power state change: {}", error);
+                    }
+                }
+            }));
+        }
 
-        Ok(())
+        #[cfg(feature = "hid")]
+        if let Some(udc) = self.hid().and_then(|hid| hid.udc()) {
+            let mut watch = udc.watch();
+            let reference = connection
+                .object_server()
+                .interface::<_, Hid>("/org/ukvm/hid")
+                .await?;
+            notifiers.push(spawn(async move {
+                while watch.changed().await.is_ok() {
+                    let hid = reference.get().await;
+                    let sigctx = reference.signal_context();
+                    if let Err(error) = hid.usb_state_changed(sigctx).await {
+                        log::error!("Error notifying USB state change: {}", error);
+                    }
+                }
+            }));
+        }
+
+        #[cfg(feature = "video")]
+        if let Some(video) = self.video() {
+            let mut watch = video.watch_mode();
+            let reference = connection
+                .object_server()
+                .interface::<_, Video>("/org/ukvm/video")
+                .await?;
+            notifiers.push(spawn(async move {
+                while watch.changed().await.is_ok() {
+                    let video = reference.get().await;
+                    let sigctx = reference.signal_context();
+                    if let Err(error) = video.width_changed(sigctx).await {
+                        log::error!("Error notifying video mode change: {}", error);
+                    }
+                    let _ = video.height_changed(sigctx).await;
+                    let _ = video.fps_changed(sigctx).await;
+                }
+            }));
+
+            let mut watch = video.watch_signal();
+            let reference = connection
+                .object_server()
+                .interface::<_, Video>("/org/ukvm/video")
+                .await?;
+            notifiers.push(spawn(async move {
+                while watch.changed().await.is_ok() {
+                    let video = reference.get().await;
+                    let sigctx = reference.signal_context();
+                    if let Err(error) = video.signal_changed(sigctx).await {
+                        log::error!("Error notifying video signal change: {}", error);
+                    }
+                }
+            }));
+
+            let mut watch = video.watch_health();
+            let reference = connection
+                .object_server()
+                .interface::<_, Video>("/org/ukvm/video")
+                .await?;
+            notifiers.push(spawn(async move {
+                while watch.changed().await.is_ok() {
+                    let video = reference.get().await;
+                    let sigctx = reference.signal_context();
+                    if let Err(error) = video.health_changed(sigctx).await {
+                        log::error!("Error notifying video health change: {}", error);
+                    }
+                }
+            }));
+        }
+
+        #[cfg(feature = "record")]
+        if let Some(recorder) = self.recorder() {
+            let mut watch = recorder.watch();
+            let reference = connection
+                .object_server()
+                .interface::<_, Recorder>("/org/ukvm/recorder")
+                .await?;
+            notifiers.push(spawn(async move {
+                while watch.changed().await.is_ok() {
+                    let recorder = reference.get().await;
+                    let sigctx = reference.signal_context();
+                    if let Err(error) = recorder.path_changed(sigctx).await {
+                        log::error!("Error notifying record path change: {}", error);
+                    }
+                }
+            }));
+        }
+
+        Ok(notifiers)
     }
 }
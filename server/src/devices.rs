@@ -0,0 +1,40 @@
+use crate::Result;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Get path of device node
+///
+/// Relative names are looked up in `/dev`.
+pub(crate) fn device_path(name: impl AsRef<Path>) -> PathBuf {
+    let name = name.as_ref();
+    if name.is_absolute() {
+        name.into()
+    } else {
+        Path::new("/dev").join(name)
+    }
+}
+
+/// List device nodes with prefix followed by number sorted by number
+pub(crate) async fn list_devices(dev: &Path, prefix: &str) -> Result<Vec<String>> {
+    let mut devices = Vec::<(u32, String)>::new();
+
+    let mut dir = match fs::read_dir(dev).await {
+        Ok(dir) => dir,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => Err(error)?,
+    };
+
+    while let Some(entry) = dir.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(number) = name
+            .strip_prefix(prefix)
+            .and_then(|number| number.parse::<u32>().ok())
+        {
+            devices.push((number, name));
+        }
+    }
+
+    devices.sort();
+
+    Ok(devices.into_iter().map(|(_, name)| name).collect())
+}
@@ -0,0 +1,531 @@
+use crate::{log, Result, Udc};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, time};
+
+/// Boot keyboard report descriptor (8 bytes input, 1 byte LEDs output)
+const KEYBOARD_REPORT_DESC: &[u8] = &[
+    0x05, 0x01, 0x09, 0x06, 0xa1, 0x01, 0x05, 0x07, 0x19, 0xe0, 0x29, 0xe7, 0x15, 0x00, 0x25, 0x01,
+    0x75, 0x01, 0x95, 0x08, 0x81, 0x02, 0x95, 0x01, 0x75, 0x08, 0x81, 0x03, 0x95, 0x05, 0x75, 0x01,
+    0x05, 0x08, 0x19, 0x01, 0x29, 0x05, 0x91, 0x02, 0x95, 0x01, 0x75, 0x03, 0x91, 0x03, 0x95, 0x06,
+    0x75, 0x08, 0x15, 0x00, 0x25, 0x65, 0x05, 0x07, 0x19, 0x00, 0x29, 0x65, 0x81, 0x00, 0xc0,
+];
+
+/// Absolute mouse report descriptor (8 buttons, 16-bit X/Y, 8-bit wheel)
+const MOUSE_REPORT_DESC: &[u8] = &[
+    0x05, 0x01, 0x09, 0x02, 0xa1, 0x01, 0x09, 0x01, 0xa1, 0x00, 0x05, 0x09, 0x19, 0x01, 0x29, 0x08,
+    0x15, 0x00, 0x25, 0x01, 0x95, 0x08, 0x75, 0x01, 0x81, 0x02, 0x05, 0x01, 0x09, 0x30, 0x09, 0x31,
+    0x16, 0x00, 0x00, 0x26, 0xff, 0x7f, 0x75, 0x10, 0x95, 0x02, 0x81, 0x02, 0x09, 0x38, 0x15, 0x81,
+    0x25, 0x7f, 0x75, 0x08, 0x95, 0x01, 0x81, 0x06, 0xc0, 0xc0,
+];
+
+/// Relative mouse report descriptor (8 buttons, 16-bit X/Y deltas, 8-bit wheel)
+const RELATIVE_MOUSE_REPORT_DESC: &[u8] = &[
+    0x05, 0x01, 0x09, 0x02, 0xa1, 0x01, 0x09, 0x01, 0xa1, 0x00, 0x05, 0x09, 0x19, 0x01, 0x29, 0x08,
+    0x15, 0x00, 0x25, 0x01, 0x95, 0x08, 0x75, 0x01, 0x81, 0x02, 0x05, 0x01, 0x09, 0x30, 0x09, 0x31,
+    0x16, 0x01, 0x80, 0x26, 0xff, 0x7f, 0x75, 0x10, 0x95, 0x02, 0x81, 0x06, 0x09, 0x38, 0x15, 0x81,
+    0x25, 0x7f, 0x75, 0x08, 0x95, 0x01, 0x81, 0x06, 0xc0, 0xc0,
+];
+
+/// English (US) strings language
+const LANG: &str = "0x409";
+
+/// Configuration name
+const CONFIG: &str = "c.1";
+
+/// USB gadget configuration
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct GadgetConfig {
+    /// Configfs USB gadgets root
+    #[serde(default = "default_configfs")]
+    pub configfs: PathBuf,
+
+    /// Device nodes directory
+    #[serde(default = "default_dev")]
+    pub dev: PathBuf,
+
+    /// Gadget name
+    #[serde(default = "default_name")]
+    pub name: String,
+
+    /// USB device controller (first available when omitted)
+    pub udc: Option<String>,
+
+    /// USB vendor ID
+    #[serde(default = "default_vendor_id")]
+    pub vendor_id: u16,
+
+    /// USB product ID
+    #[serde(default = "default_product_id")]
+    pub product_id: u16,
+
+    /// Manufacturer string
+    #[serde(default = "default_manufacturer")]
+    pub manufacturer: String,
+
+    /// Product string
+    #[serde(default = "default_product")]
+    pub product: String,
+
+    /// Serial number string
+    #[serde(default)]
+    pub serial: Option<String>,
+
+    /// Create keyboard function
+    #[serde(default = "default_true")]
+    pub keyboard: bool,
+
+    /// Create absolute mouse function
+    #[serde(default = "default_true")]
+    pub mouse: bool,
+
+    /// Create relative mouse function
+    #[serde(default)]
+    pub relative_mouse: bool,
+
+    /// Mass storage function
+    #[serde(default)]
+    pub mass_storage: Option<MassStorageConfig>,
+
+    /// Advertise remote wakeup capability
+    #[serde(default = "default_true")]
+    pub remote_wakeup: bool,
+}
+
+/// Mass storage function configuration
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct MassStorageConfig {
+    /// Backing image file
+    pub file: PathBuf,
+
+    /// Emulate CD-ROM
+    #[serde(default)]
+    pub cdrom: bool,
+
+    /// Read-only access
+    #[serde(default = "default_true")]
+    pub ro: bool,
+
+    /// Removable media
+    #[serde(default = "default_true")]
+    pub removable: bool,
+}
+
+fn default_configfs() -> PathBuf {
+    "/sys/kernel/config/usb_gadget".into()
+}
+
+fn default_dev() -> PathBuf {
+    "/dev".into()
+}
+
+fn default_name() -> String {
+    env!("CARGO_PKG_NAME").into()
+}
+
+fn default_vendor_id() -> u16 {
+    // Linux Foundation
+    0x1d6b
+}
+
+fn default_product_id() -> u16 {
+    // Multifunction Composite Gadget
+    0x0104
+}
+
+fn default_manufacturer() -> String {
+    env!("CARGO_PKG_NAME").into()
+}
+
+fn default_product() -> String {
+    "Micro KVM".into()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Gadget function kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GadgetFunction {
+    /// Keyboard HID function
+    Keyboard,
+
+    /// Absolute mouse HID function
+    Mouse,
+
+    /// Relative mouse HID function
+    RelativeMouse,
+
+    /// Mass storage function
+    MassStorage,
+}
+
+impl GadgetFunction {
+    /// Function directory name
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Keyboard => "hid.keyboard",
+            Self::Mouse => "hid.mouse",
+            Self::RelativeMouse => "hid.relative-mouse",
+            Self::MassStorage => "mass_storage.storage",
+        }
+    }
+}
+
+/// Composite USB gadget created via configfs
+///
+/// The gadget is unbound and removed when dropped.
+pub struct Gadget {
+    path: PathBuf,
+    dev: PathBuf,
+    udc: String,
+    functions: Vec<GadgetFunction>,
+    /// Group directory removal
+    remove_group: fn(&Path),
+}
+
+impl Gadget {
+    /// Create and bind gadget using config
+    pub async fn new(config: &GadgetConfig) -> Result<Self> {
+        Self::with_remove_group(config, remove_group).await
+    }
+
+    /// Create gadget using custom removal of group directories
+    async fn with_remove_group(config: &GadgetConfig, remove_group: fn(&Path)) -> Result<Self> {
+        let path = config.configfs.join(&config.name);
+
+        if fs::metadata(&path).await.is_ok() {
+            Err(format!("Gadget already exists: {}", path.display()))?;
+        }
+
+        let udc = if let Some(udc) = &config.udc {
+            udc.clone()
+        } else {
+            Udc::find().await?
+        };
+
+        let mut gadget = Self {
+            path,
+            dev: config.dev.clone(),
+            udc,
+            functions: Vec::new(),
+            remove_group,
+        };
+
+        if let Err(error) = gadget.setup(config).await {
+            gadget.teardown();
+            return Err(error);
+        }
+
+        log::info!("Gadget {} bound to {}", config.name, gadget.udc);
+
+        Ok(gadget)
+    }
+
+    /// USB device controller name
+    pub fn udc(&self) -> &str {
+        &self.udc
+    }
+
+    /// Check that function is created
+    pub fn has_function(&self, function: GadgetFunction) -> bool {
+        self.functions.contains(&function)
+    }
+
+    /// Get device node of HID function
+    ///
+    /// Waits a bit for the node to be created by udev.
+    pub async fn device(&self, function: GadgetFunction) -> Result<PathBuf> {
+        if !self.has_function(function) {
+            Err(format!("Gadget function {} missing", function.name()))?;
+        }
+
+        let dev = fs::read_to_string(
+            self.path
+                .join("functions")
+                .join(function.name())
+                .join("dev"),
+        )
+        .await?;
+        let minor = dev
+            .trim()
+            .split_once(':')
+            .map(|(_, minor)| minor)
+            .ok_or_else(|| format!("Invalid device number: {}", dev.trim()))?;
+        let path = self.dev.join(format!("hidg{minor}"));
+
+        for _ in 0..20 {
+            if fs::metadata(&path).await.is_ok() {
+                return Ok(path);
+            }
+            time::sleep(time::Duration::from_millis(100)).await;
+        }
+
+        Err(format!("Device node missing: {}", path.display()))?
+    }
+
+    async fn setup(&mut self, config: &GadgetConfig) -> Result<()> {
+        let path = &self.path.clone();
+
+        fs::create_dir(path).await?;
+
+        write_attr(path, "idVendor", format!("{:#06x}", config.vendor_id)).await?;
+        write_attr(path, "idProduct", format!("{:#06x}", config.product_id)).await?;
+        write_attr(path, "bcdDevice", "0x0100").await?;
+        write_attr(path, "bcdUSB", "0x0200").await?;
+
+        let strings = path.join("strings").join(LANG);
+        fs::create_dir_all(&strings).await?;
+        write_attr(&strings, "manufacturer", &config.manufacturer).await?;
+        write_attr(&strings, "product", &config.product).await?;
+        if let Some(serial) = &config.serial {
+            write_attr(&strings, "serialnumber", serial).await?;
+        }
+
+        let conf = path.join("configs").join(CONFIG);
+        let conf_strings = conf.join("strings").join(LANG);
+        fs::create_dir_all(&conf_strings).await?;
+        write_attr(&conf_strings, "configuration", "HID").await?;
+        write_attr(&conf, "MaxPower", "250").await?;
+        write_attr(
+            &conf,
+            "bmAttributes",
+            if config.remote_wakeup { "0xa0" } else { "0x80" },
+        )
+        .await?;
+
+        if config.keyboard {
+            self.add_hid(GadgetFunction::Keyboard, 1, 1, 8, KEYBOARD_REPORT_DESC)
+                .await?;
+        }
+
+        if config.mouse {
+            self.add_hid(GadgetFunction::Mouse, 2, 0, 6, MOUSE_REPORT_DESC)
+                .await?;
+        }
+
+        if config.relative_mouse {
+            self.add_hid(
+                GadgetFunction::RelativeMouse,
+                2,
+                0,
+                6,
+                RELATIVE_MOUSE_REPORT_DESC,
+            )
+            .await?;
+        }
+
+        if let Some(storage) = &config.mass_storage {
+            let func = self.add_function(GadgetFunction::MassStorage).await?;
+            let lun = func.join("lun.0");
+            fs::create_dir_all(&lun).await?;
+            write_attr(&lun, "cdrom", bool_attr(storage.cdrom)).await?;
+            write_attr(&lun, "ro", bool_attr(storage.ro)).await?;
+            write_attr(&lun, "removable", bool_attr(storage.removable)).await?;
+            write_attr(&lun, "file", storage.file.as_os_str().as_encoded_bytes()).await?;
+            self.link_function(GadgetFunction::MassStorage).await?;
+        }
+
+        write_attr(path, "UDC", &self.udc).await?;
+
+        Ok(())
+    }
+
+    async fn add_function(&mut self, function: GadgetFunction) -> Result<PathBuf> {
+        let func = self.path.join("functions").join(function.name());
+        fs::create_dir_all(&func).await?;
+        self.functions.push(function);
+        Ok(func)
+    }
+
+    async fn link_function(&self, function: GadgetFunction) -> Result<()> {
+        fs::symlink(
+            self.path.join("functions").join(function.name()),
+            self.path.join("configs").join(CONFIG).join(function.name()),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn add_hid(
+        &mut self,
+        function: GadgetFunction,
+        protocol: u8,
+        subclass: u8,
+        report_length: usize,
+        report_desc: &[u8],
+    ) -> Result<()> {
+        let func = self.add_function(function).await?;
+        write_attr(&func, "protocol", protocol.to_string()).await?;
+        write_attr(&func, "subclass", subclass.to_string()).await?;
+        write_attr(&func, "report_length", report_length.to_string()).await?;
+        write_attr(&func, "report_desc", report_desc).await?;
+        self.link_function(function).await
+    }
+
+    /// Unbind and remove gadget
+    fn teardown(&mut self) {
+        use std::fs;
+
+        let path = &self.path;
+        let remove_group = self.remove_group;
+
+        if let Err(error) = fs::write(path.join("UDC"), "\n") {
+            if error.kind() != ErrorKind::NotFound {
+                log::warn!("Error when unbinding gadget: {error}");
+            }
+        }
+
+        let conf = path.join("configs").join(CONFIG);
+
+        for function in self.functions.drain(..).rev() {
+            let _ = fs::remove_file(conf.join(function.name()));
+            if function == GadgetFunction::MassStorage {
+                // default LUN group can't be removed so only backing file is released
+                let file = path
+                    .join("functions")
+                    .join(function.name())
+                    .join("lun.0/file");
+                if let Err(error) = fs::write(&file, "\n") {
+                    if error.kind() != ErrorKind::NotFound {
+                        log::warn!("Error when releasing {}: {error}", file.display());
+                    }
+                }
+            }
+            remove_group(&path.join("functions").join(function.name()));
+        }
+
+        remove_group(&conf.join("strings").join(LANG));
+        remove_group(&conf);
+        remove_group(&path.join("strings").join(LANG));
+        remove_group(path);
+
+        log::info!("Gadget {} removed", path.display());
+    }
+}
+
+impl Drop for Gadget {
+    fn drop(&mut self) {
+        self.teardown();
+    }
+}
+
+fn bool_attr(value: bool) -> &'static str {
+    if value {
+        "1"
+    } else {
+        "0"
+    }
+}
+
+async fn write_attr(dir: &Path, name: &str, value: impl AsRef<[u8]>) -> Result<()> {
+    fs::write(dir.join(name), value)
+        .await
+        .map_err(|error| format!("Unable to write {}: {error}", dir.join(name).display()).into())
+}
+
+/// Remove configfs group directory
+///
+/// Attributes vanish together with groups on configfs.
+fn remove_group(path: &Path) {
+    if let Err(error) = std::fs::remove_dir(path) {
+        if error.kind() != ErrorKind::NotFound {
+            log::warn!("Error when removing {}: {error}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_config(root: &Path) -> GadgetConfig {
+        GadgetConfig {
+            configfs: root.into(),
+            dev: root.into(),
+            name: "test".into(),
+            udc: Some("dummy_udc.0".into()),
+            vendor_id: default_vendor_id(),
+            product_id: default_product_id(),
+            manufacturer: default_manufacturer(),
+            product: default_product(),
+            serial: Some("0123".into()),
+            keyboard: true,
+            mouse: true,
+            relative_mouse: false,
+            mass_storage: Some(MassStorageConfig {
+                file: "/tmp/disk.img".into(),
+                cdrom: true,
+                ro: true,
+                removable: true,
+            }),
+            remote_wakeup: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn gadget_create_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        // attributes are plain files here
+        let gadget = Gadget::with_remove_group(&test_config(root), |path| {
+            // default groups are removed together with parent
+            assert!(!path.ends_with("lun.0"), "{}", path.display());
+            if path.ends_with("mass_storage.storage") {
+                assert_eq!(
+                    std::fs::read_to_string(path.join("lun.0/file")).unwrap(),
+                    "\n"
+                );
+            }
+            let _ = std::fs::remove_dir_all(path);
+        })
+        .await
+        .unwrap();
+        let path = root.join("test");
+
+        assert_eq!(
+            std::fs::read_to_string(path.join("idVendor")).unwrap(),
+            "0x1d6b"
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.join("strings/0x409/serialnumber")).unwrap(),
+            "0123"
+        );
+        assert_eq!(
+            std::fs::read(path.join("functions/hid.keyboard/report_desc")).unwrap(),
+            KEYBOARD_REPORT_DESC
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.join("functions/mass_storage.storage/lun.0/cdrom"))
+                .unwrap(),
+            "1"
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.join("configs/c.1/bmAttributes")).unwrap(),
+            "0xa0"
+        );
+        assert!(path.join("configs/c.1/hid.mouse").is_symlink());
+        assert!(!path.join("configs/c.1/hid.relative-mouse").exists());
+        assert_eq!(
+            std::fs::read_to_string(path.join("UDC")).unwrap(),
+            "dummy_udc.0"
+        );
+
+        std::fs::write(path.join("functions/hid.mouse/dev"), "240:1\n").unwrap();
+        std::fs::write(root.join("hidg1"), "").unwrap();
+        assert_eq!(
+            gadget.device(GadgetFunction::Mouse).await.unwrap(),
+            root.join("hidg1")
+        );
+        assert!(gadget.device(GadgetFunction::RelativeMouse).await.is_err());
+
+        drop(gadget);
+
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    devices::{device_path, list_devices},
+    log, Result,
+};
+use core::fmt::{self, Display};
+use gpiod::{Chip, LineId};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// GPIO line by offset or name
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum GpioLine {
+    /// Line offset on chip
+    Offset(LineId),
+
+    /// Line name
+    Name(String),
+}
+
+impl Display for GpioLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Offset(offset) => offset.fmt(f),
+            Self::Name(name) => write!(f, "\"{name}\""),
+        }
+    }
+}
+
+impl GpioLine {
+    /// Get line offset when it's specified directly
+    pub fn offset(&self) -> Option<LineId> {
+        match self {
+            Self::Offset(offset) => Some(*offset),
+            Self::Name(_) => None,
+        }
+    }
+}
+
+/// Found GPIO line
+pub struct FoundLine {
+    /// Chip device path
+    pub path: PathBuf,
+
+    /// Opened chip
+    pub chip: Chip,
+
+    /// Line offset
+    pub line: LineId,
+}
+
+/// Open chip and find line on it
+///
+/// When chip is not specified line name is searched on all chips.
+pub async fn find_line(chip: Option<&str>, line: &GpioLine) -> Result<FoundLine> {
+    find_line_in(Path::new("/dev"), chip, line).await
+}
+
+async fn find_line_in(dev: &Path, chip: Option<&str>, line: &GpioLine) -> Result<FoundLine> {
+    let name = match line {
+        GpioLine::Offset(offset) => {
+            let Some(chip) = chip else {
+                return Err(format!("GPIO chip required for line {offset}"))?;
+            };
+            let path = device_path(chip);
+            return Ok(FoundLine {
+                chip: Chip::new(&path).await?,
+                line: *offset,
+                path,
+            });
+        }
+        GpioLine::Name(name) => name,
+    };
+
+    let paths = if let Some(chip) = chip {
+        vec![device_path(chip)]
+    } else {
+        list_devices(dev, "gpiochip")
+            .await?
+            .into_iter()
+            .map(|device| dev.join(device))
+            .collect()
+    };
+
+    let mut found = Vec::new();
+
+    for path in paths {
+        let chip = match Chip::new(&path).await {
+            Ok(chip) => chip,
+            // other chips may still have line
+            Err(error) if chip.is_none() => {
+                log::warn!("Unable to open GPIO chip {}: {error}", path.display());
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
+
+        for offset in 0..chip.num_lines() {
+            if chip.line_info(offset).await?.name == *name {
+                found.push((path.clone(), offset));
+            }
+        }
+    }
+
+    match found.len() {
+        0 => Err(if let Some(chip) = chip {
+            format!("GPIO line \"{name}\" not found on {chip}")
+        } else {
+            format!("GPIO line \"{name}\" not found on any chip")
+        })?,
+        1 => {
+            let (path, line) = found.pop().unwrap();
+            Ok(FoundLine {
+                chip: Chip::new(&path).await?,
+                path,
+                line,
+            })
+        }
+        _ => {
+            let lines = found
+                .iter()
+                .map(|(path, line)| format!("{} line {line}", path.display()))
+                .collect::<Vec<_>>();
+            Err(format!(
+                "GPIO line \"{name}\" is ambiguous: found {}",
+                lines.join(", ")
+            ))?
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        line: GpioLine,
+    }
+
+    #[test]
+    fn parse_line() {
+        let config: Config = toml::from_str("line = 23").unwrap();
+        assert_eq!(config.line, GpioLine::Offset(23));
+
+        let config: Config = toml::from_str("line = \"PWR_BTN\"").unwrap();
+        assert_eq!(config.line, GpioLine::Name("PWR_BTN".into()));
+        assert_eq!(config.line.to_string(), "\"PWR_BTN\"");
+    }
+
+    #[tokio::test]
+    async fn find_line_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let dev = dir.path();
+
+        let error = find_line_in(dev, None, &GpioLine::Offset(1))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Other error: GPIO chip required for line 1"
+        );
+
+        let error = find_line_in(dev, None, &GpioLine::Name("PWR_BTN".into()))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Other error: GPIO line \"PWR_BTN\" not found on any chip"
+        );
+    }
+}
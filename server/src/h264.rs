@@ -0,0 +1,228 @@
+use crate::{
+    log,
+    m2m::{M2mDevice, M2mEncoder},
+    Result,
+};
+use jpeg_decoder::{ColorTransform, Decoder, PixelFormat};
+use openh264::{
+    encoder::{
+        BitRate, Encoder, EncoderConfig, FrameRate, Profile, RateControlMode, UsageType, VuiConfig,
+    },
+    formats::{YUVBuffer, YUVSource},
+    OpenH264API,
+};
+
+/// H.264 encoder of JPEG frames
+///
+/// Hardware encoder is used when available, software one otherwise.
+pub struct H264Encoder {
+    bitrate: u32,
+    fps: u32,
+    /// Hardware encoder device which isn't failed yet
+    device: Option<M2mDevice>,
+    hardware: Option<M2mEncoder>,
+    software: Option<Encoder>,
+    keyframe: bool,
+}
+
+impl H264Encoder {
+    /// Create encoder with target bitrate in kbit/s
+    ///
+    /// Encoders are opened on first frame when its size is known.
+    pub fn new(bitrate: u32, fps: u32, device: Option<M2mDevice>) -> Self {
+        Self {
+            bitrate,
+            fps,
+            device,
+            hardware: None,
+            software: None,
+            keyframe: false,
+        }
+    }
+
+    /// Request key frame for next encoded frame
+    pub fn force_keyframe(&mut self) {
+        self.keyframe = true;
+    }
+
+    /// Encode JPEG frame to H.264 access unit in Annex B format
+    ///
+    /// Empty data means that frame was skipped by rate control.
+    pub fn encode(&mut self, jpeg: &[u8]) -> Result<Vec<u8>> {
+        let yuv = decode_jpeg(jpeg)?;
+
+        if let Some(device) = &self.device {
+            let size = yuv.dimensions();
+
+            if self.hardware.as_ref().map(|encoder| encoder.size()) != Some(size) {
+                // release previous encoder before reopening device
+                self.hardware = None;
+
+                match M2mEncoder::open(device, size, self.bitrate, self.fps) {
+                    Ok(encoder) => {
+                        log::info!("Use hardware H.264 encoder {}", device.path.display());
+                        self.hardware = Some(encoder);
+                    }
+                    Err(error) => {
+                        log::warn!("Unable to use hardware H.264 encoder: {error}");
+                        self.device = None;
+                    }
+                }
+            }
+
+            if let Some(encoder) = &mut self.hardware {
+                match encoder.encode(&yuv, core::mem::take(&mut self.keyframe)) {
+                    Ok(data) => return Ok(data),
+                    Err(error) => {
+                        log::warn!("Hardware H.264 encoder failed: {error}");
+                        self.hardware = None;
+                        self.device = None;
+                    }
+                }
+            }
+        }
+
+        if self.software.is_none() {
+            self.software = Some(software_encoder(self.bitrate, self.fps)?);
+            log::info!("Use software H.264 encoder");
+        }
+
+        let encoder = self.software.as_mut().unwrap();
+
+        if core::mem::take(&mut self.keyframe) {
+            encoder.force_intra_frame();
+        }
+
+        Ok(encoder
+            .encode(&yuv)
+            .map_err(|error| format!("Error when encoding H.264: {error}"))?
+            .to_vec())
+    }
+}
+
+/// Create openh264 encoder with target bitrate in kbit/s
+fn software_encoder(bitrate: u32, fps: u32) -> Result<Encoder> {
+    let config = EncoderConfig::new()
+        .profile(Profile::Baseline)
+        .usage_type(UsageType::ScreenContentRealTime)
+        // not supported for screen content
+        .adaptive_quantization(false)
+        .background_detection(false)
+        .rate_control_mode(RateControlMode::Bitrate)
+        .bitrate(BitRate::from_bps(bitrate * 1000))
+        .max_frame_rate(FrameRate::from_hz(fps.max(1) as _))
+        // JPEG uses full range BT.601 colors
+        .vui(VuiConfig::bt601().full_range(true));
+
+    Ok(Encoder::with_api_config(OpenH264API::from_source(), config)
+        .map_err(|error| format!("Unable to create H.264 encoder: {error}"))?)
+}
+
+/// Decode JPEG frame to planar YUV 4:2:0
+fn decode_jpeg(jpeg: &[u8]) -> Result<YUVBuffer> {
+    let mut decoder = Decoder::new(jpeg);
+
+    // RGB transform only interleaves components so YCbCr is kept as is
+    decoder.set_color_transform(ColorTransform::RGB);
+
+    let data = decoder
+        .decode()
+        .map_err(|error| format!("Error when decoding JPEG: {error}"))?;
+
+    let info = decoder.info().ok_or("Missing JPEG info")?;
+
+    let channels = match info.pixel_format {
+        PixelFormat::L8 => 1,
+        PixelFormat::RGB24 => 3,
+        format => Err(format!("Unsupported JPEG pixel format: {format:?}"))?,
+    };
+
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    Ok(YUVBuffer::from_vec(
+        to_i420(&data, channels, width, height),
+        width & !1,
+        height & !1,
+    ))
+}
+
+/// Convert interleaved YCbCr (or grayscale) to I420
+///
+/// Odd width and height are cropped because chroma is subsampled.
+fn to_i420(data: &[u8], channels: usize, width: usize, height: usize) -> Vec<u8> {
+    let (out_width, out_height) = (width & !1, height & !1);
+    let luma_size = out_width * out_height;
+    let mut yuv = vec![128; luma_size * 3 / 2];
+    let (luma, chroma) = yuv.split_at_mut(luma_size);
+    let (cb, cr) = chroma.split_at_mut(luma_size / 4);
+
+    for y in 0..out_height {
+        let line = &data[y * width * channels..];
+        for x in 0..out_width {
+            luma[y * out_width + x] = line[x * channels];
+        }
+    }
+
+    if channels == 3 {
+        let chroma_width = out_width / 2;
+        for y in 0..out_height / 2 {
+            let line0 = &data[y * 2 * width * 3..];
+            let line1 = &data[(y * 2 + 1) * width * 3..];
+            for x in 0..chroma_width {
+                let p = x * 2 * 3;
+                let average = |c: usize| {
+                    ((line0[p + c] as u16
+                        + line0[p + 3 + c] as u16
+                        + line1[p + c] as u16
+                        + line1[p + 3 + c] as u16
+                        + 2)
+                        / 4) as u8
+                };
+                cb[y * chroma_width + x] = average(1);
+                cr[y * chroma_width + x] = average(2);
+            }
+        }
+    }
+
+    yuv
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jpeg::{self, RawFormat, RawGeometry};
+
+    #[test]
+    fn convert_i420() {
+        // 3x2 image of YCbCr pixels
+        let data = [
+            10, 100, 200, 20, 110, 210, 30, 0, 0, //
+            40, 120, 220, 50, 130, 230, 60, 0, 0,
+        ];
+        assert_eq!(
+            to_i420(&data, 3, 3, 2),
+            [10, 20, 40, 50, 115, 215] as [u8; 6]
+        );
+    }
+
+    #[test]
+    fn encode_frames() {
+        let geometry = RawGeometry {
+            format: RawFormat::Yuyv,
+            width: 64,
+            height: 48,
+            stride: 128,
+        };
+        let frame = jpeg::encode(geometry, &vec![128; 128 * 48], 80).unwrap();
+
+        let mut encoder = H264Encoder::new(500, 30, None);
+        let data = encoder.encode(&frame).unwrap();
+
+        // starts with SPS in Annex B format
+        assert!(data.starts_with(&[0, 0, 0, 1]));
+        assert_eq!(data[4] & 0x1f, 7);
+
+        encoder.force_keyframe();
+        assert!(!encoder.encode(&frame).unwrap().is_empty());
+    }
+}
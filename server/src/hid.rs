@@ -1,14 +1,32 @@
-use crate::{log, Result};
-use hidg::{Class, Device, Keyboard, Mouse, StateChange, ValueChange};
+use crate::{log, Gadget, GadgetConfig, GadgetFunction, Result, Udc, UdcWaker};
+use core::future::pending;
+use hidg::{Class, Device, Keyboard, Mouse, MouseInput, MouseOutput, StateChange, ValueChange};
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, path::Path};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 use tokio::{
     select, spawn,
     sync::{mpsc, watch},
+    time,
 };
 
+/// Input report writing timeout
+const WRITE_TIMEOUT: time::Duration = time::Duration::from_millis(500);
+
+/// Device reopening interval
+const REOPEN_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+/// Initial delay before retrying timed out input report
+const RETRY_DELAY_MIN: time::Duration = time::Duration::from_millis(50);
+
+/// Maximum delay before retrying timed out input report
+const RETRY_DELAY_MAX: time::Duration = time::Duration::from_secs(2);
+
+pub use crate::udc::UsbState;
 pub use hidg::MouseInputChange as MouseStateChange;
-pub use ukvm_core::hid::{Button, Key, KeyboardState, Led, MouseState};
+pub use ukvm_core::hid::{Button, Key, KeyboardState, Led, MouseMode, MouseState};
 
 /// Keyboard key state change event
 pub type KeyStateChange = StateChange<Key>;
@@ -25,13 +43,85 @@ pub type PointerValueChange = ValueChange<(i16, i16)>;
 /// Mouse wheel state change event
 pub type WheelValueChange = ValueChange<i8>;
 
-#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Default)]
 pub struct HidConfig {
     /// Keyboard device
     pub keyboard: Option<String>,
 
-    /// Mouse device
+    /// Absolute mouse device
     pub mouse: Option<String>,
+
+    /// Relative mouse device
+    pub relative_mouse: Option<String>,
+
+    /// USB device controller to watch
+    ///
+    /// Controller of gadget or first available one when omitted.
+    pub udc: Option<String>,
+
+    /// Wake host up on input while suspended
+    #[serde(default)]
+    pub wakeup_on_input: bool,
+
+    /// Create USB gadget via configfs
+    ///
+    /// Devices of created gadget functions take precedence.
+    pub gadget: Option<GadgetConfig>,
+}
+
+/// Relative mouse HID class
+///
+/// Uses the same report layout as [`Mouse`] but pointer and wheel
+/// values are treated as motion deltas.
+#[derive(Clone, Copy, Debug)]
+pub struct RelativeMouse;
+
+impl Class for RelativeMouse {
+    type Input = MouseInput;
+    type Output = MouseOutput;
+
+    fn input(&self) -> Self::Input {
+        Self::Input::default()
+    }
+
+    fn output(&self) -> Self::Output {
+        Self::Output::default()
+    }
+}
+
+impl Display for RelativeMouse {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("relative-mouse")
+    }
+}
+
+/// Device class specific report handling
+pub trait HidClass: Class + Display + Copy + Send + Sync + 'static {
+    /// Update pending input report after the sent one has been written
+    fn sent(&self, _pending: &mut Self::Input, _sent: &Self::Input) {}
+
+    /// Update pending input report which can't be sent to unconfigured host
+    fn discard(&self, _pending: &mut Self::Input) {}
+}
+
+impl HidClass for Keyboard {}
+
+impl HidClass for Mouse {}
+
+impl HidClass for RelativeMouse {
+    fn sent(&self, pending: &mut Self::Input, sent: &Self::Input) {
+        // motion deltas must be sent only once
+        let (x, y) = pending.pointer();
+        let (sx, sy) = sent.pointer();
+        pending.set_pointer((x.saturating_sub(sx), y.saturating_sub(sy)));
+        pending.set_wheel(pending.wheel().saturating_sub(sent.wheel()));
+    }
+
+    fn discard(&self, pending: &mut Self::Input) {
+        // stale motion must not be replayed after enumeration
+        pending.set_pointer((0, 0));
+        pending.set_wheel(0);
+    }
 }
 
 pub struct HidIo<C: Class> {
@@ -39,40 +129,142 @@ pub struct HidIo<C: Class> {
     output_receiver: watch::Receiver<C::Output>,
 }
 
-impl<C: Class> HidIo<C> {
-    async fn new(class: C, path: impl AsRef<Path>) -> Result<Self>
+impl<C: HidClass> HidIo<C> {
+    async fn new(
+        class: C,
+        path: impl AsRef<Path>,
+        mut usb_state: Option<watch::Receiver<UsbState>>,
+        waker: Option<UdcWaker>,
+    ) -> Result<Self>
     where
-        C: Display + Copy + Send + Sync + 'static,
         C::Input: AsRef<[u8]> + Copy + Send + Sync + 'static,
         C::Output: AsMut<[u8]> + Copy + Send + Sync + core::fmt::Debug + 'static,
     {
-        let mut device = Device::<C>::open(path.as_ref()).await?;
+        let path = path.as_ref().to_owned();
+        let mut device = Some(Device::<C>::open(&path).await?);
 
         let (input_sender, mut input_receiver) = watch::channel(class.input());
         let (output_sender, output_receiver) = watch::channel(class.output());
 
+        let pending_sender = input_sender.clone();
+
         spawn(async move {
             log::debug!("{class}: Initialize receiving events");
 
             let mut output = class.output();
+            // Input report which isn't sent yet
+            let mut unsent = false;
+            // Remote wakeup already signaled
+            let mut woken = false;
+            // Timed out input report retrying
+            let mut retry_at: Option<time::Instant> = None;
+            let mut retry_delay = RETRY_DELAY_MIN;
+            let mut timeouts = 0usize;
 
             loop {
+                let state = usb_state.as_ref().map(|state| *state.borrow());
+                let configured = state.map(|state| state.is_configured()).unwrap_or(true);
+
+                if unsent && !woken && state == Some(UsbState::Suspended) {
+                    if let Some(waker) = &waker {
+                        woken = true;
+                        if let Err(error) = waker.wake().await {
+                            log::warn!("{class}: {error}");
+                        }
+                    }
+                }
+
+                if configured && device.is_some() && unsent && retry_at.is_none() {
+                    if output_sender.is_closed() {
+                        break;
+                    }
+                    let report = *input_receiver.borrow_and_update();
+                    match time::timeout(WRITE_TIMEOUT, device.as_mut().unwrap().input(&report))
+                        .await
+                    {
+                        Ok(Ok(_)) => {
+                            if timeouts > 0 {
+                                log::info!("{class}: Input sent after {timeouts} timeouts");
+                            }
+                            unsent = false;
+                            timeouts = 0;
+                            retry_delay = RETRY_DELAY_MIN;
+                            pending_sender.send_if_modified(|input| {
+                                class.sent(input, &report);
+                                false
+                            });
+                        }
+                        Ok(Err(error)) => {
+                            log::warn!("{class}: Error when sending input: {}", error);
+                            unsent = false;
+                            timeouts = 0;
+                            retry_delay = RETRY_DELAY_MIN;
+                            device = None;
+                        }
+                        // actual report is sent again after delay
+                        Err(_) => {
+                            if timeouts == 0 {
+                                log::warn!("{class}: Timeout when sending input");
+                            } else {
+                                log::debug!("{class}: Timeout when sending input again");
+                            }
+                            timeouts += 1;
+                            retry_at = Some(time::Instant::now() + retry_delay);
+                            retry_delay = (retry_delay * 2).min(RETRY_DELAY_MAX);
+                        }
+                    }
+                    continue;
+                }
+
                 select! {
                     // device dropped
                     _ = output_sender.closed() => break,
                     result = input_receiver.changed() => match result {
                         // Input report changed
                         Ok(_) => {
-                            let report = *input_receiver.borrow();
-                            if let Err(error) = device.input(&report).await {
-                                log::error!("{class}: Error when sending input: {}", error);
-                                break;
+                            unsent = true;
+                            if !configured {
+                                pending_sender.send_if_modified(|input| {
+                                    class.discard(input);
+                                    false
+                                });
                             }
                         }
                         // Disconnected
                         Err(_) => break,
                     },
-                    result = device.output(&mut output) => match result {
+                    state = usb_state_changed(&mut usb_state) => {
+                        woken = false;
+                        if state.is_configured() {
+                            // Resend actual report after re-enumeration
+                            unsent = true;
+                            retry_at = None;
+                            retry_delay = RETRY_DELAY_MIN;
+                        } else {
+                            pending_sender.send_if_modified(|input| {
+                                class.discard(input);
+                                false
+                            });
+                        }
+                    }
+                    _ = time::sleep_until(retry_at.unwrap_or_else(time::Instant::now)), if retry_at.is_some() => {
+                        retry_at = None;
+                    }
+                    _ = time::sleep(REOPEN_INTERVAL), if device.is_none() => {
+                        match Device::<C>::open(&path).await {
+                            Ok(dev) => {
+                                log::info!("{class}: Device reopened");
+                                device = Some(dev);
+                                unsent = true;
+                                retry_at = None;
+                                retry_delay = RETRY_DELAY_MIN;
+                            }
+                            Err(error) => {
+                                log::debug!("{class}: Unable to reopen device: {}", error);
+                            }
+                        }
+                    }
+                    result = read_output(&mut device, &mut output) => match result {
                         // Output report received
                         Ok(_) => {
                             let report = output;
@@ -84,8 +276,8 @@ impl<C: Class> HidIo<C> {
                         }
                         // Output report receiving error happenned
                         Err(error) => {
-                            log::error!("{class}: Error when receiving output: {}", error);
-                            break;
+                            log::warn!("{class}: Error when receiving output: {}", error);
+                            device = None;
                         }
                     },
                 }
@@ -100,6 +292,33 @@ impl<C: Class> HidIo<C> {
     }
 }
 
+/// Receive output report when device is opened
+async fn read_output<C: Class>(
+    device: &mut Option<Device<C>>,
+    output: &mut C::Output,
+) -> hidg::Result<()>
+where
+    C::Output: AsMut<[u8]>,
+{
+    if let Some(device) = device {
+        device.output(output).await
+    } else {
+        pending().await
+    }
+}
+
+/// Wait for USB state changes when watching
+async fn usb_state_changed(usb_state: &mut Option<watch::Receiver<UsbState>>) -> UsbState {
+    if let Some(receiver) = usb_state {
+        if receiver.changed().await.is_ok() {
+            return *receiver.borrow_and_update();
+        }
+        // Watcher is gone
+        *usb_state = None;
+    }
+    pending().await
+}
+
 impl HidIo<Keyboard> {
     /// Get pressed keys
     pub fn get_state(&self) -> KeyboardState {
@@ -242,27 +461,188 @@ impl HidIo<Mouse> {
     }
 }
 
+impl HidIo<RelativeMouse> {
+    /// Get pressed buttons
+    pub fn get_state(&self) -> MouseState {
+        let mut state = MouseState::from(&*self.input_sender.borrow());
+        // pending motion isn't a state
+        state.pointer = (0, 0);
+        state.wheel = 0;
+        state
+    }
+
+    /// Change mouse state
+    ///
+    /// Pointer and wheel changes are always applied as motion deltas.
+    pub async fn change_state(&self, change: MouseStateChange) -> Result<()> {
+        // pending motion saturates instead of overflowing
+        self.input_sender.send_modify(|report| match change {
+            MouseStateChange::Pointer(change) => {
+                let (x, y) = report.pointer();
+                let (dx, dy) = *change;
+                report.set_pointer((x.saturating_add(dx), y.saturating_add(dy)));
+            }
+            MouseStateChange::Wheel(change) => {
+                report.set_wheel(report.wheel().saturating_add(*change));
+            }
+            change => report.change(&change),
+        });
+        Ok(())
+    }
+
+    /// Watch mouse button changes
+    ///
+    /// Relative motion isn't a state so only button changes are reported.
+    pub fn watch_state(&self) -> mpsc::Receiver<MouseStateChange> {
+        let mut old_report = *self.input_sender.borrow();
+        let mut input_receiver = self.input_sender.subscribe();
+        let (event_sender, event_receiver) = mpsc::channel(10);
+
+        spawn(async move {
+            loop {
+                if let Err(error) = input_receiver.changed().await {
+                    log::error!("Error when receiving mouse state changes: {}", error);
+                    break;
+                } else {
+                    // New report is set
+                    let new_report = *input_receiver.borrow();
+                    for change in &new_report - &old_report {
+                        if !matches!(change, MouseStateChange::Button(_)) {
+                            continue;
+                        }
+                        log::trace!("Mouse state change {:?}", change);
+                        if let Err(error) = event_sender.send(change).await {
+                            log::error!("Error when sending mouse state change: {}", error);
+                            break;
+                        }
+                    }
+                    old_report = new_report;
+                }
+            }
+        });
+
+        event_receiver
+    }
+}
+
 pub struct Hid {
     keyboard: Option<HidIo<Keyboard>>,
     mouse: Option<HidIo<Mouse>>,
+    relative_mouse: Option<HidIo<RelativeMouse>>,
+    udc: Option<Udc>,
+    /// Should be dropped after devices
+    gadget: Option<Gadget>,
 }
 
 impl Hid {
     /// Crate HID devices from config
     pub async fn new(config: &HidConfig) -> Result<Self> {
-        let keyboard = if let Some(keyboard) = &config.keyboard {
-            Some(HidIo::new(Keyboard, keyboard).await?)
+        let gadget = if let Some(gadget) = &config.gadget {
+            log::info!("Setup USB gadget");
+            Some(Gadget::new(gadget).await?)
         } else {
             None
         };
 
-        let mouse = if let Some(mouse) = &config.keyboard {
-            Some(HidIo::new(Mouse, mouse).await?)
+        let udc = if let Some(udc) = gadget
+            .as_ref()
+            .map(|gadget| gadget.udc().to_string())
+            .or_else(|| config.udc.clone())
+        {
+            Some(udc)
         } else {
+            Udc::find().await.ok()
+        };
+
+        let udc = if let Some(udc) = udc {
+            match Udc::new(&udc).await {
+                Ok(udc) => Some(udc),
+                Err(error) => {
+                    log::warn!("Unable to watch USB state of {udc}: {error}");
+                    None
+                }
+            }
+        } else {
+            log::info!("No USB device controller to watch");
             None
         };
 
-        Ok(Self { keyboard, mouse })
+        let usb_state = udc.as_ref().map(|udc| udc.watch());
+        let waker = udc
+            .as_ref()
+            .filter(|_| config.wakeup_on_input)
+            .map(|udc| udc.waker());
+
+        let keyboard = if let Some(keyboard) =
+            Self::device(&gadget, GadgetFunction::Keyboard, &config.keyboard).await?
+        {
+            Some(HidIo::new(Keyboard, keyboard, usb_state.clone(), waker.clone()).await?)
+        } else {
+            None
+        };
+
+        let mouse = if let Some(mouse) =
+            Self::device(&gadget, GadgetFunction::Mouse, &config.mouse).await?
+        {
+            Some(HidIo::new(Mouse, mouse, usb_state.clone(), waker.clone()).await?)
+        } else {
+            None
+        };
+
+        let relative_mouse = if let Some(mouse) = Self::device(
+            &gadget,
+            GadgetFunction::RelativeMouse,
+            &config.relative_mouse,
+        )
+        .await?
+        {
+            Some(HidIo::new(RelativeMouse, mouse, usb_state, waker).await?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            keyboard,
+            mouse,
+            relative_mouse,
+            udc,
+            gadget,
+        })
+    }
+
+    async fn device(
+        gadget: &Option<Gadget>,
+        function: GadgetFunction,
+        device: &Option<String>,
+    ) -> Result<Option<PathBuf>> {
+        Ok(match gadget {
+            Some(gadget) if gadget.has_function(function) => Some(gadget.device(function).await?),
+            _ => device.as_ref().map(PathBuf::from),
+        })
+    }
+
+    /// Get access to USB gadget
+    pub fn gadget(&self) -> Option<&Gadget> {
+        self.gadget.as_ref()
+    }
+
+    /// Get access to USB device controller
+    pub fn udc(&self) -> Option<&Udc> {
+        self.udc.as_ref()
+    }
+
+    /// Wake suspended host up
+    pub async fn wake_host(&self) -> Result<()> {
+        self.udc
+            .as_ref()
+            .ok_or("No USB device controller")?
+            .wake_host()
+            .await
+    }
+
+    /// Get USB host connection state
+    pub fn usb_state(&self) -> UsbState {
+        self.udc.as_ref().map(|udc| udc.state()).unwrap_or_default()
     }
 
     /// Get access to keyboard
@@ -274,4 +654,52 @@ impl Hid {
     pub fn mouse(&self) -> Option<&HidIo<Mouse>> {
         self.mouse.as_ref()
     }
+
+    /// Get access to relative mouse
+    pub fn relative_mouse(&self) -> Option<&HidIo<RelativeMouse>> {
+        self.relative_mouse.as_ref()
+    }
+
+    /// Get default mouse mode
+    ///
+    /// Absolute mode is preferred when both mice are available.
+    pub fn mouse_mode(&self) -> Option<MouseMode> {
+        if self.mouse.is_some() {
+            Some(MouseMode::Absolute)
+        } else if self.relative_mouse.is_some() {
+            Some(MouseMode::Relative)
+        } else {
+            None
+        }
+    }
+
+    /// Check mouse mode availability
+    pub fn has_mouse_mode(&self, mode: MouseMode) -> bool {
+        match mode {
+            MouseMode::Absolute => self.mouse.is_some(),
+            MouseMode::Relative => self.relative_mouse.is_some(),
+        }
+    }
+
+    /// Change mouse state using mouse of specified mode
+    pub async fn change_mouse_state(
+        &self,
+        mode: MouseMode,
+        change: MouseStateChange,
+    ) -> Result<()> {
+        match mode {
+            MouseMode::Absolute => {
+                self.mouse()
+                    .ok_or("Mouse disabled")?
+                    .change_state(change)
+                    .await
+            }
+            MouseMode::Relative => {
+                self.relative_mouse()
+                    .ok_or("Relative mouse disabled")?
+                    .change_state(change)
+                    .await
+            }
+        }
+    }
 }
@@ -3,6 +3,7 @@ use crate::{
 };
 use core::pin::Pin;
 use futures_util::{
+    future::ready,
     sink::SinkExt,
     stream::{once, select, select_all, Stream, StreamExt},
 };
@@ -21,6 +22,111 @@ use postcard::{from_bytes as from_slice, to_stdvec as to_vec};
 
 impl warp::reject::Reject for Error {}
 
+/// Multipart boundary of MJPEG stream
+#[cfg(feature = "video")]
+const MJPEG_BOUNDARY: &str = "frame";
+
+/// Create multipart MJPEG stream from video frames
+#[cfg(feature = "video")]
+fn mjpeg_stream(
+    frames: crate::video::VideoSource,
+) -> impl Stream<Item = core::result::Result<Vec<u8>, std::convert::Infallible>> {
+    WatchStream::new(frames)
+        .filter(|frame| core::future::ready(!frame.is_empty()))
+        .map(|frame| {
+            let mut part = format!(
+                "--{MJPEG_BOUNDARY}\r\ncontent-type: image/jpeg\r\ncontent-length: {}\r\n\r\n",
+                frame.len()
+            )
+            .into_bytes();
+            part.extend_from_slice(&frame);
+            part.extend_from_slice(b"\r\n");
+            Ok(part)
+        })
+}
+
+/// Send video frames to socket
+///
+/// Frames captured while previous one is sending are skipped so slow
+/// clients always get the latest frame.
+#[cfg(feature = "video")]
+async fn send_video_frames(
+    mut frames: crate::video::VideoSource,
+    mut socket_sender: impl futures_util::Sink<warp::ws::Message, Error = warp::Error> + Unpin,
+) {
+    log::debug!("Start sending video");
+
+    frames.mark_changed();
+
+    while frames.changed().await.is_ok() {
+        let frame = frames.borrow_and_update().clone();
+        if frame.is_empty() {
+            continue;
+        }
+        let msg = match to_vec(&SocketOutput::VideoFrame { frame }) {
+            Ok(msg) => warp::ws::Message::binary(msg),
+            Err(error) => {
+                log::error!("Error when encoding frame: {}", error);
+                continue;
+            }
+        };
+        if let Err(error) = socket_sender.send(msg).await {
+            log::warn!("Error when sending frame: {}", error);
+            break;
+        }
+    }
+
+    log::debug!("Stop sending video");
+}
+
+/// Power state waiting options
+#[derive(serde::Deserialize)]
+struct PowerWaitQuery {
+    /// Power state to reach
+    state: crate::PowerState,
+    /// Timeout in milliseconds
+    #[serde(default = "default_power_timeout")]
+    timeout: u32,
+}
+
+fn default_power_timeout() -> u32 {
+    300000
+}
+
+/// Screen text waiting options
+#[cfg(feature = "screen")]
+#[derive(serde::Deserialize)]
+struct ScreenWaitQuery {
+    /// Regular expression to match
+    regex: String,
+    /// Timeout in milliseconds
+    #[serde(default = "default_screen_timeout")]
+    timeout: u32,
+}
+
+#[cfg(feature = "screen")]
+fn default_screen_timeout() -> u32 {
+    30000
+}
+
+/// Per-connection socket state
+#[derive(Default)]
+struct SocketSession {
+    /// Mouse mode (reported with state when events resubscribed)
+    #[cfg(feature = "hid")]
+    mouse_mode: tokio::sync::watch::Sender<crate::hid::MouseMode>,
+    /// WebRTC video peer
+    #[cfg(feature = "webrtc")]
+    webrtc_peer: Option<crate::WebRtcPeer>,
+}
+
+#[cfg(feature = "hid")]
+impl SocketSession {
+    fn mouse_mode(&self) -> crate::hid::MouseMode {
+        *self.mouse_mode.borrow()
+    }
+}
+
 impl Server {
     pub async fn spawn_http(&self, addr: &HttpBindAddr, gs: &GracefulShutdown) -> Result<()> {
         use warp::Filter;
@@ -48,31 +154,64 @@ impl Server {
             .and(warp::ws())
             .and(server.clone())
             .map(|ws: warp::ws::Ws, server: Server| {
+                // session must not keep instance alive when server reloaded
+                let mut server = server.downgrade();
                 ws.on_upgrade(move |socket| async move {
                     let (mut socket_sender, mut socket_receiver) = socket.split();
 
+                    let mut session = {
+                        let Ok(server) = server.acquire().await else {
+                            return;
+                        };
+
+                        #[cfg(feature = "record")]
+                        server.open_record_session().await;
+
+                        server.create_socket_session()
+                    };
+
+                    // replies to requests
+                    let (reply_sender, reply_receiver) = tokio::sync::mpsc::channel(4);
+
                     spawn({
-                        let server = server.clone();
+                        let mut server_ref = server.clone();
+                        #[cfg(feature = "hid")]
+                        let mouse_mode = session.mouse_mode.subscribe();
                         async move {
-                            let mut stream = server.create_socket_output();
-                            while let Some(res) = stream.next().await {
-                                let msg = match to_vec(&res) {
-                                    Ok(res) => warp::ws::Message::binary(res),
-                                    Err(error) => {
-                                        log::error!("Error when encoding message: {}", error);
-                                        continue;
+                            let mut replies = ReceiverStream::new(reply_receiver);
+                            // events are resubscribed when server reloaded
+                            'session: while let Ok(server) = server_ref.acquire().await {
+                                let mut stream = select(
+                                    server.create_socket_output(
+                                        #[cfg(feature = "hid")]
+                                        *mouse_mode.borrow(),
+                                    ),
+                                    &mut replies,
+                                );
+                                loop {
+                                    let res = tokio::select! {
+                                        res = stream.next() => match res {
+                                            Some(res) => res,
+                                            None => break 'session,
+                                        },
+                                        _ = server_ref.replaced() => break,
+                                    };
+                                    let msg = match to_vec(&res) {
+                                        Ok(res) => warp::ws::Message::binary(res),
+                                        Err(error) => {
+                                            log::error!("Error when encoding message: {}", error);
+                                            continue;
+                                        }
+                                    };
+                                    if let Err(error) = socket_sender.send(msg).await {
+                                        log::warn!("Error when sending message: {}", error);
+                                        break 'session;
                                     }
-                                };
-                                if let Err(error) = socket_sender.feed(msg).await {
-                                    log::warn!("Error when sending message: {}", error);
-                                    break;
                                 }
                             }
                         }
                     });
 
-                    let server = server.downgrade();
-
                     while let Some(req) = socket_receiver.next().await {
                         let msg = match req {
                             Ok(msg) => msg,
@@ -90,21 +229,228 @@ impl Server {
                                 }
                             };
 
-                            let server = if let Ok(server) = server.upgrade() {
+                            let server = if let Ok(server) = server.acquire().await {
                                 server
                             } else {
                                 break;
                             };
 
-                            if let Err(error) = server.process_socket_input(req).await {
-                                log::warn!("Error when processing input: {}", error);
+                            match server.process_socket_input(&mut session, req).await {
+                                Ok(Some(reply)) => {
+                                    if reply_sender.send(reply).await.is_err() {
+                                        break;
+                                    }
+                                }
+                                Ok(None) => {}
+                                Err(error) => {
+                                    log::warn!("Error when processing input: {}", error);
+                                }
                             }
                         }
                     }
+
+                    #[cfg(feature = "record")]
+                    if let Ok(server) = server.upgrade() {
+                        server.close_record_session().await;
+                    }
                 })
             });
 
-        let http_server = warp::serve(index.or(socket));
+        let leds = warp::path!("api" / "leds")
+            .and(warp::get())
+            .and(server.clone())
+            .map(|server: Server| {
+                let leds = server
+                    .leds()
+                    .iter()
+                    .map(|(id, led)| (*id, led.info()))
+                    .collect::<std::collections::HashMap<_, _>>();
+                warp::reply::with_header(warp::reply::json(&leds), "cache-control", "no-cache")
+            });
+
+        let sensors = warp::path!("api" / "sensors")
+            .and(warp::get())
+            .and(server.clone())
+            .map(|server: Server| {
+                let sensors = server
+                    .sensors()
+                    .iter()
+                    .map(|(name, sensor)| (name.clone(), sensor.info()))
+                    .collect::<std::collections::HashMap<_, _>>();
+                warp::reply::with_header(warp::reply::json(&sensors), "cache-control", "no-cache")
+            });
+
+        let power = warp::path!("api" / "power")
+            .and(warp::get())
+            .and(server.clone())
+            .map(|server: Server| {
+                let state = server.power().state();
+                warp::reply::with_header(warp::reply::json(&state), "cache-control", "no-cache")
+            });
+
+        let power_wait = warp::path!("api" / "power" / "wait")
+            .and(warp::get())
+            .and(warp::query::<PowerWaitQuery>())
+            .and(server.clone())
+            .and_then(|query: PowerWaitQuery, server: Server| {
+                let wait = server.wait_power(
+                    query.state,
+                    core::time::Duration::from_millis(query.timeout as _),
+                );
+                async move {
+                    wait.await?;
+                    Ok::<_, warp::Rejection>(warp::reply::with_header(
+                        warp::reply::json(&query.state),
+                        "cache-control",
+                        "no-cache",
+                    ))
+                }
+            });
+
+        let routes = index
+            .or(socket)
+            .or(leds)
+            .or(sensors)
+            .or(power)
+            .or(power_wait);
+
+        #[cfg(feature = "video")]
+        let routes = {
+            let video_socket = warp::path("video")
+                .and(warp::path::end())
+                .and(warp::ws())
+                .and(server.clone())
+                .and_then(|ws: warp::ws::Ws, server: Server| async move {
+                    let frames = server.video().ok_or(warp::reject::not_found())?.frames();
+                    Ok::<_, warp::Rejection>(ws.on_upgrade(move |socket| async move {
+                        let (socket_sender, mut socket_receiver) = socket.split();
+
+                        let sending = spawn(send_video_frames(frames, socket_sender));
+
+                        // Wait for closing
+                        while let Some(Ok(_)) = socket_receiver.next().await {}
+
+                        sending.abort();
+                    }))
+                });
+
+            let video_stream = warp::path("video.mjpeg")
+                .and(warp::path::end())
+                .and(warp::get())
+                .and(server.clone())
+                .and_then(|server: Server| async move {
+                    let frames = server.video().ok_or(warp::reject::not_found())?.frames();
+                    Ok::<_, warp::Rejection>(
+                        warp::http::Response::builder()
+                            .header(
+                                "content-type",
+                                format!("multipart/x-mixed-replace; boundary={MJPEG_BOUNDARY}"),
+                            )
+                            .header("cache-control", "no-cache")
+                            .body(warp::hyper::Body::wrap_stream(mjpeg_stream(frames))),
+                    )
+                });
+
+            let video_snapshot = warp::path("snapshot.jpg")
+                .and(warp::path::end())
+                .and(warp::get())
+                .and(server.clone())
+                .and_then(|server: Server| async move {
+                    let frame = server
+                        .video()
+                        .ok_or(warp::reject::not_found())?
+                        .snapshot()
+                        .await?;
+                    Ok::<_, warp::Rejection>(
+                        warp::http::Response::builder()
+                            .header("content-type", "image/jpeg")
+                            .header("cache-control", "no-cache")
+                            .body(frame.as_ref().clone()),
+                    )
+                });
+
+            let video_health = warp::path!("api" / "video" / "health")
+                .and(warp::get())
+                .and(server.clone())
+                .and_then(|server: Server| async move {
+                    let info = server
+                        .video()
+                        .ok_or(warp::reject::not_found())?
+                        .health_info();
+                    Ok::<_, warp::Rejection>(warp::reply::with_header(
+                        warp::reply::json(&info),
+                        "cache-control",
+                        "no-cache",
+                    ))
+                });
+
+            routes
+                .or(video_socket)
+                .or(video_stream)
+                .or(video_snapshot)
+                .or(video_health)
+        };
+
+        #[cfg(feature = "screenshot")]
+        let routes = {
+            let screenshot = warp::path!("api" / "screenshot")
+                .and(warp::get())
+                .and(warp::query::<crate::ScreenshotOptions>())
+                .and(server.clone())
+                .and_then(
+                    |options: crate::ScreenshotOptions, server: Server| async move {
+                        let image = server.screenshot(options).await?;
+                        Ok::<_, warp::Rejection>(
+                            warp::http::Response::builder()
+                                .header("content-type", options.format.mime_type())
+                                .header("cache-control", "no-cache")
+                                .body(image.as_ref().clone()),
+                        )
+                    },
+                );
+
+            routes.or(screenshot)
+        };
+
+        #[cfg(feature = "screen")]
+        let routes = {
+            let screen_text = warp::path!("api" / "screen" / "text")
+                .and(warp::get())
+                .and(server.clone())
+                .and_then(|server: Server| async move {
+                    let text = server.screen_text().await?;
+                    Ok::<_, warp::Rejection>(
+                        warp::http::Response::builder()
+                            .header("content-type", "text/plain; charset=utf-8")
+                            .header("cache-control", "no-cache")
+                            .body(text),
+                    )
+                });
+
+            let screen_wait = warp::path!("api" / "screen" / "wait")
+                .and(warp::get())
+                .and(warp::query::<ScreenWaitQuery>())
+                .and(server.clone())
+                .and_then(|query: ScreenWaitQuery, server: Server| {
+                    let wait = server.wait_screen_text(
+                        &query.regex,
+                        core::time::Duration::from_millis(query.timeout as _),
+                    );
+                    async move {
+                        let text = wait.await?;
+                        Ok::<_, warp::Rejection>(
+                            warp::http::Response::builder()
+                                .header("content-type", "text/plain; charset=utf-8")
+                                .header("cache-control", "no-cache")
+                                .body(text),
+                        )
+                    }
+                });
+
+            routes.or(screen_text).or(screen_wait)
+        };
+
+        let http_server = warp::serve(routes);
 
         let tls = &addr.tls;
 
@@ -185,13 +531,50 @@ impl Server {
         Ok(())
     }
 
-    fn create_socket_state(&self) -> SocketOutput {
+    fn create_socket_session(&self) -> SocketSession {
+        SocketSession {
+            #[cfg(feature = "hid")]
+            mouse_mode: tokio::sync::watch::channel(
+                self.hid()
+                    .and_then(|hid| hid.mouse_mode())
+                    .unwrap_or_default(),
+            )
+            .0,
+            #[cfg(feature = "webrtc")]
+            webrtc_peer: None,
+        }
+    }
+
+    fn create_socket_state(
+        &self,
+        #[cfg(feature = "hid")] mouse_mode: crate::hid::MouseMode,
+    ) -> SocketOutput {
         let leds = self
             .leds()
             .iter()
             .map(|(id, obj)| (*id, obj.state()))
             .collect();
 
+        let led_patterns = self
+            .leds()
+            .iter()
+            .map(|(id, obj)| (*id, obj.pattern()))
+            .collect();
+
+        let led_activities = self
+            .leds()
+            .iter()
+            .filter_map(|(id, obj)| Some((*id, obj.activity()?)))
+            .collect();
+
+        let sensors = self
+            .sensors()
+            .iter()
+            .map(|(name, obj)| (name.clone(), obj.reading()))
+            .collect();
+
+        let power = self.power().state();
+
         let buttons = self
             .buttons()
             .iter()
@@ -208,19 +591,60 @@ impl Server {
         let mouse = self
             .hid()
             .and_then(|hid| hid.mouse())
-            .map(|mouse| mouse.get_state());
+            .map(|mouse| mouse.get_state())
+            .or_else(|| {
+                self.hid()
+                    .and_then(|hid| hid.relative_mouse())
+                    .map(|mouse| mouse.get_state())
+            });
+
+        // none when session mode unavailable
+        #[cfg(feature = "hid")]
+        let mouse_mode = self
+            .hid()
+            .filter(|hid| hid.has_mouse_mode(mouse_mode))
+            .map(|_| mouse_mode);
+
+        #[cfg(feature = "hid")]
+        let usb_state = self.hid().map(|hid| hid.usb_state());
+
+        #[cfg(feature = "video")]
+        let video_mode = self.video().map(|video| video.mode());
+
+        #[cfg(feature = "video")]
+        let video_signal = self.video().map(|video| video.signal());
+
+        #[cfg(feature = "video")]
+        let video_health = self.video().map(|video| video.health());
 
         SocketOutput::State {
             leds,
+            led_patterns,
+            led_activities,
+            sensors,
+            power,
             buttons,
             #[cfg(feature = "hid")]
             keyboard,
             #[cfg(feature = "hid")]
             mouse,
+            #[cfg(feature = "hid")]
+            mouse_mode,
+            #[cfg(feature = "hid")]
+            usb_state,
+            #[cfg(feature = "video")]
+            video_mode,
+            #[cfg(feature = "video")]
+            video_signal,
+            #[cfg(feature = "video")]
+            video_health,
         }
     }
 
-    fn create_socket_output(&self) -> impl Stream<Item = SocketOutput> {
+    fn create_socket_output(
+        &self,
+        #[cfg(feature = "hid")] mouse_mode: crate::hid::MouseMode,
+    ) -> impl Stream<Item = SocketOutput> {
         let button_events = select_all(self.buttons().iter().map(|(id, obj)| {
             let button = *id;
             WatchStream::new(obj.watch()).map(move |state| SocketOutput::Button { button, state })
@@ -231,12 +655,42 @@ impl Server {
             WatchStream::new(obj.watch()).map(move |state| SocketOutput::Led { led, state })
         }));
 
-        let events = select(button_events, led_events);
+        let led_pattern_events = select_all(self.leds().iter().map(|(id, obj)| {
+            let led = *id;
+            WatchStream::from_changes(obj.watch_pattern())
+                .map(move |pattern| SocketOutput::LedPattern { led, pattern })
+        }));
+
+        let led_activity_events = select_all(self.leds().iter().map(|(id, obj)| {
+            let led = *id;
+            WatchStream::from_changes(obj.watch_activity()).filter_map(move |activity| {
+                ready(activity.map(|activity| SocketOutput::LedActivity { led, activity }))
+            })
+        }));
+
+        let sensor_events = select_all(self.sensors().iter().map(|(name, obj)| {
+            let name = name.clone();
+            WatchStream::from_changes(obj.watch()).map(move |reading| SocketOutput::Sensor {
+                name: name.clone(),
+                reading,
+            })
+        }));
+
+        let power_events = WatchStream::from_changes(self.power().watch())
+            .map(|state| SocketOutput::Power { state });
+
+        let events = select(
+            select(button_events, select(sensor_events, power_events)),
+            select(led_events, select(led_pattern_events, led_activity_events)),
+        );
 
         let events = select(
             events,
             once({
-                let state = self.create_socket_state();
+                let state = self.create_socket_state(
+                    #[cfg(feature = "hid")]
+                    mouse_mode,
+                );
                 async move { state }
             }),
         );
@@ -286,27 +740,69 @@ impl Server {
                 events = Box::pin(select(events, mouse_events))
             }
 
+            if let Some(mouse) = self.hid().and_then(|hid| hid.relative_mouse()) {
+                use crate::hid::MouseStateChange;
+
+                let mouse_events =
+                    ReceiverStream::new(mouse.watch_state()).filter_map(|change| async move {
+                        match change {
+                            MouseStateChange::Button(change) => Some(SocketOutput::MouseButton {
+                                button: *change,
+                                state: change.state(),
+                            }),
+                            _ => None,
+                        }
+                    });
+
+                events = Box::pin(select(events, mouse_events))
+            }
+
+            if let Some(udc) = self.hid().and_then(|hid| hid.udc()) {
+                let usb_events =
+                    WatchStream::new(udc.watch()).map(|state| SocketOutput::UsbState { state });
+
+                events = Box::pin(select(events, usb_events))
+            }
+
             events
         };
 
         #[cfg(feature = "video")]
         let events = {
-            let events = Box::pin(events) as Pin<Box<dyn Stream<Item = SocketOutput> + Send>>;
+            let mut events = Box::pin(events) as Pin<Box<dyn Stream<Item = SocketOutput> + Send>>;
 
             if let Some(video) = self.video() {
-                let video_events = WatchStream::new(video.frames())
-                    .map(|frame| SocketOutput::VideoFrame { frame });
+                let mode_events = WatchStream::from_changes(video.watch_mode())
+                    .map(|mode| SocketOutput::VideoMode { mode });
+
+                let signal_events = WatchStream::from_changes(video.watch_signal())
+                    .map(|signal| SocketOutput::VideoSignal { signal });
+
+                let health_events = WatchStream::from_changes(video.watch_health())
+                    .map(|health| SocketOutput::VideoHealth { health });
 
-                Box::pin(select(events, video_events))
-            } else {
-                events
+                events = Box::pin(select(
+                    events,
+                    select(mode_events, select(signal_events, health_events)),
+                ))
             }
+
+            events
         };
 
         events
     }
 
-    async fn process_socket_input(&self, req: SocketInput) -> Result<()> {
+    async fn process_socket_input(
+        &self,
+        #[allow(unused_variables)] session: &mut SocketSession,
+        req: SocketInput,
+    ) -> Result<Option<SocketOutput>> {
+        #[cfg(feature = "record")]
+        if let Some(recorder) = self.recorder() {
+            recorder.log_input(&req);
+        }
+
         match req {
             SocketInput::Button { button, state } => {
                 self.buttons()
@@ -325,11 +821,13 @@ impl Server {
             #[cfg(feature = "hid")]
             SocketInput::MouseButton { button, state } => {
                 self.hid()
-                    .and_then(|hid| hid.mouse())
                     .ok_or("Mouse disabled")?
-                    .change_state(crate::hid::MouseStateChange::Button(
-                        crate::hid::ButtonStateChange::new(button, state),
-                    ))
+                    .change_mouse_state(
+                        session.mouse_mode(),
+                        crate::hid::MouseStateChange::Button(crate::hid::ButtonStateChange::new(
+                            button, state,
+                        )),
+                    )
                     .await?;
             }
             #[cfg(feature = "hid")]
@@ -343,17 +841,179 @@ impl Server {
                     .await?;
             }
             #[cfg(feature = "hid")]
+            SocketInput::MouseMotion { x, y } => {
+                self.hid()
+                    .ok_or("Mouse disabled")?
+                    .change_mouse_state(
+                        session.mouse_mode(),
+                        crate::hid::MouseStateChange::Pointer(
+                            crate::hid::PointerValueChange::relative((x, y)),
+                        ),
+                    )
+                    .await?;
+            }
+            #[cfg(feature = "hid")]
             SocketInput::MouseWheel { wheel } => {
                 self.hid()
-                    .and_then(|hid| hid.mouse())
                     .ok_or("Mouse disabled")?
-                    .change_state(crate::hid::MouseStateChange::Wheel(
-                        crate::hid::WheelValueChange::absolute(wheel),
-                    ))
+                    .change_mouse_state(
+                        session.mouse_mode(),
+                        crate::hid::MouseStateChange::Wheel(
+                            crate::hid::WheelValueChange::absolute(wheel),
+                        ),
+                    )
                     .await?;
             }
+            #[cfg(feature = "hid")]
+            SocketInput::MouseMode { mode } => {
+                if !self.hid().is_some_and(|hid| hid.has_mouse_mode(mode)) {
+                    Err(format!("Mouse mode {mode} unavailable"))?;
+                }
+                session.mouse_mode.send_replace(mode);
+            }
+            #[cfg(feature = "hid")]
+            SocketInput::WakeHost => {
+                self.hid().ok_or("HID disabled")?.wake_host().await?;
+            }
+            #[cfg(feature = "video")]
+            SocketInput::VideoMode { mode } => {
+                self.video().ok_or("Video disabled")?.set_mode(mode).await?;
+            }
+            #[cfg(feature = "webrtc")]
+            SocketInput::WebRtcOffer { sdp } => {
+                // only one peer per session
+                session.webrtc_peer = None;
+                let (peer, sdp) = self.video().ok_or("Video disabled")?.webrtc(sdp).await?;
+                session.webrtc_peer = Some(peer);
+                return Ok(Some(SocketOutput::WebRtcAnswer { sdp }));
+            }
+            #[cfg(feature = "webrtc")]
+            SocketInput::WebRtcClose => {
+                session.webrtc_peer = None;
+            }
         }
 
-        Ok(())
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "video")]
+    #[tokio::test]
+    async fn mjpeg_stream_parts() {
+        use super::mjpeg_stream;
+        use futures_util::StreamExt;
+        use std::sync::Arc;
+        use tokio::sync::watch;
+
+        let (sender, receiver) = watch::channel(Arc::new(Vec::new()));
+        let mut stream = Box::pin(mjpeg_stream(receiver));
+
+        sender.send(Arc::new(b"jpeg".to_vec())).unwrap();
+        assert_eq!(
+            stream.next().await.unwrap().unwrap(),
+            b"--frame\r\ncontent-type: image/jpeg\r\ncontent-length: 4\r\n\r\njpeg\r\n"
+        );
+
+        drop(sender);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[cfg(feature = "screenshot")]
+    #[tokio::test]
+    async fn screenshot_query() {
+        use crate::{Crop, ImageFormat, ScreenshotOptions};
+
+        let filter = warp::query::<ScreenshotOptions>();
+
+        let options = warp::test::request()
+            .path("/api/screenshot?w=320&fmt=webp&crop=10,20,640,480")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(
+            options,
+            ScreenshotOptions {
+                format: ImageFormat::Webp,
+                width: Some(320),
+                crop: Some(Crop {
+                    x: 10,
+                    y: 20,
+                    width: 640,
+                    height: 480
+                }),
+                ..Default::default()
+            }
+        );
+
+        assert!(warp::test::request()
+            .path("/api/screenshot?crop=10,20")
+            .filter(&filter)
+            .await
+            .is_err());
+    }
+
+    #[cfg(feature = "hid")]
+    #[tokio::test]
+    async fn session_mouse_mode() {
+        use crate::{hid::MouseMode, HidConfig, Server, ServerConfig, SocketInput, SocketOutput};
+        use nix::{pty::openpty, unistd::ttyname};
+
+        // pty slaves stand in for HID devices
+        let mouse = openpty(None, None).unwrap();
+        let relative_mouse = openpty(None, None).unwrap();
+
+        let config = ServerConfig {
+            hid: Some(HidConfig {
+                mouse: Some(ttyname(&mouse.slave).unwrap().display().to_string()),
+                relative_mouse: Some(
+                    ttyname(&relative_mouse.slave)
+                        .unwrap()
+                        .display()
+                        .to_string(),
+                ),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let server = Server::new(&config).await.unwrap();
+        let hid = server.hid().unwrap();
+        let mut session = server.create_socket_session();
+        assert_eq!(session.mouse_mode(), MouseMode::Absolute);
+
+        server
+            .process_socket_input(&mut session, SocketInput::MousePointer { x: 100, y: 100 })
+            .await
+            .unwrap();
+        server
+            .process_socket_input(&mut session, SocketInput::MouseMotion { x: 5, y: -5 })
+            .await
+            .unwrap();
+        assert_eq!(hid.mouse().unwrap().get_state().pointer, (105, 95));
+
+        server
+            .process_socket_input(
+                &mut session,
+                SocketInput::MouseMode {
+                    mode: MouseMode::Relative,
+                },
+            )
+            .await
+            .unwrap();
+        server
+            .process_socket_input(&mut session, SocketInput::MouseMotion { x: 5, y: -5 })
+            .await
+            .unwrap();
+        assert_eq!(hid.mouse().unwrap().get_state().pointer, (105, 95));
+
+        // state reports mode of session
+        assert!(matches!(
+            server.create_socket_state(session.mouse_mode()),
+            SocketOutput::State {
+                mouse_mode: Some(MouseMode::Relative),
+                ..
+            }
+        ));
     }
 }
@@ -0,0 +1,323 @@
+use crate::{gpio::find_line, log, LedId, Result};
+use gpiod::{Active, Bias, Edge, EdgeDetect, Input, Lines, Options};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, process::Stdio, time::Duration};
+use tokio::{
+    fs,
+    net::TcpStream,
+    process::Command,
+    select, spawn,
+    sync::watch,
+    time::{interval, timeout, MissedTickBehavior},
+};
+
+/// Source of LED level
+#[async_trait::async_trait]
+pub trait LedInput: Send {
+    /// Read current level
+    async fn level(&mut self) -> Result<bool>;
+
+    /// Wait until level changed
+    ///
+    /// Returns new level. Must be cancel safe since it is awaited together with timers.
+    async fn changed(&mut self) -> Result<bool>;
+}
+
+/// GPIO line input
+pub struct GpioInput {
+    lines: Lines<Input>,
+}
+
+impl GpioInput {
+    pub async fn new(
+        id: LedId,
+        chip: Option<&str>,
+        line: &crate::GpioLine,
+        active: Active,
+        bias: Bias,
+    ) -> Result<Self> {
+        let found = find_line(chip, line).await?;
+
+        let lines = found
+            .chip
+            .request_lines(
+                Options::input(&[found.line])
+                    .active(active)
+                    .bias(bias)
+                    .edge(EdgeDetect::Both)
+                    .consumer(format!("{}-{}-led", env!("CARGO_PKG_NAME"), id)),
+            )
+            .await?;
+
+        Ok(Self { lines })
+    }
+}
+
+#[async_trait::async_trait]
+impl LedInput for GpioInput {
+    async fn level(&mut self) -> Result<bool> {
+        Ok(self.lines.get_values([false]).await?[0])
+    }
+
+    async fn changed(&mut self) -> Result<bool> {
+        let event = self.lines.read_event().await?;
+        log::trace!("Event received: {}", event);
+        Ok(matches!(event.edge, Edge::Rising))
+    }
+}
+
+fn default_interval() -> u32 {
+    1000
+}
+
+fn default_timeout() -> u32 {
+    1000
+}
+
+/// Sysfs attribute or other file input
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FileInputConfig {
+    /// File path (like `/sys/class/leds/input3::capslock/brightness`)
+    pub path: PathBuf,
+
+    /// Content meaning on (any content except `0` when omitted)
+    #[serde(default)]
+    pub on: Option<String>,
+
+    /// Polling interval in milliseconds
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+}
+
+/// Command exit status input
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CommandInputConfig {
+    /// Program and arguments (like `["ping", "-c1", "-W1", "10.0.0.2"]`)
+    pub run: Vec<String>,
+
+    /// Polling interval in milliseconds
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+
+    /// Command timeout in milliseconds (considered off when exceeded)
+    #[serde(default = "default_timeout")]
+    pub timeout: u32,
+}
+
+/// TCP connection probe input
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TcpInputConfig {
+    /// Address to connect (like `10.0.0.2:22`)
+    pub addr: String,
+
+    /// Polling interval in milliseconds
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+
+    /// Connection timeout in milliseconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u32,
+}
+
+/// Polled level probe
+#[async_trait::async_trait]
+trait Probe: Send + Sync {
+    /// Check level
+    async fn probe(&self) -> Result<bool>;
+}
+
+#[async_trait::async_trait]
+impl Probe for FileInputConfig {
+    async fn probe(&self) -> Result<bool> {
+        let data = fs::read_to_string(&self.path).await?;
+        let data = data.trim();
+        Ok(if let Some(on) = &self.on {
+            data == on
+        } else {
+            !data.is_empty() && data != "0"
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Probe for CommandInputConfig {
+    async fn probe(&self) -> Result<bool> {
+        let (program, args) = self.run.split_first().ok_or("Command required")?;
+        let status = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .status();
+
+        Ok(
+            match timeout(Duration::from_millis(self.timeout as _), status).await {
+                Ok(status) => status?.success(),
+                Err(_) => false,
+            },
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl Probe for TcpInputConfig {
+    async fn probe(&self) -> Result<bool> {
+        let connect = TcpStream::connect(&self.addr);
+        Ok(matches!(
+            timeout(Duration::from_millis(self.timeout as _), connect).await,
+            Ok(Ok(_))
+        ))
+    }
+}
+
+/// Input which polls level periodically
+///
+/// Probes run in separate task so waiting for changes can be cancelled at any time.
+pub struct PollInput {
+    /// Unknown until first probe finished
+    level_receiver: watch::Receiver<Option<bool>>,
+    level: bool,
+}
+
+impl PollInput {
+    fn new(probe: impl Probe + 'static, period: u32) -> Self {
+        let mut ticker = interval(Duration::from_millis(period.max(1) as _));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let (level_sender, level_receiver) = watch::channel(None);
+
+        spawn(async move {
+            loop {
+                let result = select! {
+                    // input dropped
+                    _ = level_sender.closed() => break,
+                    result = async {
+                        // first tick completes immediately
+                        ticker.tick().await;
+                        probe.probe().await
+                    } => result,
+                };
+
+                match result {
+                    Ok(level) => {
+                        level_sender.send_if_modified(|current| {
+                            let changed = *current != Some(level);
+                            *current = Some(level);
+                            changed
+                        });
+                    }
+                    // keep last level until probe recovers
+                    Err(error) => {
+                        log::warn!("Unable to probe level: {error}");
+                        level_sender.send_if_modified(|current| {
+                            let unknown = current.is_none();
+                            current.get_or_insert(false);
+                            unknown
+                        });
+                    }
+                }
+            }
+        });
+
+        Self {
+            level_receiver,
+            level: false,
+        }
+    }
+
+    pub fn file(config: &FileInputConfig) -> Self {
+        Self::new(config.clone(), config.interval)
+    }
+
+    pub fn command(config: &CommandInputConfig) -> Result<Self> {
+        if config.run.is_empty() {
+            return Err("Command required")?;
+        }
+        Ok(Self::new(config.clone(), config.interval))
+    }
+
+    pub fn tcp(config: &TcpInputConfig) -> Self {
+        Self::new(config.clone(), config.interval)
+    }
+}
+
+#[async_trait::async_trait]
+impl LedInput for PollInput {
+    async fn level(&mut self) -> Result<bool> {
+        let level = self
+            .level_receiver
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| "Polling stopped")?
+            .unwrap_or_default();
+        self.level = level;
+        Ok(level)
+    }
+
+    async fn changed(&mut self) -> Result<bool> {
+        loop {
+            self.level_receiver
+                .changed()
+                .await
+                .map_err(|_| "Polling stopped")?;
+            let level = self.level_receiver.borrow_and_update().unwrap_or_default();
+            if level != self.level {
+                self.level = level;
+                return Ok(level);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn poll_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("brightness");
+        fs::write(&path, "0\n").await.unwrap();
+
+        let mut input = PollInput::file(&FileInputConfig {
+            path: path.clone(),
+            on: None,
+            interval: 10,
+        });
+        assert!(!input.level().await.unwrap());
+        // interrupted waiting doesn't lose changes
+        assert!(timeout(Duration::from_millis(1), input.changed())
+            .await
+            .is_err());
+        fs::write(&path, "255\n").await.unwrap();
+        assert!(input.changed().await.unwrap());
+        fs::remove_file(&path).await.unwrap();
+
+        // failed probe means off
+        let mut input = PollInput::file(&FileInputConfig {
+            path: path.clone(),
+            on: None,
+            interval: 10,
+        });
+        assert!(!input.level().await.unwrap());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut input = PollInput::tcp(&TcpInputConfig {
+            addr: listener.local_addr().unwrap().to_string(),
+            interval: 10,
+            timeout: default_timeout(),
+        });
+        assert!(input.level().await.unwrap());
+        drop(listener);
+        assert!(!input.changed().await.unwrap());
+
+        let mut input = PollInput::command(&CommandInputConfig {
+            run: vec!["true".into()],
+            interval: 10,
+            timeout: default_timeout(),
+        })
+        .unwrap();
+        assert!(input.level().await.unwrap());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::Result;
+use jpeg_encoder::{Encoder, ImageBuffer, JpegColorType};
+use serde::{Deserialize, Serialize};
+
+/// Raw pixel format which can be encoded to JPEG
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RawFormat {
+    /// Packed YUV 4:2:2 (Y0 U Y1 V)
+    Yuyv,
+
+    /// Planar Y with interleaved UV 4:2:0
+    Nv12,
+}
+
+impl RawFormat {
+    /// Minimum buffer size for frame
+    pub fn frame_size(&self, stride: usize, height: usize) -> usize {
+        match self {
+            Self::Yuyv => stride * height,
+            Self::Nv12 => stride * height + stride * height.div_ceil(2),
+        }
+    }
+}
+
+/// Raw frame geometry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawGeometry {
+    /// Pixel format
+    pub format: RawFormat,
+
+    /// Frame width in pixels
+    pub width: u32,
+
+    /// Frame height in pixels
+    pub height: u32,
+
+    /// Bytes per line (of luma plane for planar formats)
+    pub stride: u32,
+}
+
+/// Raw frame to encode
+struct RawImage<'a> {
+    geometry: RawGeometry,
+    data: &'a [u8],
+}
+
+impl ImageBuffer for RawImage<'_> {
+    fn get_jpeg_color_type(&self) -> JpegColorType {
+        JpegColorType::Ycbcr
+    }
+
+    fn width(&self) -> u16 {
+        self.geometry.width as _
+    }
+
+    fn height(&self) -> u16 {
+        self.geometry.height as _
+    }
+
+    fn fill_buffers(&self, y: u16, buffers: &mut [Vec<u8>; 4]) {
+        let y = y as usize;
+        let width = self.geometry.width as usize;
+        let stride = self.geometry.stride as usize;
+
+        match self.geometry.format {
+            RawFormat::Yuyv => {
+                let line = &self.data[y * stride..];
+                for x in 0..width {
+                    let pair = &line[(x & !1) * 2..];
+                    buffers[0].push(line[x * 2]);
+                    buffers[1].push(pair[1]);
+                    buffers[2].push(pair[3]);
+                }
+            }
+            RawFormat::Nv12 => {
+                let height = self.geometry.height as usize;
+                let luma = &self.data[y * stride..];
+                let chroma = &self.data[stride * height + (y / 2) * stride..];
+                for x in 0..width {
+                    buffers[0].push(luma[x]);
+                    buffers[1].push(chroma[x & !1]);
+                    buffers[2].push(chroma[(x & !1) + 1]);
+                }
+            }
+        }
+    }
+}
+
+/// Encode raw frame to JPEG
+pub fn encode(geometry: RawGeometry, data: &[u8], quality: u8) -> Result<Vec<u8>> {
+    let RawGeometry {
+        format,
+        width,
+        height,
+        stride,
+    } = geometry;
+
+    if width == 0 || height == 0 || width > u16::MAX as _ || height > u16::MAX as _ {
+        Err(format!("Unsupported frame size: {width}x{height}"))?;
+    }
+
+    let min_stride = match format {
+        RawFormat::Yuyv => width.div_ceil(2) * 4,
+        RawFormat::Nv12 => width.div_ceil(2) * 2,
+    };
+
+    if stride < min_stride {
+        Err(format!("Too small line length: {stride}"))?;
+    }
+
+    if data.len() < format.frame_size(stride as _, height as _) {
+        Err(format!("Too small frame: {} bytes", data.len()))?;
+    }
+
+    let mut output = Vec::new();
+
+    Encoder::new(&mut output, quality)
+        .encode_image(RawImage { geometry, data })
+        .map_err(|error| format!("Error when encoding JPEG: {error}"))?;
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Synthetic color bars like vivid produces
+    fn yuyv_bars(width: u32, height: u32) -> Vec<u8> {
+        const BARS: [(u8, u8, u8); 4] = [
+            (235, 128, 128),
+            (210, 16, 146),
+            (145, 54, 34),
+            (16, 128, 128),
+        ];
+        let mut data = Vec::new();
+        for _ in 0..height {
+            for x in (0..width).step_by(2) {
+                let (y, u, v) = BARS[(x * 4 / width) as usize];
+                data.extend_from_slice(&[y, u, y, v]);
+            }
+        }
+        data
+    }
+
+    fn is_jpeg(data: &[u8]) -> bool {
+        data.starts_with(&[0xff, 0xd8]) && data.ends_with(&[0xff, 0xd9])
+    }
+
+    #[test]
+    fn encode_yuyv() {
+        let geometry = RawGeometry {
+            format: RawFormat::Yuyv,
+            width: 64,
+            height: 48,
+            stride: 128,
+        };
+        let data = yuyv_bars(64, 48);
+
+        assert!(is_jpeg(&encode(geometry, &data, 80).unwrap()));
+        assert!(encode(geometry, &data[..100], 80).is_err());
+    }
+
+    #[test]
+    fn encode_nv12() {
+        let geometry = RawGeometry {
+            format: RawFormat::Nv12,
+            width: 64,
+            height: 48,
+            stride: 64,
+        };
+        let mut data = vec![128; 64 * 48 * 3 / 2];
+        data[..64 * 48].fill(200);
+
+        assert!(is_jpeg(&encode(geometry, &data, 50).unwrap()));
+        assert!(encode(
+            RawGeometry {
+                stride: 16,
+                ..geometry
+            },
+            &data,
+            50
+        )
+        .is_err());
+    }
+}
@@ -1,75 +1,548 @@
-use crate::{log, LedId, Result};
-use gpiod::{Active, Bias, Chip, Edge, EdgeDetect, LineId, Options};
+use crate::{
+    inputs::{CommandInputConfig, FileInputConfig, GpioInput, LedInput, PollInput, TcpInputConfig},
+    log, GpioLine, LedActivity, LedId, LedMode, LedPattern, Result,
+};
+use gpiod::{Active, Bias};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tokio::{select, spawn, sync::watch};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    select, spawn,
+    sync::watch,
+    time::{sleep_until, Instant},
+};
+
+/// Allowed deviation of blinking periods in percents
+const BLINK_TOLERANCE: u32 = 25;
 
 /// Single LED
 pub struct Led {
     state_receiver: watch::Receiver<bool>,
+    pattern_receiver: watch::Receiver<LedPattern>,
+    activity_receiver: watch::Receiver<Option<LedActivity>>,
+    meter: Arc<Mutex<DutyMeter>>,
+}
+
+/// LED activity counters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct LedCounters {
+    /// Total number of edges
+    pub edges: u64,
+    /// Total time LED was on in milliseconds
+    pub on_time: u64,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// LED state report
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct LedInfo {
+    /// Current level
+    pub state: bool,
+    /// Derived behavior
+    pub mode: LedMode,
+    /// Blinking period in milliseconds
+    pub period: u32,
+    /// Activity over last sampling interval
+    pub activity: Option<LedActivity>,
+    /// Activity counters
+    pub counters: LedCounters,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct LedConfig {
-    /// GPIO chip name
-    pub chip: String,
+    /// GPIO chip name (all chips are searched for line name when omitted)
+    #[serde(default)]
+    pub chip: Option<String>,
 
-    /// GPIO line offset
-    pub line: LineId,
+    /// GPIO line offset or name
+    #[serde(default)]
+    pub line: Option<GpioLine>,
 
-    /// GPIO line active state
+    /// Active state (low inverts polled inputs too)
     #[serde(default)]
     pub active: Active,
 
     /// GPIO line bias
     #[serde(default)]
     pub bias: Bias,
+
+    /// Poll file content (like sysfs attribute)
+    #[serde(default)]
+    pub file: Option<FileInputConfig>,
+
+    /// Poll command exit status
+    #[serde(default)]
+    pub command: Option<CommandInputConfig>,
+
+    /// Probe TCP connection
+    #[serde(default)]
+    pub tcp: Option<TcpInputConfig>,
+
+    /// Ignore pulses shorter than given milliseconds (0 disables)
+    #[serde(default)]
+    pub debounce: u32,
+
+    /// Time window for detecting blinking in milliseconds
+    #[serde(default = "default_blink_window")]
+    pub blink_window: u32,
+
+    /// Minimum blinking period in milliseconds (faster changes treated as activity)
+    #[serde(default = "default_blink_min_period")]
+    pub blink_min_period: u32,
+
+    /// Report level and activity once per given milliseconds instead of each edge (0 disables)
+    ///
+    /// Pattern is derived from sampled edge counts then.
+    #[serde(default)]
+    pub sample: u32,
+}
+
+impl LedConfig {
+    /// Check that exactly one input is configured
+    pub(crate) fn validate(&self) -> Result<()> {
+        let inputs = self.line.is_some() as usize
+            + self.file.is_some() as usize
+            + self.command.is_some() as usize
+            + self.tcp.is_some() as usize;
+
+        if inputs != 1 {
+            return Err("Exactly one of line, file, command or tcp required")?;
+        }
+
+        Ok(())
+    }
+
+    /// Open configured input
+    async fn input(&self, id: LedId) -> Result<Box<dyn LedInput>> {
+        self.validate()?;
+
+        Ok(if let Some(line) = &self.line {
+            Box::new(GpioInput::new(id, self.chip.as_deref(), line, self.active, self.bias).await?)
+        } else if let Some(file) = &self.file {
+            Box::new(PollInput::file(file))
+        } else if let Some(command) = &self.command {
+            Box::new(PollInput::command(command)?)
+        } else {
+            Box::new(PollInput::tcp(
+                self.tcp.as_ref().ok_or("LED input required")?,
+            ))
+        })
+    }
+}
+
+/// Input with inverted level
+struct Inverted(Box<dyn LedInput>);
+
+#[async_trait::async_trait]
+impl LedInput for Inverted {
+    async fn level(&mut self) -> Result<bool> {
+        Ok(!self.0.level().await?)
+    }
+
+    async fn changed(&mut self) -> Result<bool> {
+        Ok(!self.0.changed().await?)
+    }
+}
+
+fn default_blink_window() -> u32 {
+    5000
+}
+
+fn default_blink_min_period() -> u32 {
+    200
+}
+
+/// LED edges analyzer
+///
+/// Classifies LED as steady, blinking or flickering using recent edges.
+struct LedAnalyzer {
+    /// Edges are forgotten after window
+    window: Duration,
+    /// Minimum blinking period
+    min_period: Duration,
+    /// Limit of remembered edges
+    max_edges: usize,
+    /// Current level
+    level: bool,
+    /// Times of recent edges
+    edges: VecDeque<Instant>,
+    /// End times and edge counts of recent sampling intervals
+    samples: VecDeque<(Instant, u32)>,
+    /// Derived state
+    pattern: LedPattern,
+}
+
+impl LedAnalyzer {
+    fn new(config: &LedConfig, level: bool) -> Self {
+        let window = Duration::from_millis(config.blink_window as _);
+        let min_period = Duration::from_millis(config.blink_min_period as _);
+        let mut analyzer = Self {
+            window,
+            min_period,
+            // more edges within window means period shorter than minimum
+            max_edges: (window.as_millis() / min_period.as_millis().max(1)) as usize * 2 + 3,
+            level,
+            edges: VecDeque::new(),
+            samples: VecDeque::new(),
+            pattern: LedPattern::default(),
+        };
+        analyzer.pattern = analyzer.steady();
+        analyzer
+    }
+
+    fn steady(&self) -> LedPattern {
+        LedPattern {
+            mode: if self.level {
+                LedMode::On
+            } else {
+                LedMode::Off
+            },
+            period: 0,
+        }
+    }
+
+    /// Mean blinking period
+    fn period(&self) -> Option<Duration> {
+        let count = self.edge_periods().count();
+        if count == 0 {
+            return None;
+        }
+
+        Some(self.edge_periods().sum::<Duration>() / count as u32)
+    }
+
+    /// Handle edge
+    fn edge(&mut self, level: bool, time: Instant) {
+        self.level = level;
+        if self.edges.len() >= self.max_edges {
+            self.edges.pop_front();
+        }
+        self.edges.push_back(time);
+        self.update(time);
+    }
+
+    /// Update derived state
+    fn update(&mut self, now: Instant) {
+        while let Some(time) = self.edges.front() {
+            if now.duration_since(*time) < self.window {
+                break;
+            }
+            self.edges.pop_front();
+        }
+
+        self.pattern = if let Some(period) = self.period() {
+            let last = *self.edges.back().unwrap();
+            let regular = self
+                .edge_periods()
+                .all(|other| within_tolerance(other, period));
+
+            if now.duration_since(last) > stale_after(period) {
+                // blinking or flickering stopped
+                self.edges.clear();
+                self.steady()
+            } else if regular && period >= self.min_period {
+                LedPattern {
+                    mode: LedMode::Blinking,
+                    period: period.as_millis() as _,
+                }
+            } else {
+                LedPattern {
+                    mode: LedMode::Activity,
+                    period: 0,
+                }
+            }
+        } else {
+            self.steady()
+        };
+    }
+
+    /// Handle sampled activity instead of separate edges
+    ///
+    /// Irregular changes cannot be told apart from blinking here so only rate matters.
+    fn sample(&mut self, level: bool, activity: LedActivity, interval: Duration, now: Instant) {
+        self.level = level;
+        self.samples.push_back((now, activity.edges));
+        while let Some((time, _)) = self.samples.front() {
+            if now.duration_since(*time) < self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        let edges = self.samples.iter().map(|(_, edges)| edges).sum::<u32>();
+        let span = interval * self.samples.len() as u32;
+
+        self.pattern = if edges < 2 {
+            self.steady()
+        } else {
+            let period = span * 2 / edges;
+            if period >= self.min_period {
+                LedPattern {
+                    mode: LedMode::Blinking,
+                    period: period.as_millis() as _,
+                }
+            } else {
+                LedPattern {
+                    mode: LedMode::Activity,
+                    period: 0,
+                }
+            }
+        };
+    }
+
+    /// Intervals between edges of same direction
+    fn edge_periods(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.edges
+            .iter()
+            .zip(self.edges.iter().skip(2))
+            .map(|(a, b)| *b - *a)
+    }
+
+    /// Time when derived state should be updated
+    fn deadline(&self) -> Option<Instant> {
+        let expire = *self.edges.front()? + self.window;
+        Some(if let Some(period) = self.period() {
+            expire.min(*self.edges.back()? + stale_after(period))
+        } else {
+            expire
+        })
+    }
+}
+
+fn within_tolerance(value: Duration, expected: Duration) -> bool {
+    value.abs_diff(expected) * 100 <= expected * BLINK_TOLERANCE
+}
+
+/// Edges absence duration after which LED considered steady
+fn stale_after(period: Duration) -> Duration {
+    period + period * BLINK_TOLERANCE / 100
+}
+
+/// LED on time and edges meter
+#[derive(Clone)]
+struct DutyMeter {
+    /// Current level
+    level: bool,
+    /// Time of last accounting
+    updated: Instant,
+    /// Start of sampling interval
+    started: Instant,
+    /// On time in sampling interval
+    interval_on: Duration,
+    /// Edges in sampling interval
+    interval_edges: u32,
+    /// Total on time
+    total_on: Duration,
+    /// Total edges
+    total_edges: u64,
+}
+
+impl DutyMeter {
+    fn new(level: bool, now: Instant) -> Self {
+        Self {
+            level,
+            updated: now,
+            started: now,
+            interval_on: Duration::ZERO,
+            interval_edges: 0,
+            total_on: Duration::ZERO,
+            total_edges: 0,
+        }
+    }
+
+    /// Account on time until given time
+    fn account(&mut self, now: Instant) {
+        let now = now.max(self.updated);
+        if self.level {
+            let on = now - self.updated;
+            self.interval_on += on;
+            self.total_on += on;
+        }
+        self.updated = now;
+    }
+
+    /// Handle edge
+    fn edge(&mut self, level: bool, time: Instant) {
+        self.account(time);
+        self.level = level;
+        self.interval_edges += 1;
+        self.total_edges += 1;
+    }
+
+    /// Get activity over sampling interval and start new one
+    fn sample(&mut self, now: Instant) -> LedActivity {
+        self.account(now);
+        let interval = self.updated - self.started;
+        let duty = if interval.is_zero() {
+            if self.level {
+                100
+            } else {
+                0
+            }
+        } else {
+            (self.interval_on.as_micros() * 100 / interval.as_micros()) as u8
+        };
+        let activity = LedActivity {
+            duty,
+            edges: self.interval_edges,
+        };
+        self.started = self.updated;
+        self.interval_on = Duration::ZERO;
+        self.interval_edges = 0;
+        activity
+    }
+
+    fn counters(&self, now: Instant) -> LedCounters {
+        let mut on = self.total_on;
+        if self.level {
+            on += now.saturating_duration_since(self.updated);
+        }
+        LedCounters {
+            edges: self.total_edges,
+            on_time: on.as_millis() as _,
+        }
+    }
+}
+
+/// Check that derived state changed significantly
+fn pattern_changed(old: &LedPattern, new: &LedPattern) -> bool {
+    old.mode != new.mode
+        || !within_tolerance(
+            Duration::from_millis(new.period as _),
+            Duration::from_millis(old.period as _),
+        )
 }
 
 impl Led {
     pub async fn new(id: LedId, config: &LedConfig) -> Result<Self> {
-        let mut inputs = Chip::new(&config.chip)
-            .await?
-            .request_lines(
-                Options::input(&[config.line])
-                    .active(config.active)
-                    .bias(config.bias)
-                    .edge(EdgeDetect::Both)
-                    .consumer(format!("{}-{}-led", env!("CARGO_PKG_NAME"), id)),
-            )
-            .await?;
-
-        let (state_sender, state_receiver) = watch::channel(inputs.get_values([false]).await?[0]);
-
-        spawn(async move {
-            log::debug!("{id}: Initialize receiving events");
-
-            loop {
-                select! {
-                    // LED object dropped
-                    _ = state_sender.closed() => break,
-                    result = inputs.read_event() => match result {
-                        // Edge event received
-                        Ok(event) => {
-                            log::trace!("{id}: Event received: {}", event);
-                            let state = matches!(event.edge, Edge::Rising);
-                            if let Err(error) = state_sender.send(state) {
-                                log::error!("{id}: Error when sending state: {}", error);
+        let mut input = config
+            .input(id)
+            .await
+            .map_err(|error| format!("LED {id}: {error}"))?;
+
+        // GPIO lines handle active state by itself
+        if config.active == Active::Low && config.line.is_none() {
+            input = Box::new(Inverted(input));
+        }
+
+        let level = input.level().await?;
+        let mut analyzer = LedAnalyzer::new(config, level);
+        let debounce = Duration::from_millis(config.debounce as _);
+        let sample = Duration::from_millis(config.sample as _);
+        let meter = Arc::new(Mutex::new(DutyMeter::new(level, Instant::now())));
+
+        let (state_sender, state_receiver) = watch::channel(level);
+        let (pattern_sender, pattern_receiver) = watch::channel(analyzer.pattern);
+        let (activity_sender, activity_receiver) =
+            watch::channel((!sample.is_zero()).then(LedActivity::default));
+
+        spawn({
+            let shared_meter = meter.clone();
+            let mut meter = meter.lock().unwrap().clone();
+            async move {
+                log::debug!("{id}: Initialize receiving events");
+
+                let mut level = level;
+                // level waiting for debounce with deadline and edge time
+                let mut pending: Option<(Instant, bool, Instant)> = None;
+                let mut next_sample = (!sample.is_zero()).then(|| Instant::now() + sample);
+
+                loop {
+                    let debounced = pending.map(|(deadline, _, _)| deadline);
+                    let deadline = analyzer.deadline();
+
+                    select! {
+                        // LED object dropped
+                        _ = state_sender.closed() => break,
+                        result = input.changed() => match result {
+                            // Level change received
+                            Ok(state) => {
+                                let now = Instant::now();
+                                if debounce.is_zero() {
+                                    pending = Some((now, state, now));
+                                } else if state == level {
+                                    // pulse too short
+                                    pending = None;
+                                } else {
+                                    pending = Some((now + debounce, state, now));
+                                }
+                            }
+                            // Input error happenned
+                            Err(error) => {
+                                log::error!("{id}: Error when receiving event: {}", error);
                                 break;
                             }
+                        },
+                        _ = sleep_until(debounced.unwrap_or_else(Instant::now)), if debounced.is_some() => {}
+                        _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {}
+                        _ = sleep_until(next_sample.unwrap_or_else(Instant::now)), if next_sample.is_some() => {}
+                    }
+
+                    let now = Instant::now();
+
+                    if let Some((deadline, state, time)) = pending {
+                        if deadline <= now {
+                            pending = None;
+                            level = state;
+                            meter.edge(state, time);
+                            // sampled LEDs are analyzed once per interval
+                            if sample.is_zero() {
+                                analyzer.edge(state, time);
+                                *shared_meter.lock().unwrap() = meter.clone();
+                                if let Err(error) = state_sender.send(state) {
+                                    log::error!("{id}: Error when sending state: {}", error);
+                                    break;
+                                }
+                            }
                         }
-                        // Input error happenned
-                        Err(error) => {
-                            log::error!("{id}: Error when receiving event: {}", error);
-                            break;
+                    }
+
+                    if let Some(time) = next_sample {
+                        if time <= now {
+                            next_sample = Some(time + sample);
+                            let activity = meter.sample(now);
+                            *shared_meter.lock().unwrap() = meter.clone();
+                            log::trace!("{id}: Activity: {}", activity);
+                            analyzer.sample(level, activity, sample, now);
+                            activity_sender.send_if_modified(|current| {
+                                let changed = *current != Some(activity);
+                                *current = Some(activity);
+                                changed
+                            });
+                            state_sender.send_if_modified(|state| {
+                                let changed = *state != level;
+                                *state = level;
+                                changed
+                            });
+                        }
+                    }
+
+                    if sample.is_zero() {
+                        analyzer.update(now);
+                    }
+
+                    pattern_sender.send_if_modified(|pattern| {
+                        if pattern_changed(pattern, &analyzer.pattern) {
+                            log::debug!("{id}: Pattern changed: {}", analyzer.pattern);
+                            *pattern = analyzer.pattern;
+                            true
+                        } else {
+                            false
                         }
-                    },
+                    });
                 }
+                log::debug!("{id}: Finalize receiving events");
             }
-            log::debug!("{id}: Finalize receiving events");
         });
 
-        Ok(Self { state_receiver })
+        Ok(Self {
+            state_receiver,
+            pattern_receiver,
+            activity_receiver,
+            meter,
+        })
     }
 
     /// Get current state
@@ -81,10 +554,47 @@ impl Led {
     pub fn watch(&self) -> watch::Receiver<bool> {
         self.state_receiver.clone()
     }
+
+    /// Get derived state
+    pub fn pattern(&self) -> LedPattern {
+        *self.pattern_receiver.borrow()
+    }
+
+    /// Watch derived state changes
+    pub fn watch_pattern(&self) -> watch::Receiver<LedPattern> {
+        self.pattern_receiver.clone()
+    }
+
+    /// Get activity over last sampling interval (when sampling enabled)
+    pub fn activity(&self) -> Option<LedActivity> {
+        *self.activity_receiver.borrow()
+    }
+
+    /// Watch sampled activity changes
+    pub fn watch_activity(&self) -> watch::Receiver<Option<LedActivity>> {
+        self.activity_receiver.clone()
+    }
+
+    /// Get activity counters
+    pub fn counters(&self) -> LedCounters {
+        self.meter.lock().unwrap().counters(Instant::now())
+    }
+
+    /// Get state report
+    pub fn info(&self) -> LedInfo {
+        let pattern = self.pattern();
+        LedInfo {
+            state: self.state(),
+            mode: pattern.mode,
+            period: pattern.period,
+            activity: self.activity(),
+            counters: self.counters(),
+        }
+    }
 }
 
 /// LEDs configuration
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 #[serde(transparent)]
 pub struct LedsConfig {
     /// LED configurations
@@ -96,18 +606,189 @@ pub struct LedsConfig {
 #[educe(Deref)]
 pub struct Leds {
     /// LEDs
-    leds: HashMap<LedId, Led>,
+    leds: HashMap<LedId, Arc<Led>>,
 }
 
 impl Leds {
     /// Create LEDs state service using specified config
     pub async fn new(config: &LedsConfig) -> Result<Self> {
+        Self::with_reused(config, HashMap::default()).await
+    }
+
+    /// Create LEDs state service reusing already instantiated LEDs
+    pub async fn with_reused(
+        config: &LedsConfig,
+        mut reused: HashMap<LedId, Arc<Led>>,
+    ) -> Result<Self> {
         let mut leds = HashMap::default();
 
         for (id, config) in &config.leds {
-            leds.insert(*id, Led::new(*id, config).await?);
+            let led = if let Some(led) = reused.remove(id) {
+                led
+            } else {
+                Arc::new(Led::new(*id, config).await?)
+            };
+            leds.insert(*id, led);
         }
 
         Ok(Self { leds })
     }
+
+    /// Get LEDs which config wasn't changed
+    pub fn unchanged(&self, old: &LedsConfig, new: &LedsConfig) -> HashMap<LedId, Arc<Led>> {
+        self.leds
+            .iter()
+            .filter(|(id, _)| old.leds.get(id) == new.leds.get(id))
+            .map(|(id, led)| (*id, led.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> LedConfig {
+        toml::from_str("line = 1").unwrap()
+    }
+
+    #[test]
+    fn duty_meter() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut meter = DutyMeter::new(false, start);
+
+        meter.edge(true, at(100));
+        meter.edge(false, at(200));
+        meter.edge(true, at(700));
+        assert_eq!(meter.sample(at(1000)), LedActivity { duty: 40, edges: 3 });
+
+        // edge delayed by debouncing belongs to previous interval
+        meter.edge(false, at(900));
+        assert_eq!(meter.sample(at(2000)), LedActivity { duty: 0, edges: 1 });
+
+        assert_eq!(
+            meter.counters(at(2500)),
+            LedCounters {
+                edges: 4,
+                on_time: 400
+            }
+        );
+    }
+
+    #[test]
+    fn analyze_pattern() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut analyzer = LedAnalyzer::new(&config(), false);
+        assert_eq!(analyzer.pattern.mode, LedMode::Off);
+
+        // single change is steady
+        analyzer.edge(true, at(0));
+        assert_eq!(analyzer.pattern.mode, LedMode::On);
+
+        // standby blinking with short flashes
+        for (index, ms) in [900, 1000, 1900, 2000, 2900].into_iter().enumerate() {
+            analyzer.edge(index % 2 == 1, at(ms));
+        }
+        assert_eq!(
+            analyzer.pattern,
+            LedPattern {
+                mode: LedMode::Blinking,
+                period: 1000
+            }
+        );
+        assert_eq!(analyzer.deadline(), Some(at(2900 + 1250)));
+
+        // blinking stopped in off state
+        analyzer.update(at(4200));
+        assert_eq!(analyzer.pattern.mode, LedMode::Off);
+        assert_eq!(analyzer.deadline(), None);
+
+        // irregular flickering
+        for (index, ms) in [5000, 5010, 5100, 5130, 5600, 5620].into_iter().enumerate() {
+            analyzer.edge(index % 2 == 0, at(ms));
+        }
+        assert_eq!(analyzer.pattern.mode, LedMode::Activity);
+
+        // too fast regular changes
+        for (index, ms) in (0..10).map(|index| 6000 + index * 20).enumerate() {
+            analyzer.edge(index % 2 == 0, at(ms));
+        }
+        assert_eq!(analyzer.pattern.mode, LedMode::Activity);
+
+        // edges of fast activity are limited
+        for (index, ms) in (0..1000).map(|index| 7000 + index).enumerate() {
+            analyzer.edge(index % 2 == 0, at(ms));
+        }
+        assert_eq!(analyzer.pattern.mode, LedMode::Activity);
+        assert_eq!(analyzer.edges.len(), 5000 / 200 * 2 + 3);
+
+        assert!(!pattern_changed(
+            &LedPattern {
+                mode: LedMode::Blinking,
+                period: 1000
+            },
+            &LedPattern {
+                mode: LedMode::Blinking,
+                period: 1100
+            }
+        ));
+    }
+
+    #[test]
+    fn analyze_sampled_pattern() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let interval = Duration::from_millis(1000);
+        let mut analyzer = LedAnalyzer::new(&config(), false);
+
+        analyzer.sample(true, LedActivity { duty: 60, edges: 1 }, interval, at(1000));
+        assert_eq!(analyzer.pattern.mode, LedMode::On);
+
+        analyzer.sample(
+            false,
+            LedActivity { duty: 50, edges: 2 },
+            interval,
+            at(2000),
+        );
+        analyzer.sample(
+            false,
+            LedActivity { duty: 50, edges: 2 },
+            interval,
+            at(3000),
+        );
+        assert_eq!(
+            analyzer.pattern,
+            LedPattern {
+                mode: LedMode::Blinking,
+                period: 1200
+            }
+        );
+
+        analyzer.sample(
+            true,
+            LedActivity {
+                duty: 30,
+                edges: 80,
+            },
+            interval,
+            at(4000),
+        );
+        assert_eq!(analyzer.pattern.mode, LedMode::Activity);
+
+        // fast changes left the window
+        for ms in [5000, 6000, 7000, 8000, 9000] {
+            analyzer.sample(
+                true,
+                LedActivity {
+                    duty: 100,
+                    edges: 0,
+                },
+                interval,
+                at(ms),
+            );
+        }
+        assert_eq!(analyzer.pattern.mode, LedMode::On);
+    }
 }
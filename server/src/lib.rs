@@ -1,7 +1,14 @@
 mod args;
 mod buttons;
+mod check;
+mod devices;
+mod gpio;
+mod inputs;
 mod leds;
+mod power;
+mod probe;
 mod result;
+mod sensors;
 mod server;
 
 #[cfg(feature = "http")]
@@ -13,16 +20,57 @@ mod dbus;
 #[cfg(feature = "hid")]
 mod hid;
 
+#[cfg(feature = "hid")]
+mod gadget;
+
+#[cfg(feature = "hid")]
+mod udc;
+
 #[cfg(feature = "video")]
 mod video;
 
+#[cfg(feature = "video")]
+mod jpeg;
+
+#[cfg(feature = "video")]
+mod v4l2;
+
+#[cfg(feature = "webrtc")]
+mod h264;
+
+#[cfg(feature = "webrtc")]
+mod m2m;
+
+#[cfg(feature = "webrtc")]
+mod rtc;
+
+#[cfg(feature = "record")]
+mod record;
+
+#[cfg(feature = "relay")]
+mod relay;
+
+#[cfg(feature = "screen")]
+mod screen;
+
+#[cfg(feature = "screenshot")]
+mod screenshot;
+
 pub use tracing as log;
 
-pub use args::Args;
+pub use args::{Args, Command, ProbeArgs};
 pub use buttons::{Buttons, ButtonsConfig};
-pub use leds::{Leds, LedsConfig};
-pub use server::{GracefulShutdown, Server, ServerConfig, ServerRef};
-pub use ukvm_core::{ButtonId, LedId};
+pub use gpio::GpioLine;
+pub use inputs::{CommandInputConfig, FileInputConfig, LedInput, TcpInputConfig};
+pub use leds::{LedCounters, LedInfo, Leds, LedsConfig};
+pub use power::{Power, PowerConfig};
+pub use probe::{GpioChipInfo, GpioLineInfo, Probe};
+pub use sensors::{SensorConfig, SensorInfo, Sensors, SensorsConfig};
+pub use server::{GracefulShutdown, ReloadError, Server, ServerConfig, ServerRef};
+pub use ukvm_core::{
+    ButtonId, LedActivity, LedId, LedMode, LedPattern, PowerState, SensorKind, SensorReading,
+    SensorStatus,
+};
 
 #[cfg(feature = "http")]
 pub use ukvm_core::{SocketInput, SocketOutput};
@@ -39,7 +87,33 @@ pub use ukvm_core::{HttpAddr, HttpBindAddr};
 #[cfg(feature = "hid")]
 pub use hid::{Hid, HidConfig};
 
+#[cfg(feature = "hid")]
+pub use gadget::{Gadget, GadgetConfig, GadgetFunction, MassStorageConfig};
+
+#[cfg(feature = "hid")]
+pub use probe::HidDeviceInfo;
+
+#[cfg(feature = "hid")]
+pub use udc::{Udc, UdcWaker, UsbState};
+
 #[cfg(feature = "video")]
-pub use video::{Video, VideoConfig};
+pub use video::{
+    Video, VideoConfig, VideoHealth, VideoHealthConfig, VideoHealthInfo, VideoMode, VideoSignal,
+};
+
+#[cfg(feature = "webrtc")]
+pub use rtc::{WebRtcConfig, WebRtcPeer};
+
+#[cfg(feature = "relay")]
+pub use relay::{HidRelayConfig, HttpOutletConfig, SerialRelayConfig, SysfsLedConfig};
+
+#[cfg(feature = "record")]
+pub use record::{Recorder, RecorderConfig};
+
+#[cfg(feature = "screen")]
+pub use screen::{Screen, ScreenConfig};
+
+#[cfg(feature = "screenshot")]
+pub use screenshot::{Crop, ImageFormat, ScreenshotOptions, Screenshots};
 
 pub use result::{Error, Result};
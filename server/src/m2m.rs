@@ -0,0 +1,799 @@
+use crate::{devices::list_devices, log, Result};
+use linux_video::{types::*, Device};
+use nix::{
+    errno::Errno,
+    ioctl_readwrite, ioctl_write_ptr, libc,
+    poll::{poll, PollFd, PollFlags, PollTimeout},
+    sys::mman::{mmap, munmap, MapFlags, ProtFlags},
+};
+use openh264::formats::YUVSource;
+use std::{
+    ffi::c_void,
+    fs::{File, OpenOptions},
+    num::NonZeroUsize,
+    ops::Range,
+    os::{
+        fd::{AsFd, AsRawFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::{Path, PathBuf},
+    ptr::NonNull,
+    time::{Duration, Instant},
+};
+
+/// Encoded frame waiting timeout
+const ENCODE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Number of raw frame buffers
+const OUTPUT_BUFFERS: u32 = 2;
+
+/// Number of encoded frame buffers
+const CAPTURE_BUFFERS: u32 = 4;
+
+const BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
+const BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+const BUF_TYPE_VIDEO_CAPTURE_MPLANE: u32 = 9;
+const BUF_TYPE_VIDEO_OUTPUT_MPLANE: u32 = 10;
+
+const MEMORY_MMAP: u32 = 1;
+
+const FIELD_NONE: u32 = 1;
+
+const COLORSPACE_JPEG: u32 = 7;
+
+const SEL_TGT_CROP: u32 = 0;
+
+const PIX_FMT_YUV420: u32 = u32::from_le_bytes(*b"YU12");
+const PIX_FMT_H264: u32 = u32::from_le_bytes(*b"H264");
+
+const CID_CODEC_BASE: u32 = 0x00990900;
+const CID_BITRATE_MODE: u32 = CID_CODEC_BASE + 206;
+const CID_BITRATE: u32 = CID_CODEC_BASE + 207;
+const CID_REPEAT_SEQ_HEADER: u32 = CID_CODEC_BASE + 226;
+const CID_FORCE_KEY_FRAME: u32 = CID_CODEC_BASE + 229;
+const CID_H264_PROFILE: u32 = CID_CODEC_BASE + 363;
+
+const BITRATE_MODE_CBR: i32 = 1;
+const H264_PROFILE_CONSTRAINED_BASELINE: i32 = 1;
+
+/// Single-planar pixel format
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PixFormat {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    bytesperline: u32,
+    sizeimage: u32,
+    colorspace: u32,
+    priv_: u32,
+    flags: u32,
+    ycbcr_enc: u32,
+    quantization: u32,
+    xfer_func: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct PlanePixFormat {
+    sizeimage: u32,
+    bytesperline: u32,
+    reserved: [u16; 6],
+}
+
+/// Multi-planar pixel format
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct PixFormatMplane {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    colorspace: u32,
+    plane_fmt: [PlanePixFormat; 8],
+    num_planes: u8,
+    flags: u8,
+    ycbcr_enc: u8,
+    quantization: u8,
+    xfer_func: u8,
+    reserved: [u8; 7],
+}
+
+#[repr(C)]
+union FormatUnion {
+    pix: PixFormat,
+    pix_mp: PixFormatMplane,
+    // aligned like union with pointers
+    raw: [usize; 200 / core::mem::size_of::<usize>()],
+}
+
+#[repr(C)]
+struct Format {
+    type_: u32,
+    fmt: FormatUnion,
+}
+
+#[repr(C)]
+struct RequestBuffers {
+    count: u32,
+    type_: u32,
+    memory: u32,
+    capabilities: u32,
+    flags: u8,
+    reserved: [u8; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union PlaneM {
+    mem_offset: u32,
+    userptr: libc::c_ulong,
+    fd: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Plane {
+    bytesused: u32,
+    length: u32,
+    m: PlaneM,
+    data_offset: u32,
+    reserved: [u32; 11],
+}
+
+#[repr(C)]
+union BufferM {
+    offset: u32,
+    userptr: libc::c_ulong,
+    planes: *mut Plane,
+    fd: i32,
+}
+
+#[repr(C)]
+struct Buffer {
+    index: u32,
+    type_: u32,
+    bytesused: u32,
+    flags: u32,
+    field: u32,
+    timestamp: libc::timeval,
+    timecode: [u32; 4],
+    sequence: u32,
+    memory: u32,
+    m: BufferM,
+    length: u32,
+    reserved2: u32,
+    request_fd: i32,
+}
+
+#[repr(C)]
+struct Control {
+    id: u32,
+    value: i32,
+}
+
+#[repr(C)]
+struct OutputParm {
+    capability: u32,
+    outputmode: u32,
+    timeperframe: [u32; 2],
+    extendedmode: u32,
+    writebuffers: u32,
+    reserved: [u32; 4],
+}
+
+#[repr(C)]
+struct StreamParm {
+    type_: u32,
+    parm: OutputParm,
+    reserved: [u8; 160],
+}
+
+#[repr(C)]
+struct Selection {
+    type_: u32,
+    target: u32,
+    flags: u32,
+    rect: [u32; 4],
+    reserved: [u32; 9],
+}
+
+ioctl_readwrite!(s_fmt, b'V', 5, Format);
+ioctl_readwrite!(reqbufs, b'V', 8, RequestBuffers);
+ioctl_readwrite!(querybuf, b'V', 9, Buffer);
+ioctl_readwrite!(qbuf, b'V', 15, Buffer);
+ioctl_readwrite!(dqbuf, b'V', 17, Buffer);
+ioctl_write_ptr!(streamon, b'V', 18, libc::c_int);
+ioctl_write_ptr!(streamoff, b'V', 19, libc::c_int);
+ioctl_readwrite!(s_parm, b'V', 22, StreamParm);
+ioctl_readwrite!(s_ctrl, b'V', 28, Control);
+ioctl_readwrite!(s_selection, b'V', 95, Selection);
+
+/// Memory-to-memory encoder device
+#[derive(Clone, Debug)]
+pub struct M2mDevice {
+    /// Device node path
+    pub path: PathBuf,
+    /// Multi-planar API is used
+    mplane: bool,
+}
+
+impl M2mDevice {
+    /// Find H.264 encoder which accepts planar YUV 4:2:0 frames
+    pub async fn find() -> Option<Self> {
+        let dev = Path::new("/dev");
+
+        for name in list_devices(dev, "video").await.ok()? {
+            let path = dev.join(name);
+            match Self::probe(&path).await {
+                Ok(Some(device)) => return Some(device),
+                Ok(None) => {}
+                Err(error) => log::debug!("Unable to probe {}: {error}", path.display()),
+            }
+        }
+
+        None
+    }
+
+    async fn probe(path: &Path) -> Result<Option<Self>> {
+        let device = Device::open(path).await?;
+
+        let caps = device.capabilities().await?;
+        let caps = if caps.capabilities().contains(CapabilityFlag::DeviceCaps) {
+            caps.device_capabilities()
+        } else {
+            caps.capabilities()
+        };
+
+        let (mplane, capture, output) = if caps.contains(CapabilityFlag::VideoM2mMplane) {
+            (
+                true,
+                BufferType::VideoCaptureMplane,
+                BufferType::VideoOutputMplane,
+            )
+        } else if caps.contains(CapabilityFlag::VideoM2m) {
+            (false, BufferType::VideoCapture, BufferType::VideoOutput)
+        } else {
+            return Ok(None);
+        };
+
+        if !caps.contains(CapabilityFlag::Streaming)
+            || !has_format(&device, capture, FourCc::H264).await?
+            || !has_format(&device, output, FourCc::Yuv420).await?
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            path: path.into(),
+            mplane,
+        }))
+    }
+}
+
+async fn has_format(device: &Device, type_: BufferType, pixel_format: FourCc) -> Result<bool> {
+    let mut formats = device.formats(type_);
+    while let Some(format) = formats.fetch_next().await? {
+        if format.pixel_format() == pixel_format {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Memory mapped buffer
+struct Mapping {
+    data: NonNull<c_void>,
+    length: usize,
+}
+
+// mapping is owned exclusively like boxed data
+unsafe impl Send for Mapping {}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        let _ = unsafe { munmap(self.data, self.length) };
+    }
+}
+
+impl AsRef<[u8]> for Mapping {
+    fn as_ref(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.data.as_ptr() as _, self.length) }
+    }
+}
+
+impl AsMut<[u8]> for Mapping {
+    fn as_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.data.as_ptr() as _, self.length) }
+    }
+}
+
+/// V4L2 memory-to-memory H.264 encoder
+///
+/// Raw frames are written to output queue and encoded ones are read from capture queue.
+pub struct M2mEncoder {
+    mplane: bool,
+    size: (usize, usize),
+    /// Luma bytes per line of raw frame
+    stride: usize,
+    /// Rows of raw frame planes
+    rows: usize,
+    /// Raw frame buffer size
+    frame_size: usize,
+    output: Vec<Mapping>,
+    capture: Vec<Mapping>,
+    /// Output buffers which can be filled
+    free: Vec<u32>,
+    file: File,
+}
+
+impl Drop for M2mEncoder {
+    fn drop(&mut self) {
+        let fd = self.file.as_raw_fd();
+        for type_ in [self.output_type(), self.capture_type()] {
+            let _ = unsafe { streamoff(fd, &(type_ as libc::c_int)) };
+        }
+    }
+}
+
+impl M2mEncoder {
+    /// Open encoder for frames of specified size
+    pub fn open(device: &M2mDevice, size: (usize, usize), bitrate: u32, fps: u32) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&device.path)?;
+
+        let mut encoder = Self {
+            mplane: device.mplane,
+            size,
+            stride: 0,
+            rows: 0,
+            frame_size: 0,
+            output: Vec::new(),
+            capture: Vec::new(),
+            free: Vec::new(),
+            file,
+        };
+
+        let (width, height) = (size.0 as u32, size.1 as u32);
+
+        let format = encoder.set_format(encoder.output_type(), width, height, PIX_FMT_YUV420)?;
+        if format.pixelformat != PIX_FMT_YUV420
+            || format.width != width
+            || format.height < height
+            || format.bytesperline < width
+            || (format.sizeimage as usize)
+                < format.bytesperline as usize * format.height as usize * 3 / 2
+        {
+            Err(format!("Unsupported frame size {width}x{height}"))?;
+        }
+
+        encoder.stride = format.bytesperline as _;
+        encoder.rows = format.height as _;
+        encoder.frame_size = format.sizeimage as _;
+
+        if format.height != height {
+            // aligned rows are cropped in stream headers
+            if let Err(error) = encoder.set_crop(width, height) {
+                Err(format!("Unable to crop frame to {width}x{height}: {error}"))?;
+            }
+        }
+
+        let format = encoder.set_format(encoder.capture_type(), width, height, PIX_FMT_H264)?;
+        if format.pixelformat != PIX_FMT_H264 {
+            Err("H.264 format not accepted")?;
+        }
+
+        // optional settings so unsupported ones are ignored
+        for (id, value) in [
+            (CID_BITRATE_MODE, BITRATE_MODE_CBR),
+            (CID_BITRATE, (bitrate * 1000) as _),
+            (CID_H264_PROFILE, H264_PROFILE_CONSTRAINED_BASELINE),
+            (CID_REPEAT_SEQ_HEADER, 1),
+        ] {
+            if let Err(error) = encoder.set_control(id, value) {
+                log::debug!("Unable to set encoder control {id:#x}: {error}");
+            }
+        }
+
+        if let Err(error) = encoder.set_fps(fps) {
+            log::debug!("Unable to set encoder frame rate: {error}");
+        }
+
+        encoder.output = encoder.request_buffers(encoder.output_type(), OUTPUT_BUFFERS)?;
+        encoder.capture = encoder.request_buffers(encoder.capture_type(), CAPTURE_BUFFERS)?;
+        encoder.free = (0..encoder.output.len() as u32).rev().collect();
+
+        for index in 0..encoder.capture.len() as u32 {
+            encoder.queue(encoder.capture_type(), index, 0)?;
+        }
+
+        let fd = encoder.file.as_raw_fd();
+        for type_ in [encoder.capture_type(), encoder.output_type()] {
+            unsafe { streamon(fd, &(type_ as libc::c_int)) }
+                .map_err(|error| format!("Unable to start encoder: {error}"))?;
+        }
+
+        Ok(encoder)
+    }
+
+    /// Size of frames which encoder accepts
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    /// Encode frame to H.264 access unit in Annex B format
+    pub fn encode(&mut self, yuv: &impl YUVSource, keyframe: bool) -> Result<Vec<u8>> {
+        if yuv.dimensions() != self.size {
+            Err("Frame size changed")?;
+        }
+
+        if keyframe {
+            if let Err(error) = self.set_control(CID_FORCE_KEY_FRAME, 0) {
+                log::debug!("Unable to force key frame: {error}");
+            }
+        }
+
+        let deadline = Instant::now() + ENCODE_TIMEOUT;
+
+        let index = loop {
+            self.reclaim()?;
+            if let Some(index) = self.free.pop() {
+                break index;
+            }
+            if !self.wait(PollFlags::POLLOUT, deadline)? {
+                Err("Timeout when waiting for free encoder buffer")?;
+            }
+        };
+
+        let output = &mut self.output[index as usize];
+        copy_i420(yuv, output.as_mut(), self.stride, self.rows);
+        if let Err(error) = self.queue(self.output_type(), index, self.frame_size) {
+            self.free.push(index);
+            return Err(error);
+        }
+
+        let mut data = Vec::new();
+
+        // parameter sets may come in separate buffers
+        while !has_slice(&data) {
+            if !self.wait(PollFlags::POLLIN, deadline)? {
+                Err("Timeout when encoding H.264")?;
+            }
+            while let Some((index, range)) = self.dequeue(self.capture_type())? {
+                data.extend_from_slice(&self.capture[index as usize].as_ref()[range]);
+                self.queue(self.capture_type(), index, 0)?;
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn output_type(&self) -> u32 {
+        if self.mplane {
+            BUF_TYPE_VIDEO_OUTPUT_MPLANE
+        } else {
+            BUF_TYPE_VIDEO_OUTPUT
+        }
+    }
+
+    fn capture_type(&self) -> u32 {
+        if self.mplane {
+            BUF_TYPE_VIDEO_CAPTURE_MPLANE
+        } else {
+            BUF_TYPE_VIDEO_CAPTURE
+        }
+    }
+
+    /// Set format of queue and get accepted one
+    ///
+    /// Colors are full range BT.601 like in decoded JPEG frames.
+    fn set_format(
+        &self,
+        type_: u32,
+        width: u32,
+        height: u32,
+        pixelformat: u32,
+    ) -> Result<PixFormat> {
+        let mut format = unsafe { core::mem::zeroed::<Format>() };
+        format.type_ = type_;
+
+        if self.mplane {
+            let pix_mp = unsafe { &mut format.fmt.pix_mp };
+            pix_mp.width = width;
+            pix_mp.height = height;
+            pix_mp.pixelformat = pixelformat;
+            pix_mp.field = FIELD_NONE;
+            pix_mp.colorspace = COLORSPACE_JPEG;
+            pix_mp.num_planes = 1;
+        } else {
+            let pix = unsafe { &mut format.fmt.pix };
+            pix.width = width;
+            pix.height = height;
+            pix.pixelformat = pixelformat;
+            pix.field = FIELD_NONE;
+            pix.colorspace = COLORSPACE_JPEG;
+        }
+
+        unsafe { s_fmt(self.file.as_raw_fd(), &mut format) }
+            .map_err(|error| format!("Unable to set encoder format: {error}"))?;
+
+        Ok(if self.mplane {
+            let pix_mp = unsafe { format.fmt.pix_mp };
+            if pix_mp.num_planes != 1 {
+                Err("Encoder requires separate planes")?;
+            }
+            let plane = pix_mp.plane_fmt[0];
+            PixFormat {
+                width: pix_mp.width,
+                height: pix_mp.height,
+                pixelformat: pix_mp.pixelformat,
+                bytesperline: plane.bytesperline,
+                sizeimage: plane.sizeimage,
+                ..unsafe { core::mem::zeroed() }
+            }
+        } else {
+            unsafe { format.fmt.pix }
+        })
+    }
+
+    fn set_crop(&self, width: u32, height: u32) -> nix::Result<()> {
+        let mut selection = unsafe { core::mem::zeroed::<Selection>() };
+        // selection API uses single-planar types only
+        selection.type_ = BUF_TYPE_VIDEO_OUTPUT;
+        selection.target = SEL_TGT_CROP;
+        selection.rect = [0, 0, width, height];
+
+        unsafe { s_selection(self.file.as_raw_fd(), &mut selection) }?;
+        Ok(())
+    }
+
+    fn set_control(&self, id: u32, value: i32) -> nix::Result<()> {
+        let mut control = Control { id, value };
+        unsafe { s_ctrl(self.file.as_raw_fd(), &mut control) }?;
+        Ok(())
+    }
+
+    fn set_fps(&self, fps: u32) -> nix::Result<()> {
+        let mut param = unsafe { core::mem::zeroed::<StreamParm>() };
+        param.type_ = self.output_type();
+        param.parm.timeperframe = [1, fps.max(1)];
+
+        unsafe { s_parm(self.file.as_raw_fd(), &mut param) }?;
+        Ok(())
+    }
+
+    /// Allocate and map queue buffers
+    fn request_buffers(&self, type_: u32, count: u32) -> Result<Vec<Mapping>> {
+        let fd = self.file.as_raw_fd();
+
+        let mut request = RequestBuffers {
+            count,
+            type_,
+            memory: MEMORY_MMAP,
+            capabilities: 0,
+            flags: 0,
+            reserved: [0; 3],
+        };
+
+        unsafe { reqbufs(fd, &mut request) }
+            .map_err(|error| format!("Unable to request encoder buffers: {error}"))?;
+
+        (0..request.count)
+            .map(|index| {
+                let mut plane = unsafe { core::mem::zeroed::<Plane>() };
+                let mut buffer = self.buffer(type_, index, &mut plane);
+
+                unsafe { querybuf(fd, &mut buffer) }
+                    .map_err(|error| format!("Unable to query encoder buffer: {error}"))?;
+
+                let (offset, length) = if self.mplane {
+                    (unsafe { plane.m.mem_offset }, plane.length)
+                } else {
+                    (unsafe { buffer.m.offset }, buffer.length)
+                };
+
+                let length = length as usize;
+
+                let data = unsafe {
+                    mmap(
+                        None,
+                        NonZeroUsize::new(length).ok_or("Empty encoder buffer")?,
+                        ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                        MapFlags::MAP_SHARED,
+                        self.file.as_fd(),
+                        offset as _,
+                    )
+                }
+                .map_err(|error| format!("Unable to map encoder buffer: {error}"))?;
+
+                Ok(Mapping { data, length })
+            })
+            .collect()
+    }
+
+    /// Make buffer descriptor
+    ///
+    /// Plane must outlive descriptor when multi-planar API is used.
+    fn buffer(&self, type_: u32, index: u32, plane: &mut Plane) -> Buffer {
+        let mut buffer = unsafe { core::mem::zeroed::<Buffer>() };
+        buffer.type_ = type_;
+        buffer.index = index;
+        buffer.memory = MEMORY_MMAP;
+        if self.mplane {
+            buffer.m.planes = plane;
+            buffer.length = 1;
+        }
+        buffer
+    }
+
+    fn queue(&self, type_: u32, index: u32, length: usize) -> Result<()> {
+        let buffers = if type_ == self.capture_type() {
+            &self.capture
+        } else {
+            &self.output
+        };
+
+        let mut plane = unsafe { core::mem::zeroed::<Plane>() };
+        plane.bytesused = length as _;
+        plane.length = buffers[index as usize].length as _;
+
+        let mut buffer = self.buffer(type_, index, &mut plane);
+        buffer.bytesused = length as _;
+
+        unsafe { qbuf(self.file.as_raw_fd(), &mut buffer) }
+            .map_err(|error| format!("Unable to queue encoder buffer: {error}"))?;
+
+        Ok(())
+    }
+
+    /// Dequeue buffer when ready
+    ///
+    /// Returns index and range of data.
+    fn dequeue(&self, type_: u32) -> Result<Option<(u32, Range<usize>)>> {
+        let mut plane = unsafe { core::mem::zeroed::<Plane>() };
+        let mut buffer = self.buffer(type_, 0, &mut plane);
+
+        match unsafe { dqbuf(self.file.as_raw_fd(), &mut buffer) } {
+            Ok(_) => {}
+            Err(Errno::EAGAIN) => return Ok(None),
+            Err(error) => Err(format!("Unable to dequeue encoder buffer: {error}"))?,
+        }
+
+        let data = if self.mplane {
+            plane.data_offset.min(plane.bytesused) as usize..plane.bytesused as usize
+        } else {
+            0..buffer.bytesused as usize
+        };
+
+        Ok(Some((buffer.index, data)))
+    }
+
+    /// Take back raw frame buffers which was encoded
+    fn reclaim(&mut self) -> Result<()> {
+        while let Some((index, _)) = self.dequeue(self.output_type())? {
+            self.free.push(index);
+        }
+        Ok(())
+    }
+
+    /// Wait for queue readiness until deadline
+    fn wait(&self, events: PollFlags, deadline: Instant) -> Result<bool> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+
+        let mut fds = [PollFd::new(self.file.as_fd(), events)];
+        match poll(&mut fds, timeout) {
+            Ok(count) => Ok(count > 0),
+            Err(Errno::EINTR) => Ok(true),
+            Err(error) => Err(format!("Error when waiting for encoder: {error}"))?,
+        }
+    }
+}
+
+/// Copy planar YUV 4:2:0 frame to buffer with specified luma stride
+///
+/// Rows below frame repeat its last row.
+fn copy_i420(yuv: &impl YUVSource, buffer: &mut [u8], stride: usize, rows: usize) {
+    let (width, height) = yuv.dimensions();
+    let (y_stride, u_stride, v_stride) = yuv.strides();
+
+    let (luma, chroma) = buffer.split_at_mut(stride * rows);
+    let (cb, cr) = chroma.split_at_mut((stride / 2) * (rows / 2));
+
+    copy_plane(luma, stride, rows, yuv.y(), y_stride, width, height);
+    copy_plane(
+        cb,
+        stride / 2,
+        rows / 2,
+        yuv.u(),
+        u_stride,
+        width / 2,
+        height / 2,
+    );
+    copy_plane(
+        cr,
+        stride / 2,
+        rows / 2,
+        yuv.v(),
+        v_stride,
+        width / 2,
+        height / 2,
+    );
+}
+
+fn copy_plane(
+    plane: &mut [u8],
+    stride: usize,
+    rows: usize,
+    source: &[u8],
+    source_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    for row in 0..rows {
+        let source_row = row.min(height - 1) * source_stride;
+        plane[row * stride..][..width].copy_from_slice(&source[source_row..][..width]);
+    }
+}
+
+/// Check that Annex B data contains coded slice
+fn has_slice(data: &[u8]) -> bool {
+    data.windows(4)
+        .any(|unit| unit[..3] == [0, 0, 1] && matches!(unit[3] & 0x1f, 1..=5))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use openh264::formats::YUVBuffer;
+
+    #[test]
+    fn struct_layouts() {
+        assert_eq!(core::mem::size_of::<PixFormat>(), 48);
+        assert_eq!(core::mem::size_of::<PixFormatMplane>(), 192);
+        assert_eq!(core::mem::size_of::<RequestBuffers>(), 20);
+        assert_eq!(core::mem::size_of::<StreamParm>(), 204);
+        assert_eq!(core::mem::size_of::<Selection>(), 64);
+        #[cfg(target_pointer_width = "64")]
+        {
+            assert_eq!(core::mem::size_of::<Format>(), 208);
+            assert_eq!(core::mem::size_of::<Plane>(), 64);
+            assert_eq!(core::mem::size_of::<Buffer>(), 88);
+        }
+    }
+
+    #[test]
+    fn copy_strided_frame() {
+        // 2x2 frame
+        let yuv = YUVBuffer::from_vec(vec![1, 2, 3, 4, 5, 6], 2, 2);
+
+        // 4 bytes per line and 4 rows
+        let mut buffer = [0; 24];
+        copy_i420(&yuv, &mut buffer, 4, 4);
+
+        assert_eq!(
+            buffer,
+            [
+                1, 2, 0, 0, 3, 4, 0, 0, 3, 4, 0, 0, 3, 4, 0, 0, // luma
+                5, 0, 5, 0, // cb
+                6, 0, 6, 0, // cr
+            ]
+        );
+    }
+
+    #[test]
+    fn find_slices() {
+        // parameter sets only
+        assert!(!has_slice(&[
+            0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce
+        ]));
+        // IDR slice
+        assert!(has_slice(&[0, 0, 0, 1, 0x68, 0xce, 0, 0, 1, 0x65, 0x88]));
+        // non-IDR slice
+        assert!(has_slice(&[0, 0, 0, 1, 0x41, 0x9a]));
+    }
+}
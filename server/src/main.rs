@@ -1,4 +1,6 @@
-use ukvm::{Args, GracefulShutdown, Result, Server, ServerConfig};
+use ukvm::{
+    Args, BindAddr, Command, GracefulShutdown, Probe, ReloadError, Result, Server, ServerConfig,
+};
 
 pub use tracing as log;
 
@@ -48,45 +50,93 @@ async fn main() -> Result<()> {
         registry.init();
     }
 
-    let mut run = true;
+    if let Some(Command::Probe(probe_args)) = &args.command {
+        let probe = Probe::scan(&probe_args.root).await?;
+        if probe_args.sample {
+            print!("{}", probe.sample_config());
+        } else {
+            print!("{probe}");
+        }
+        return Ok(());
+    }
+
+    if args.check_config {
+        let problems = match ServerConfig::from_file(&args.config).await {
+            Ok(config) => config.check(&args.bind).await,
+            Err(error) => vec![error.to_string()],
+        };
+
+        for problem in &problems {
+            eprintln!("{}: {problem}", args.config.display());
+        }
+
+        if !problems.is_empty() {
+            std::process::exit(1);
+        }
+
+        println!("{}: OK", args.config.display());
+        return Ok(());
+    }
+
+    let mut config = ServerConfig::from_file(&args.config).await?;
+
+    log::debug!("Config: {:#?}", config);
+
+    if !args.run {
+        return Ok(());
+    }
 
     let mut intr = signal(SignalKind::interrupt())?;
     let mut term = signal(SignalKind::terminate())?;
     let mut usr1 = signal(SignalKind::user_defined1())?;
 
-    while run {
-        let config = ServerConfig::from_file(&args.config).await?;
+    // create server instance
+    let mut server = Server::new(&config).await?;
 
-        log::debug!("Config: {:#?}", config);
+    // running server interfaces
+    let mut binds: Vec<(BindAddr, GracefulShutdown)> = Vec::new();
 
-        if !args.run {
-            break;
-        }
+    log::info!("Starting");
 
-        let gs = GracefulShutdown::default();
+    let mut starting = true;
 
-        // create server instance
-        let server = Server::new(&config).await?;
+    let mut result = Ok(());
 
-        log::info!("Starting");
+    loop {
+        // start new server interfaces
+        for bind in args.bind.iter().chain(&config.binds) {
+            if binds.iter().any(|(running, _)| running == bind) {
+                continue;
+            }
 
-        // start server interfaces
-        server
-            .spawn(args.bind.iter().chain(&config.binds), &gs)
-            .await?;
+            let gs = GracefulShutdown::default();
+
+            if let Err(error) = server.spawn([bind], &gs).await {
+                if starting {
+                    return Err(error);
+                }
+                log::error!("Unable to start {bind:?} due to: {error}");
+                gs.shutdown().await;
+                continue;
+            }
+
+            binds.push((bind.clone(), gs));
+        }
 
         log::info!("Started");
 
+        starting = false;
+
         select! {
             // stop server
             _ = intr.recv() => {
                 log::info!("Interrupt");
-                run = false;
+                break;
             }
             // stop server
             _ = term.recv() => {
                 log::info!("Terminate");
-                run = false;
+                break;
             }
             // reload server
             _ = usr1.recv() => {
@@ -94,11 +144,47 @@ async fn main() -> Result<()> {
             }
         }
 
-        // stop server interfaces
+        let new_config = match ServerConfig::from_file(&args.config).await {
+            Ok(config) => config,
+            Err(error) => {
+                log::error!("Invalid config: {error}");
+                continue;
+            }
+        };
+
+        log::debug!("Config: {:#?}", new_config);
+
+        // stop removed server interfaces
+        let mut running = Vec::new();
+        for (bind, gs) in binds {
+            if args.bind.contains(&bind) || new_config.binds.contains(&bind) {
+                running.push((bind, gs));
+            } else {
+                gs.shutdown().await;
+            }
+        }
+        binds = running;
+
+        server = match server.reload(&mut config, new_config).await {
+            Ok(server) => server,
+            Err(ReloadError::Rejected(server, error)) => {
+                log::error!("Unable to apply config due to: {error}");
+                server
+            }
+            Err(ReloadError::Failed(error)) => {
+                log::error!("Unable to restore config due to: {error}");
+                result = Err(error);
+                break;
+            }
+        };
+    }
+
+    // stop server interfaces
+    for (_, gs) in binds {
         gs.shutdown().await;
     }
 
     log::info!("Bye");
 
-    Ok(())
+    result
 }
@@ -0,0 +1,493 @@
+use crate::{
+    log, LedId, LedMode, LedPattern, Leds, PowerState, Result, SensorReading, Sensors, Server,
+};
+use core::future::Future;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::{
+    select, spawn,
+    sync::watch,
+    time::{self, sleep_until, Instant},
+};
+
+#[cfg(feature = "hid")]
+use crate::{Hid, UsbState};
+
+#[cfg(feature = "video")]
+use crate::{Video, VideoHealth, VideoSignal};
+
+/// Power state inference configuration
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PowerConfig {
+    /// LED which reflects host power
+    ///
+    /// Lit or flickering means powered, slow blinking means standby.
+    #[serde(default = "default_led")]
+    pub led: LedId,
+
+    /// Sensor which measures host power draw
+    ///
+    /// Takes precedence over LED when configured.
+    #[serde(default)]
+    pub sensor: Option<String>,
+
+    /// Minimum sensor value when host is powered
+    #[serde(default)]
+    pub on_threshold: Option<f64>,
+
+    /// Minimum sensor value when host is in standby
+    #[serde(default)]
+    pub standby_threshold: Option<f64>,
+
+    /// Take video signal presence into account
+    #[serde(default = "default_true")]
+    pub video: bool,
+
+    /// Take USB enumeration state into account
+    #[serde(default = "default_true")]
+    pub usb: bool,
+
+    /// Consider host hung when screen content is frozen
+    #[serde(default)]
+    pub frozen_hung: bool,
+
+    /// Time in milliseconds from power up until host is considered hung
+    /// unless video and USB come up (0 disables)
+    #[serde(default = "default_boot_timeout")]
+    pub boot_timeout: u32,
+}
+
+fn default_led() -> LedId {
+    LedId::Power
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_boot_timeout() -> u32 {
+    300000
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            led: default_led(),
+            sensor: None,
+            on_threshold: None,
+            standby_threshold: None,
+            video: true,
+            usb: true,
+            frozen_hung: false,
+            boot_timeout: default_boot_timeout(),
+        }
+    }
+}
+
+/// Signals which power state is derived from
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct PowerInputs {
+    /// Power LED behavior
+    pub led: Option<LedMode>,
+    /// Power draw sensor value
+    pub draw: Option<f64>,
+    /// Video signal presence
+    pub video: Option<bool>,
+    /// Screen content is frozen
+    pub frozen: bool,
+    /// USB device configured by host
+    pub usb: Option<bool>,
+    /// USB bus suspended by host
+    pub suspended: bool,
+}
+
+/// Power state inference
+pub(crate) struct PowerMonitor {
+    on_threshold: Option<f64>,
+    standby_threshold: Option<f64>,
+    frozen_hung: bool,
+    boot_timeout: Option<Duration>,
+    /// Time when booting started
+    booting_since: Option<Instant>,
+}
+
+impl PowerMonitor {
+    pub fn new(config: &PowerConfig) -> Self {
+        Self {
+            on_threshold: config.on_threshold,
+            standby_threshold: config.standby_threshold,
+            frozen_hung: config.frozen_hung,
+            boot_timeout: Some(Duration::from_millis(config.boot_timeout as _))
+                .filter(|timeout| !timeout.is_zero()),
+            booting_since: None,
+        }
+    }
+
+    /// Power rail state from sensor or LED
+    fn rail(&self, inputs: &PowerInputs) -> Option<PowerState> {
+        if let (Some(draw), Some(on)) = (inputs.draw, self.on_threshold) {
+            return Some(if draw >= on {
+                PowerState::On
+            } else if self
+                .standby_threshold
+                .map(|min| draw >= min)
+                .unwrap_or_default()
+            {
+                PowerState::Standby
+            } else {
+                PowerState::Off
+            });
+        }
+
+        inputs.led.map(|mode| match mode {
+            LedMode::Off => PowerState::Off,
+            LedMode::On | LedMode::Activity => PowerState::On,
+            LedMode::Blinking => PowerState::Standby,
+        })
+    }
+
+    /// Derive state from inputs ignoring boot timeout
+    fn infer(&self, inputs: &PowerInputs) -> PowerState {
+        // operating system is up when host drives display and enumerates gadget
+        let up = match (inputs.video, inputs.usb) {
+            (None, None) => None,
+            (video, usb) => Some(video.unwrap_or(true) && usb.unwrap_or(true)),
+        };
+
+        match self.rail(inputs) {
+            Some(PowerState::Off) => PowerState::Off,
+            Some(PowerState::Standby) => PowerState::Standby,
+            None if up != Some(true) => PowerState::Unknown,
+            _ if inputs.suspended && inputs.video != Some(true) => PowerState::Standby,
+            _ if self.frozen_hung && inputs.frozen => PowerState::Hung,
+            _ if up == Some(false) => PowerState::Booting,
+            _ => PowerState::On,
+        }
+    }
+
+    /// Update state using actual inputs
+    pub fn update(&mut self, inputs: &PowerInputs, now: Instant) -> PowerState {
+        let state = self.infer(inputs);
+
+        if state != PowerState::Booting {
+            self.booting_since = None;
+            return state;
+        }
+
+        let since = *self.booting_since.get_or_insert(now);
+
+        if self
+            .boot_timeout
+            .map(|timeout| now >= since + timeout)
+            .unwrap_or_default()
+        {
+            PowerState::Hung
+        } else {
+            state
+        }
+    }
+
+    /// Time when state should be updated even inputs unchanged
+    pub fn deadline(&self) -> Option<Instant> {
+        Some(self.booting_since? + self.boot_timeout?)
+    }
+}
+
+/// Wait for changes of optional channel
+///
+/// Never completes when channel is missing or closed.
+async fn changed<T>(receiver: &mut Option<watch::Receiver<T>>) {
+    if let Some(inner) = receiver {
+        if inner.changed().await.is_ok() {
+            return;
+        }
+    }
+    *receiver = None;
+    core::future::pending().await
+}
+
+/// Channels of subsystems which power state is derived from
+#[derive(Default)]
+struct PowerSources {
+    led: Option<watch::Receiver<LedPattern>>,
+    sensor: Option<watch::Receiver<SensorReading>>,
+    #[cfg(feature = "video")]
+    signal: Option<watch::Receiver<VideoSignal>>,
+    #[cfg(feature = "video")]
+    health: Option<watch::Receiver<VideoHealth>>,
+    #[cfg(feature = "hid")]
+    usb: Option<watch::Receiver<UsbState>>,
+}
+
+impl PowerSources {
+    /// Get actual inputs
+    fn inputs(&mut self) -> PowerInputs {
+        #[cfg(feature = "video")]
+        let video = self
+            .signal
+            .as_mut()
+            .map(|signal| *signal.borrow_and_update() == VideoSignal::Present);
+        #[cfg(feature = "video")]
+        let frozen = self
+            .health
+            .as_mut()
+            .map(|health| *health.borrow_and_update() == VideoHealth::Frozen)
+            .unwrap_or_default();
+        #[cfg(not(feature = "video"))]
+        let (video, frozen) = (None, false);
+
+        #[cfg(feature = "hid")]
+        let usb = self.usb.as_mut().map(|usb| *usb.borrow_and_update());
+        #[cfg(feature = "hid")]
+        let (usb, suspended) = (
+            usb.map(|usb| usb == UsbState::Configured),
+            usb == Some(UsbState::Suspended),
+        );
+        #[cfg(not(feature = "hid"))]
+        let (usb, suspended) = (None, false);
+
+        PowerInputs {
+            led: self.led.as_mut().map(|led| led.borrow_and_update().mode),
+            draw: self
+                .sensor
+                .as_mut()
+                .and_then(|sensor| sensor.borrow_and_update().value),
+            video,
+            frozen,
+            usb,
+            suspended,
+        }
+    }
+
+    /// Wait until any input changed
+    async fn changed(&mut self) {
+        #[cfg(feature = "video")]
+        let video = {
+            let (signal, health) = (&mut self.signal, &mut self.health);
+            async {
+                select! {
+                    _ = changed(signal) => {}
+                    _ = changed(health) => {}
+                }
+            }
+        };
+        #[cfg(not(feature = "video"))]
+        let video = core::future::pending::<()>();
+
+        #[cfg(feature = "hid")]
+        let usb = changed(&mut self.usb);
+        #[cfg(not(feature = "hid"))]
+        let usb = core::future::pending::<()>();
+
+        select! {
+            _ = changed(&mut self.led) => {}
+            _ = changed(&mut self.sensor) => {}
+            _ = video => {}
+            _ = usb => {}
+        }
+    }
+}
+
+/// Host power state tracker
+pub struct Power {
+    state_receiver: watch::Receiver<PowerState>,
+}
+
+impl Power {
+    /// Start tracking power state using subsystems of server
+    pub fn new(
+        config: &PowerConfig,
+        leds: &Leds,
+        sensors: &Sensors,
+        #[cfg(feature = "hid")] hid: Option<&Hid>,
+        #[cfg(feature = "video")] video: Option<&Video>,
+    ) -> Result<Self> {
+        if config.sensor.is_some() && config.on_threshold.is_none() {
+            return Err("Power sensor requires on_threshold")?;
+        }
+
+        let sensor = if let Some(name) = &config.sensor {
+            let sensor = sensors
+                .get(name)
+                .ok_or_else(|| format!("Power sensor {name} not configured"))?;
+            Some(sensor.watch())
+        } else {
+            None
+        };
+
+        let mut sources = PowerSources {
+            led: leds.get(&config.led).map(|led| led.watch_pattern()),
+            sensor,
+            ..Default::default()
+        };
+
+        #[cfg(feature = "video")]
+        if let Some(video) = video.filter(|_| config.video) {
+            sources.signal = Some(video.watch_signal());
+            sources.health = Some(video.watch_health());
+        }
+
+        #[cfg(feature = "hid")]
+        if let Some(udc) = hid.and_then(|hid| hid.udc()).filter(|_| config.usb) {
+            sources.usb = Some(udc.watch());
+        }
+
+        let mut monitor = PowerMonitor::new(config);
+        let (state_sender, state_receiver) =
+            watch::channel(monitor.update(&sources.inputs(), Instant::now()));
+
+        log::info!("Initial power state: {}", *state_receiver.borrow());
+
+        spawn(async move {
+            log::debug!("Initialize power state tracking");
+
+            loop {
+                let deadline = monitor.deadline();
+
+                select! {
+                    // Power object dropped
+                    _ = state_sender.closed() => break,
+                    _ = sources.changed() => {}
+                    _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {}
+                }
+
+                let state = monitor.update(&sources.inputs(), Instant::now());
+
+                state_sender.send_if_modified(|current| {
+                    if *current == state {
+                        return false;
+                    }
+                    log::info!("Power state changed: {current} -> {state}");
+                    *current = state;
+                    true
+                });
+            }
+
+            log::debug!("Finalize power state tracking");
+        });
+
+        Ok(Self { state_receiver })
+    }
+
+    /// Get current state
+    pub fn state(&self) -> PowerState {
+        *self.state_receiver.borrow()
+    }
+
+    /// Watch state changes
+    pub fn watch(&self) -> watch::Receiver<PowerState> {
+        self.state_receiver.clone()
+    }
+}
+
+impl Server {
+    /// Wait until host reaches power state
+    ///
+    /// Returned future doesn't hold server instance, so long waits don't delay reloading.
+    pub fn wait_power(
+        &self,
+        state: PowerState,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        let mut receiver = self.power().watch();
+
+        async move {
+            time::timeout(timeout, receiver.wait_for(|current| *current == state))
+                .await
+                .map_err(|_| format!("Timeout when waiting for power state {state}"))?
+                .map_err(|_| "Power state tracking stopped")?;
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn infer_power_state() {
+        let mut monitor = PowerMonitor::new(&PowerConfig::default());
+        let now = Instant::now();
+
+        assert_eq!(
+            monitor.update(&PowerInputs::default(), now),
+            PowerState::Unknown
+        );
+
+        let mut inputs = PowerInputs {
+            led: Some(LedMode::Off),
+            video: Some(false),
+            usb: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(monitor.update(&inputs, now), PowerState::Off);
+
+        inputs.led = Some(LedMode::Blinking);
+        assert_eq!(monitor.update(&inputs, now), PowerState::Standby);
+
+        inputs.led = Some(LedMode::On);
+        assert_eq!(monitor.update(&inputs, now), PowerState::Booting);
+        assert_eq!(
+            monitor.deadline(),
+            Some(now + Duration::from_millis(default_boot_timeout() as _))
+        );
+
+        inputs.video = Some(true);
+        let later = now + Duration::from_secs(10);
+        assert_eq!(monitor.update(&inputs, later), PowerState::Booting);
+
+        inputs.usb = Some(true);
+        assert_eq!(monitor.update(&inputs, later), PowerState::On);
+        assert_eq!(monitor.deadline(), None);
+
+        inputs.usb = Some(false);
+        inputs.suspended = true;
+        inputs.video = Some(false);
+        assert_eq!(monitor.update(&inputs, later), PowerState::Standby);
+
+        // stuck in booting
+        inputs.suspended = false;
+        assert_eq!(monitor.update(&inputs, later), PowerState::Booting);
+        let timeout = later + Duration::from_millis(default_boot_timeout() as _);
+        assert_eq!(monitor.update(&inputs, timeout), PowerState::Hung);
+
+        // no LED but host is clearly up
+        inputs = PowerInputs {
+            video: Some(true),
+            usb: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(monitor.update(&inputs, now), PowerState::On);
+    }
+
+    #[test]
+    fn infer_power_state_from_sensor() {
+        let mut monitor = PowerMonitor::new(&PowerConfig {
+            on_threshold: Some(5.0),
+            standby_threshold: Some(0.5),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        // sensor takes precedence over LED
+        let mut inputs = PowerInputs {
+            led: Some(LedMode::On),
+            draw: Some(0.1),
+            ..Default::default()
+        };
+        assert_eq!(monitor.update(&inputs, now), PowerState::Off);
+
+        inputs.draw = Some(1.0);
+        assert_eq!(monitor.update(&inputs, now), PowerState::Standby);
+
+        inputs.draw = Some(12.0);
+        assert_eq!(monitor.update(&inputs, now), PowerState::On);
+
+        // sensor read error falls back to LED
+        inputs.draw = None;
+        inputs.led = Some(LedMode::Off);
+        assert_eq!(monitor.update(&inputs, now), PowerState::Off);
+    }
+}
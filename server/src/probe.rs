@@ -0,0 +1,545 @@
+use crate::{devices::list_devices, Result};
+use core::fmt::{self, Display, Write};
+use gpiod::{Chip, Direction, LineId};
+use std::path::Path;
+use tokio::fs;
+
+#[cfg(feature = "hid")]
+use crate::GadgetFunction;
+
+#[cfg(feature = "video")]
+use linux_video::{types::*, Device};
+
+/// USB gadgets configfs directory relative to root
+#[cfg(feature = "hid")]
+const CONFIGFS: &str = "sys/kernel/config/usb_gadget";
+
+/// Video devices sysfs directory relative to root
+#[cfg(feature = "video")]
+const V4L_CLASS: &str = "sys/class/video4linux";
+
+/// GPIO line description
+#[derive(Clone, Debug)]
+pub struct GpioLineInfo {
+    /// Line offset
+    pub line: LineId,
+    /// Line name
+    pub name: String,
+    /// Consumer which requested line
+    pub consumer: String,
+    /// Line direction
+    pub direction: Direction,
+    /// Line is used by kernel or some process
+    pub used: bool,
+}
+
+/// GPIO chip description
+#[derive(Clone, Debug)]
+pub struct GpioChipInfo {
+    /// Device name (gpiochip0)
+    pub device: String,
+    /// Chip label
+    pub label: String,
+    /// Chip lines
+    pub lines: Vec<GpioLineInfo>,
+    /// Error when querying chip
+    pub error: Option<String>,
+}
+
+/// HID gadget device description
+#[cfg(feature = "hid")]
+#[derive(Clone, Debug)]
+pub struct HidDeviceInfo {
+    /// Device name (hidg0)
+    pub device: String,
+    /// Gadget function path relative to configfs
+    pub function: Option<String>,
+    /// Kind of function
+    pub kind: Option<GadgetFunction>,
+}
+
+/// Video device description
+#[cfg(feature = "video")]
+#[derive(Clone, Debug)]
+pub struct VideoDeviceInfo {
+    /// Device name (video0)
+    pub device: String,
+    /// Card name
+    pub card: String,
+    /// Device can capture video (unknown when device cannot be queried)
+    pub capture: Option<bool>,
+    /// Supported capture pixel formats
+    pub formats: Vec<String>,
+    /// Error when querying device
+    pub error: Option<String>,
+}
+
+/// Discovered hardware
+#[derive(Clone, Debug, Default)]
+pub struct Probe {
+    /// GPIO chips
+    pub gpio: Vec<GpioChipInfo>,
+    /// HID gadget devices
+    #[cfg(feature = "hid")]
+    pub hid: Vec<HidDeviceInfo>,
+    /// Video devices
+    #[cfg(feature = "video")]
+    pub video: Vec<VideoDeviceInfo>,
+}
+
+impl Probe {
+    /// Discover hardware under root directory
+    ///
+    /// Devices are looked up in `dev` and details in `sys` subdirectories
+    /// so mock trees can be used instead of `/`.
+    pub async fn scan(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        let dev = root.join("dev");
+
+        let mut gpio = Vec::new();
+        for device in list_devices(&dev, "gpiochip").await? {
+            gpio.push(probe_gpio(&dev, device).await);
+        }
+
+        #[cfg(feature = "hid")]
+        let hid = {
+            let functions = hid_functions(&root.join(CONFIGFS)).await;
+            list_devices(&dev, "hidg")
+                .await?
+                .into_iter()
+                .map(|device| {
+                    let function = functions
+                        .iter()
+                        .find(|(name, _, _)| *name == device)
+                        .map(|(_, function, kind)| (function.clone(), *kind));
+                    HidDeviceInfo {
+                        device,
+                        function: function.as_ref().map(|(function, _)| function.clone()),
+                        kind: function.and_then(|(_, kind)| kind),
+                    }
+                })
+                .collect()
+        };
+
+        #[cfg(feature = "video")]
+        let video = {
+            let mut video = Vec::new();
+            for device in list_devices(&dev, "video").await? {
+                video.push(probe_video(root, &dev, device).await);
+            }
+            video
+        };
+
+        Ok(Self {
+            gpio,
+            #[cfg(feature = "hid")]
+            hid,
+            #[cfg(feature = "video")]
+            video,
+        })
+    }
+
+    /// Make commented starter config using discovered hardware
+    pub fn sample_config(&self) -> String {
+        let mut out = String::new();
+        // writing to string never fails
+        let _ = self.write_sample_config(&mut out);
+        out
+    }
+
+    fn write_sample_config(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "# Starter config generated by `ukvm probe`")?;
+        writeln!(out)?;
+        writeln!(out, "[[binds]]")?;
+        writeln!(out, "proto = \"http\"")?;
+        writeln!(out, "type = \"tcp\"")?;
+        writeln!(out, "addr = \"127.0.0.1:8080\"")?;
+        writeln!(out)?;
+        writeln!(out, "#[[binds]]")?;
+        writeln!(out, "#proto = \"dbus\"")?;
+        writeln!(out, "#type = \"system\"")?;
+
+        let chip = self.gpio.first().map(|chip| chip.device.as_str());
+        for chip in &self.gpio {
+            writeln!(out)?;
+            writeln!(out, "# {chip}")?;
+            let free = chip
+                .lines
+                .iter()
+                .filter(|line| !line.used)
+                .map(line_title)
+                .collect::<Vec<_>>();
+            if !free.is_empty() {
+                writeln!(out, "# free lines: {}", free.join(", "))?;
+            }
+        }
+
+        for (section, ids) in [("buttons", ["power", "reset"]), ("leds", ["power", "disk"])] {
+            for id in ids {
+                writeln!(out)?;
+                writeln!(out, "#[{section}.{id}]")?;
+                writeln!(out, "#chip = \"{}\"", chip.unwrap_or("gpiochip0"))?;
+                writeln!(out, "#line = 0")?;
+            }
+        }
+
+        #[cfg(feature = "hid")]
+        {
+            let find = |kind| {
+                self.hid
+                    .iter()
+                    .find(|hid| hid.kind == Some(kind))
+                    .map(|hid| hid.device.as_str())
+            };
+            let unknown = self
+                .hid
+                .iter()
+                .filter(|hid| hid.kind.is_none())
+                .map(|hid| hid.device.as_str())
+                .collect::<Vec<_>>();
+            // unknown devices are assumed to be keyboard and mouse in order
+            let keyboard = find(GadgetFunction::Keyboard).or(unknown.first().copied());
+            let mouse = find(GadgetFunction::Mouse).or(unknown.get(1).copied());
+            let relative_mouse = find(GadgetFunction::RelativeMouse);
+
+            writeln!(out)?;
+            if keyboard.is_some() || mouse.is_some() || relative_mouse.is_some() {
+                writeln!(out, "[hid]")?;
+                for (key, device) in [
+                    ("keyboard", keyboard),
+                    ("mouse", mouse),
+                    ("relative_mouse", relative_mouse),
+                ] {
+                    if let Some(device) = device {
+                        writeln!(out, "{key} = \"{device}\"")?;
+                    } else {
+                        writeln!(out, "#{key} = \"hidg0\"")?;
+                    }
+                }
+            } else {
+                writeln!(out, "# No HID gadget devices found, create gadget on start")?;
+                writeln!(out, "#[hid]")?;
+                writeln!(out, "#[hid.gadget]")?;
+            }
+        }
+
+        #[cfg(feature = "video")]
+        {
+            writeln!(out)?;
+            if let Some(video) = self
+                .video
+                .iter()
+                .find(|video| video.capture.unwrap_or(true))
+            {
+                writeln!(out, "[video]")?;
+                writeln!(out, "device = \"{}\"", video.device)?;
+            } else {
+                writeln!(out, "# No video capture devices found")?;
+                writeln!(out, "#[video]")?;
+                writeln!(out, "#device = \"video0\"")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "GPIO chips:")?;
+        if self.gpio.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for chip in &self.gpio {
+            writeln!(f, "  {chip}")?;
+            for line in &chip.lines {
+                write!(f, "    {} {}", line_title(line), line.direction)?;
+                if !line.consumer.is_empty() {
+                    write!(f, " used by {}", line.consumer)?;
+                } else if line.used {
+                    write!(f, " used")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        #[cfg(feature = "hid")]
+        {
+            writeln!(f, "HID gadget devices:")?;
+            if self.hid.is_empty() {
+                writeln!(f, "  none")?;
+            }
+            for hid in &self.hid {
+                write!(f, "  {}", hid.device)?;
+                if let Some(kind) = hid.kind {
+                    write!(f, " {kind:?}")?;
+                }
+                if let Some(function) = &hid.function {
+                    write!(f, " ({function})")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        #[cfg(feature = "video")]
+        {
+            writeln!(f, "Video devices:")?;
+            if self.video.is_empty() {
+                writeln!(f, "  none")?;
+            }
+            for video in &self.video {
+                write!(f, "  {}", video.device)?;
+                if !video.card.is_empty() {
+                    write!(f, " [{}]", video.card)?;
+                }
+                match video.capture {
+                    Some(true) => write!(f, " capture")?,
+                    Some(false) => write!(f, " no capture")?,
+                    None => {}
+                }
+                if !video.formats.is_empty() {
+                    write!(f, " {}", video.formats.join(" "))?;
+                }
+                if let Some(error) = &video.error {
+                    write!(f, " ({error})")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for GpioChipInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.device)?;
+        if !self.label.is_empty() {
+            write!(f, " [{}]", self.label)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " ({error})")?;
+        } else {
+            write!(f, " {} lines", self.lines.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Line offset with name
+fn line_title(line: &GpioLineInfo) -> String {
+    if line.name.is_empty() {
+        line.line.to_string()
+    } else {
+        format!("{} \"{}\"", line.line, line.name)
+    }
+}
+
+async fn probe_gpio(dev: &Path, device: String) -> GpioChipInfo {
+    let mut info = GpioChipInfo {
+        label: String::new(),
+        lines: Vec::new(),
+        error: None,
+        device,
+    };
+
+    let chip = match Chip::new(dev.join(&info.device)).await {
+        Ok(chip) => chip,
+        Err(error) => {
+            info.error = Some(error.to_string());
+            return info;
+        }
+    };
+
+    info.label = chip.label().into();
+
+    for line in 0..chip.num_lines() {
+        match chip.line_info(line).await {
+            Ok(line_info) => info.lines.push(GpioLineInfo {
+                line,
+                name: line_info.name,
+                consumer: line_info.consumer,
+                direction: line_info.direction,
+                used: line_info.used,
+            }),
+            Err(error) => {
+                info.error = Some(format!("Unable to get line {line} info: {error}"));
+                break;
+            }
+        }
+    }
+
+    info
+}
+
+/// Find HID gadget functions
+///
+/// Returns device name, function path and kind.
+#[cfg(feature = "hid")]
+async fn hid_functions(configfs: &Path) -> Vec<(String, String, Option<GadgetFunction>)> {
+    let mut functions = Vec::new();
+
+    for gadget in read_dir_names(configfs).await {
+        let path = configfs.join(&gadget).join("functions");
+        for function in read_dir_names(&path).await {
+            if !function.starts_with("hid.") {
+                continue;
+            }
+            let path = path.join(&function);
+            let Some(dev) = read_trimmed(path.join("dev")).await else {
+                continue;
+            };
+            let Some((_, minor)) = dev.split_once(':') else {
+                continue;
+            };
+
+            let kind = [
+                GadgetFunction::Keyboard,
+                GadgetFunction::Mouse,
+                GadgetFunction::RelativeMouse,
+            ]
+            .into_iter()
+            .find(|kind| kind.name() == function)
+            .or(match read_trimmed(path.join("protocol")).await.as_deref() {
+                Some("1") => Some(GadgetFunction::Keyboard),
+                Some("2") => Some(GadgetFunction::Mouse),
+                _ => None,
+            });
+
+            functions.push((
+                format!("hidg{minor}"),
+                format!("{gadget}/functions/{function}"),
+                kind,
+            ));
+        }
+    }
+
+    functions
+}
+
+#[cfg(feature = "video")]
+async fn probe_video(root: &Path, dev: &Path, device: String) -> VideoDeviceInfo {
+    let mut info = VideoDeviceInfo {
+        card: read_trimmed(root.join(V4L_CLASS).join(&device).join("name"))
+            .await
+            .unwrap_or_default(),
+        capture: None,
+        formats: Vec::new(),
+        error: None,
+        device,
+    };
+
+    if let Err(error) = query_video(&dev.join(&info.device), &mut info).await {
+        info.error = Some(error.to_string());
+    }
+
+    info
+}
+
+#[cfg(feature = "video")]
+async fn query_video(path: &Path, info: &mut VideoDeviceInfo) -> Result<()> {
+    let device = Device::open(path).await?;
+
+    let caps = device.capabilities().await?;
+    info.card = caps.card().into();
+
+    let capture = caps.capabilities().contains(CapabilityFlag::VideoCapture);
+    info.capture = Some(capture);
+
+    if capture {
+        let mut formats = device.formats(BufferType::VideoCapture);
+        while let Some(format) = formats.fetch_next().await? {
+            info.formats.push(format.pixel_format().to_string());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "hid")]
+async fn read_dir_names(path: &Path) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Ok(mut dir) = fs::read_dir(path).await {
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    names
+}
+
+async fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    fs::read_to_string(path)
+        .await
+        .ok()
+        .map(|data| data.trim().into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    async fn write(path: PathBuf, data: &str) {
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        fs::write(path, data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn probe_mock_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        for device in ["gpiochip0", "hidg0", "hidg1", "video0", "video10", "null"] {
+            write(root.join("dev").join(device), "").await;
+        }
+
+        let functions = root.join("sys/kernel/config/usb_gadget/g1/functions");
+        write(functions.join("hid.usb0/dev"), "240:1\n").await;
+        write(functions.join("hid.usb0/protocol"), "1\n").await;
+        write(functions.join("hid.mouse/dev"), "240:0\n").await;
+        write(root.join("sys/class/video4linux/video0/name"), "Mock\n").await;
+
+        let probe = Probe::scan(root).await.unwrap();
+
+        assert_eq!(probe.gpio.len(), 1);
+        assert!(probe.gpio[0].error.is_some());
+
+        #[cfg(feature = "hid")]
+        {
+            assert_eq!(probe.hid.len(), 2);
+            assert_eq!(probe.hid[0].kind, Some(GadgetFunction::Mouse));
+            assert_eq!(probe.hid[1].kind, Some(GadgetFunction::Keyboard));
+            assert_eq!(
+                probe.hid[1].function.as_deref(),
+                Some("g1/functions/hid.usb0")
+            );
+        }
+
+        #[cfg(feature = "video")]
+        {
+            assert_eq!(
+                probe
+                    .video
+                    .iter()
+                    .map(|video| video.device.as_str())
+                    .collect::<Vec<_>>(),
+                ["video0", "video10"]
+            );
+            assert_eq!(probe.video[0].card, "Mock");
+            assert!(probe.video[0].error.is_some());
+        }
+
+        let config = crate::ServerConfig::from_toml(&probe.sample_config()).unwrap();
+
+        #[cfg(feature = "hid")]
+        {
+            let hid = config.hid.unwrap();
+            assert_eq!(hid.keyboard.as_deref(), Some("hidg1"));
+            assert_eq!(hid.mouse.as_deref(), Some("hidg0"));
+        }
+
+        #[cfg(feature = "video")]
+        assert_eq!(config.video.unwrap().device, "video0");
+    }
+}
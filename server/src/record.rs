@@ -0,0 +1,372 @@
+use crate::{log, Result, Server, SocketInput};
+use futures_util::stream::{select, select_all, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+    time::{Instant, SystemTime},
+};
+use tokio::{
+    select, spawn,
+    sync::{
+        broadcast::{self, error::RecvError},
+        watch, Mutex,
+    },
+    task::{spawn_blocking, JoinHandle},
+};
+use tokio_stream::wrappers::WatchStream;
+use ukvm_core::record::{RecordEvent, RecordWriter};
+
+/// Session recorder configuration
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RecorderConfig {
+    /// Directory to store records
+    #[serde(default = "default_path")]
+    pub path: PathBuf,
+
+    /// Record automatically while clients are connected
+    #[serde(default)]
+    pub auto: bool,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            path: default_path(),
+            auto: false,
+        }
+    }
+}
+
+fn default_path() -> PathBuf {
+    "/var/lib/ukvm/records".into()
+}
+
+/// Recorded chunk
+enum Chunk {
+    /// Video frame
+    #[cfg(feature = "video")]
+    Frame(crate::video::VideoFrame),
+    /// Event
+    Event(RecordEvent),
+}
+
+/// Active recording
+struct Recording {
+    path: PathBuf,
+    task: JoinHandle<Result<()>>,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    recording: Option<Recording>,
+    /// Number of connected clients
+    sessions: usize,
+    /// Recording started by client connection
+    automatic: bool,
+}
+
+/// Session recorder
+pub struct Recorder {
+    config: RecorderConfig,
+    state: Mutex<RecorderState>,
+    path_sender: watch::Sender<Option<PathBuf>>,
+    /// Input events (`None` stops recording)
+    input_sender: broadcast::Sender<Option<RecordEvent>>,
+}
+
+impl Recorder {
+    /// Create recorder using config
+    pub fn new(config: &RecorderConfig) -> Self {
+        Self {
+            config: config.clone(),
+            state: Default::default(),
+            path_sender: watch::channel(None).0,
+            input_sender: broadcast::channel(64).0,
+        }
+    }
+
+    /// Path of current record
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path_sender.borrow().clone()
+    }
+
+    /// Subscribe to record path changes
+    pub fn watch(&self) -> watch::Receiver<Option<PathBuf>> {
+        self.path_sender.subscribe()
+    }
+
+    /// Log client input
+    pub fn log_input(&self, input: &SocketInput) {
+        if self.input_sender.receiver_count() > 0 {
+            let _ = self.input_sender.send(Some(RecordEvent::Input {
+                input: input.clone(),
+            }));
+        }
+    }
+
+    /// Stop recording under lock
+    ///
+    /// Record should be finished after lock released.
+    fn stop_locked(&self, state: &mut RecorderState) -> Result<Recording> {
+        let recording = state.recording.take().ok_or("Not recording")?;
+        state.automatic = false;
+        self.path_sender.send_replace(None);
+
+        // stop marker is queued after pending inputs
+        let _ = self.input_sender.send(None);
+
+        Ok(recording)
+    }
+}
+
+impl Recording {
+    /// Wait until record written
+    async fn finish(self) -> Result<PathBuf> {
+        self.task
+            .await
+            .map_err(|error| format!("Recording task failed: {error}"))??;
+
+        log::info!("Stop recording {}", self.path.display());
+
+        Ok(self.path)
+    }
+}
+
+impl Server {
+    /// Start session recording
+    ///
+    /// Returns path of record file.
+    pub async fn start_recording(&self) -> Result<PathBuf> {
+        let recorder = self.recorder().ok_or("Recording disabled")?;
+        let mut state = recorder.state.lock().await;
+
+        if state.recording.is_some() {
+            Err("Already recording")?;
+        }
+
+        let recording = self.spawn_recording(recorder)?;
+        let path = recording.path.clone();
+
+        state.recording = Some(recording);
+        state.automatic = false;
+        recorder.path_sender.send_replace(Some(path.clone()));
+
+        Ok(path)
+    }
+
+    /// Stop session recording
+    ///
+    /// Returns path of finished record file.
+    pub async fn stop_recording(&self) -> Result<PathBuf> {
+        let recorder = self.recorder().ok_or("Recording disabled")?;
+        let recording = recorder.stop_locked(&mut *recorder.state.lock().await)?;
+
+        recording.finish().await
+    }
+
+    /// Notify recorder about connected client
+    pub(crate) async fn open_record_session(&self) {
+        let Some(recorder) = self.recorder() else {
+            return;
+        };
+        let mut state = recorder.state.lock().await;
+
+        state.sessions += 1;
+
+        if recorder.config.auto && state.recording.is_none() {
+            match self.spawn_recording(recorder) {
+                Ok(recording) => {
+                    recorder
+                        .path_sender
+                        .send_replace(Some(recording.path.clone()));
+                    state.recording = Some(recording);
+                    state.automatic = true;
+                }
+                Err(error) => log::error!("Unable to start recording: {error}"),
+            }
+        }
+    }
+
+    /// Notify recorder about disconnected client
+    pub(crate) async fn close_record_session(&self) {
+        let Some(recorder) = self.recorder() else {
+            return;
+        };
+
+        let recording = {
+            let mut state = recorder.state.lock().await;
+
+            state.sessions = state.sessions.saturating_sub(1);
+
+            if state.sessions == 0 && state.automatic {
+                recorder.stop_locked(&mut state)
+            } else {
+                return;
+            }
+        };
+
+        let result = match recording {
+            Ok(recording) => recording.finish().await,
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = result {
+            log::error!("Unable to stop recording: {error}");
+        }
+    }
+
+    fn spawn_recording(&self, recorder: &Recorder) -> Result<Recording> {
+        let start_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|error| error.to_string())?
+            .as_millis() as u64;
+
+        std::fs::create_dir_all(&recorder.config.path)?;
+        let path = recorder
+            .config
+            .path
+            .join(format!("session-{start_time}.ukvmrec"));
+        let file = File::create(&path)?;
+
+        log::info!("Start recording {}", path.display());
+
+        let mut inputs = recorder.input_sender.subscribe();
+        let chunks = self.create_record_chunks();
+
+        let task = spawn(async move {
+            let (chunk_sender, mut chunk_receiver) = tokio::sync::mpsc::channel(16);
+
+            let writing = spawn_blocking(move || -> Result<()> {
+                let mut writer = RecordWriter::new(BufWriter::new(file), start_time)?;
+
+                while let Some((time, chunk)) = chunk_receiver.blocking_recv() {
+                    match chunk {
+                        #[cfg(feature = "video")]
+                        Chunk::Frame(frame) => writer.write_frame(time, &frame)?,
+                        Chunk::Event(event) => writer.write_event(time, &event)?,
+                    }
+                }
+
+                writer.finish()?;
+
+                Ok(())
+            });
+
+            let start = Instant::now();
+            let mut chunks = Box::pin(chunks);
+            loop {
+                let chunk = select! {
+                    // inputs go first so none is lost when stopping
+                    biased;
+                    input = inputs.recv() => match input {
+                        Ok(Some(event)) => Chunk::Event(event),
+                        Ok(None) | Err(RecvError::Closed) => break,
+                        Err(RecvError::Lagged(count)) => {
+                            log::warn!("Lost {count} input events");
+                            continue;
+                        }
+                    },
+                    Some(chunk) = chunks.next() => chunk,
+                };
+                if chunk_sender.send((start.elapsed(), chunk)).await.is_err() {
+                    // writer failed
+                    break;
+                }
+            }
+            drop(chunk_sender);
+
+            let result = writing
+                .await
+                .map_err(|error| format!("Record writing failed: {error}"))?;
+
+            if let Err(error) = &result {
+                log::error!("Error when writing record: {error}");
+            }
+
+            result
+        });
+
+        Ok(Recording { path, task })
+    }
+
+    fn create_record_chunks(&self) -> impl Stream<Item = Chunk> + Send {
+        // current states are recorded first
+        let button_events = select_all(self.buttons().iter().map(|(id, obj)| {
+            let button = *id;
+            WatchStream::new(obj.watch()).map(move |state| RecordEvent::Button { button, state })
+        }));
+
+        let led_events = select_all(self.leds().iter().map(|(id, obj)| {
+            let led = *id;
+            WatchStream::new(obj.watch()).map(move |state| RecordEvent::Led { led, state })
+        }));
+
+        let events = select(button_events, led_events).map(Chunk::Event);
+
+        #[cfg(feature = "video")]
+        let events = {
+            let frames = self
+                .video()
+                .map(|video| {
+                    WatchStream::new(video.frames())
+                        .filter(|frame| core::future::ready(!frame.is_empty()))
+                        .map(Chunk::Frame)
+                        .boxed()
+                })
+                .unwrap_or_else(|| futures_util::stream::pending().boxed());
+
+            select(events, frames)
+        };
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ButtonId, ServerConfig};
+    use ukvm_core::record::{RecordData, RecordReader};
+
+    #[tokio::test]
+    async fn record_session_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_owned();
+        let server = Server::new(&ServerConfig {
+            record: Some(RecorderConfig {
+                path: path.clone(),
+                auto: true,
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let recorder = server.recorder().unwrap();
+
+        server.open_record_session().await;
+        let record = recorder.path().unwrap();
+
+        recorder.log_input(&SocketInput::Button {
+            button: ButtonId::Power,
+            state: true,
+        });
+
+        server.close_record_session().await;
+        assert!(recorder.path().is_none());
+
+        let mut reader = RecordReader::new(File::open(&record).unwrap()).unwrap();
+        let chunk = reader.next_chunk().unwrap().unwrap();
+        assert!(matches!(
+            chunk.data,
+            RecordData::Event(RecordEvent::Input {
+                input: SocketInput::Button {
+                    button: ButtonId::Power,
+                    state: true
+                }
+            })
+        ));
+        assert!(reader.next_chunk().unwrap().is_none());
+    }
+}
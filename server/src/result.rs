@@ -20,6 +20,9 @@ pub enum Error {
     DBus(#[from] zbus::Error),
     #[error("DBus FDO error: {0}")]
     DBusFdo(#[from] zbus::fdo::Error),
+    #[cfg(feature = "webrtc")]
+    #[error("WebRTC error: {0}")]
+    WebRtc(#[from] webrtc::Error),
     #[error("Other error: {0}")]
     Other(String),
 }
@@ -55,6 +58,8 @@ impl From<Error> for zbus::fdo::Error {
             Error::Toml(e) => Failed(e.to_string()),
             Error::DBus(e) => ZBus(e),
             Error::DBusFdo(e) => e,
+            #[cfg(feature = "webrtc")]
+            Error::WebRtc(e) => Failed(e.to_string()),
             Error::Other(e) => Failed(e),
         }
     }
@@ -73,6 +78,8 @@ impl From<Error> for zbus::Error {
             Error::Toml(e) => Failure(e.to_string()),
             Error::DBus(e) => e,
             Error::DBusFdo(e) => FDO(Box::new(e)),
+            #[cfg(feature = "webrtc")]
+            Error::WebRtc(e) => Failure(e.to_string()),
             Error::Other(e) => Failure(e),
         }
     }
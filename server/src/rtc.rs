@@ -0,0 +1,390 @@
+use crate::{h264::H264Encoder, log, m2m::M2mDevice, video::VideoSource, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tokio::{select, spawn, sync::watch, task::spawn_blocking};
+use webrtc::{
+    api::{
+        interceptor_registry::register_default_interceptors,
+        media_engine::{MediaEngine, MIME_TYPE_H264},
+        APIBuilder,
+    },
+    ice_transport::ice_server::RTCIceServer,
+    interceptor::registry::Registry,
+    media::Sample,
+    peer_connection::{
+        configuration::RTCConfiguration, peer_connection_state::RTCPeerConnectionState,
+        sdp::session_description::RTCSessionDescription, RTCPeerConnection,
+    },
+    rtcp::payload_feedbacks::{
+        full_intra_request::FullIntraRequest, picture_loss_indication::PictureLossIndication,
+    },
+    rtp_transceiver::rtp_codec::RTCRtpCodecCapability,
+    track::track_local::{track_local_static_sample::TrackLocalStaticSample, TrackLocal},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct WebRtcConfig {
+    /// Target H.264 bitrate in kbit/s
+    #[serde(default = "default_bitrate")]
+    pub bitrate: u32,
+
+    /// Use V4L2 M2M hardware H.264 encoder when found
+    ///
+    /// Software encoder is used when hardware one is missing or failed.
+    #[serde(default = "default_true")]
+    pub hardware_encoder: bool,
+
+    /// ICE server URLs (like stun:stun.l.google.com:19302)
+    #[serde(default)]
+    pub ice_servers: Vec<String>,
+}
+
+impl Default for WebRtcConfig {
+    fn default() -> Self {
+        Self {
+            bitrate: default_bitrate(),
+            hardware_encoder: default_true(),
+            ice_servers: Vec::new(),
+        }
+    }
+}
+
+fn default_bitrate() -> u32 {
+    2000
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// H.264 format parameters which browsers support
+const H264_FMTP: &str = "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f";
+
+/// WebRTC peer which receives video
+pub struct WebRtcPeer {
+    connection: Arc<RTCPeerConnection>,
+}
+
+impl Drop for WebRtcPeer {
+    fn drop(&mut self) {
+        let connection = self.connection.clone();
+        spawn(async move {
+            if let Err(error) = connection.close().await {
+                log::warn!("Error when closing WebRTC connection: {error}");
+            }
+        });
+    }
+}
+
+impl WebRtcPeer {
+    /// Accept session offer of remote peer
+    ///
+    /// Returns peer and session answer. ICE candidates are gathered before answering.
+    pub async fn accept(
+        config: &WebRtcConfig,
+        frames: VideoSource,
+        fps: u32,
+        offer: String,
+    ) -> Result<(Self, String)> {
+        let mut media_engine = MediaEngine::default();
+        media_engine.register_default_codecs()?;
+
+        let registry = register_default_interceptors(Registry::new(), &mut media_engine)?;
+
+        let api = APIBuilder::new()
+            .with_media_engine(media_engine)
+            .with_interceptor_registry(registry)
+            .build();
+
+        let ice_servers = if config.ice_servers.is_empty() {
+            Vec::new()
+        } else {
+            vec![RTCIceServer {
+                urls: config.ice_servers.clone(),
+                ..Default::default()
+            }]
+        };
+
+        let connection = Arc::new(
+            api.new_peer_connection(RTCConfiguration {
+                ice_servers,
+                ..Default::default()
+            })
+            .await?,
+        );
+
+        // close connection on errors below
+        let peer = Self { connection };
+        let connection = &peer.connection;
+
+        let (state_sender, state_receiver) = watch::channel(RTCPeerConnectionState::New);
+
+        connection.on_peer_connection_state_change(Box::new(move |state| {
+            log::debug!("WebRTC connection state changed to {state}");
+            let _ = state_sender.send(state);
+            Box::pin(async {})
+        }));
+
+        let track = Arc::new(TrackLocalStaticSample::new(
+            RTCRtpCodecCapability {
+                mime_type: MIME_TYPE_H264.into(),
+                clock_rate: 90000,
+                sdp_fmtp_line: H264_FMTP.into(),
+                ..Default::default()
+            },
+            "video".into(),
+            "ukvm".into(),
+        ));
+
+        let sender = connection
+            .add_track(track.clone() as Arc<dyn TrackLocal + Send + Sync>)
+            .await?;
+
+        let keyframe = Arc::new(AtomicBool::new(false));
+
+        // process RTCP to handle key frame requests
+        spawn({
+            let keyframe = keyframe.clone();
+            async move {
+                while let Ok((packets, _)) = sender.read_rtcp().await {
+                    for packet in packets {
+                        let packet = packet.as_any();
+                        if packet.is::<PictureLossIndication>() || packet.is::<FullIntraRequest>() {
+                            keyframe.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+            }
+        });
+
+        connection
+            .set_remote_description(RTCSessionDescription::offer(offer)?)
+            .await?;
+
+        let answer = connection.create_answer(None).await?;
+
+        let mut gathered = connection.gathering_complete_promise().await;
+        connection.set_local_description(answer).await?;
+        let _ = gathered.recv().await;
+
+        let answer = connection
+            .local_description()
+            .await
+            .ok_or("Missing local session description")?;
+
+        let device = if config.hardware_encoder {
+            M2mDevice::find().await
+        } else {
+            None
+        };
+
+        spawn(send_frames(
+            H264Encoder::new(config.bitrate, fps, device),
+            frames,
+            state_receiver,
+            track,
+            keyframe,
+        ));
+
+        Ok((peer, answer.sdp))
+    }
+}
+
+/// Encode and send video frames while connected
+async fn send_frames(
+    encoder: H264Encoder,
+    mut frames: VideoSource,
+    mut states: watch::Receiver<RTCPeerConnectionState>,
+    track: Arc<TrackLocalStaticSample>,
+    keyframe: Arc<AtomicBool>,
+) {
+    use RTCPeerConnectionState::*;
+
+    loop {
+        match *states.borrow_and_update() {
+            Connected => break,
+            Disconnected | Failed | Closed => return,
+            _ => {}
+        }
+        if states.changed().await.is_err() {
+            return;
+        }
+    }
+
+    let mut encoder = Some(encoder);
+
+    log::info!("Start sending WebRTC video");
+
+    // send actual frame immediately
+    frames.mark_changed();
+
+    let mut last_sent = Instant::now();
+
+    loop {
+        select! {
+            result = frames.changed() => if result.is_err() {
+                break;
+            },
+            result = states.changed() => {
+                if result.is_err() || *states.borrow() != Connected {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        let frame = frames.borrow_and_update().clone();
+        if frame.is_empty() {
+            continue;
+        }
+
+        let mut frame_encoder = encoder.take().unwrap();
+        if keyframe.swap(false, Ordering::Relaxed) {
+            frame_encoder.force_keyframe();
+        }
+
+        // encode frame outside of runtime
+        let result = spawn_blocking(move || {
+            let result = frame_encoder.encode(&frame);
+            (frame_encoder, result)
+        })
+        .await;
+
+        let data = match result {
+            Ok((frame_encoder, result)) => {
+                encoder = Some(frame_encoder);
+                match result {
+                    Ok(data) => data,
+                    Err(error) => {
+                        log::warn!("{error}");
+                        continue;
+                    }
+                }
+            }
+            Err(error) => {
+                log::error!("Encoding task failed: {error}");
+                break;
+            }
+        };
+
+        if data.is_empty() {
+            // skipped by rate control
+            continue;
+        }
+
+        let now = Instant::now();
+
+        if let Err(error) = track
+            .write_sample(&Sample {
+                data: Bytes::from(data),
+                duration: now - last_sent,
+                ..Default::default()
+            })
+            .await
+        {
+            log::warn!("Error when sending WebRTC video: {error}");
+            break;
+        }
+
+        last_sent = now;
+    }
+
+    log::info!("Stop sending WebRTC video");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jpeg::{self, RawFormat, RawGeometry};
+    use tokio::{sync::mpsc, time};
+    use webrtc::rtp_transceiver::{
+        rtp_codec::RTPCodecType, rtp_transceiver_direction::RTCRtpTransceiverDirection,
+        RTCRtpTransceiverInit,
+    };
+
+    #[tokio::test]
+    async fn loopback_peer() {
+        let mut media_engine = MediaEngine::default();
+        media_engine.register_default_codecs().unwrap();
+        let api = APIBuilder::new().with_media_engine(media_engine).build();
+
+        let client = api
+            .new_peer_connection(RTCConfiguration::default())
+            .await
+            .unwrap();
+
+        client
+            .add_transceiver_from_kind(
+                RTPCodecType::Video,
+                Some(RTCRtpTransceiverInit {
+                    direction: RTCRtpTransceiverDirection::Recvonly,
+                    send_encodings: Vec::new(),
+                }),
+            )
+            .await
+            .unwrap();
+
+        let (packet_sender, mut packet_receiver) = mpsc::channel(1);
+
+        client.on_track(Box::new(move |track, _, _| {
+            let packet_sender = packet_sender.clone();
+            Box::pin(async move {
+                if let Ok((packet, _)) = track.read_rtp().await {
+                    let mime_type = track.codec().capability.mime_type;
+                    let _ = packet_sender.send((mime_type, packet.payload)).await;
+                }
+            })
+        }));
+
+        let offer = client.create_offer(None).await.unwrap();
+        let mut gathered = client.gathering_complete_promise().await;
+        client.set_local_description(offer).await.unwrap();
+        let _ = gathered.recv().await;
+        let offer = client.local_description().await.unwrap().sdp;
+
+        let geometry = RawGeometry {
+            format: RawFormat::Yuyv,
+            width: 64,
+            height: 48,
+            stride: 128,
+        };
+        let frame = jpeg::encode(geometry, &vec![128; 128 * 48], 80).unwrap();
+        let (frame_sender, frames) = watch::channel(Arc::new(frame));
+
+        let (peer, answer) = WebRtcPeer::accept(&WebRtcConfig::default(), frames, 30, offer)
+            .await
+            .unwrap();
+
+        client
+            .set_remote_description(RTCSessionDescription::answer(answer).unwrap())
+            .await
+            .unwrap();
+
+        let (mime_type, payload) = time::timeout(time::Duration::from_secs(10), async {
+            loop {
+                select! {
+                    packet = packet_receiver.recv() => break packet.unwrap(),
+                    // keep frames coming
+                    _ = time::sleep(time::Duration::from_millis(100)) => {
+                        frame_sender.send_modify(|_| {});
+                    }
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(mime_type, MIME_TYPE_H264);
+        assert!(!payload.is_empty());
+
+        drop(peer);
+        client.close().await.unwrap();
+    }
+}
@@ -0,0 +1,547 @@
+use crate::{video::VideoFrame, Result, Server};
+use core::{future::Future, time::Duration};
+use jpeg_decoder::{ColorTransform, Decoder, PixelFormat};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{io::Read, path::PathBuf, sync::Arc};
+use tokio::{task::spawn_blocking, time};
+
+/// Text screen recognition configuration
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ScreenConfig {
+    /// Console font in PSF format (may be gzipped)
+    pub font: PathBuf,
+
+    /// Text columns
+    #[serde(default = "default_columns")]
+    pub columns: u32,
+
+    /// Text rows
+    #[serde(default = "default_rows")]
+    pub rows: u32,
+
+    /// Character cell width in font pixels
+    ///
+    /// VGA text modes use 9 pixels wide cells with 8 pixels wide font.
+    /// Detected from frame width when omitted.
+    #[serde(default)]
+    pub cell_width: Option<u32>,
+}
+
+fn default_columns() -> u32 {
+    80
+}
+
+fn default_rows() -> u32 {
+    25
+}
+
+/// Minimum difference between foreground and background
+const MIN_CONTRAST: u8 = 48;
+
+/// Bitmap font glyph
+struct Glyph {
+    /// Character
+    char: char,
+
+    /// Pixels row by row
+    bits: Vec<bool>,
+}
+
+/// Bitmap console font
+pub struct Font {
+    width: u32,
+    height: u32,
+    glyphs: Vec<Glyph>,
+}
+
+impl Font {
+    /// Load PSF font
+    pub fn from_psf(data: &[u8]) -> Result<Self> {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            let mut unpacked = Vec::new();
+            flate2::read::GzDecoder::new(data).read_to_end(&mut unpacked)?;
+            return Self::from_psf(&unpacked);
+        }
+
+        let u32_at = |offset: usize| -> Result<u32> {
+            Ok(u32::from_le_bytes(
+                data.get(offset..offset + 4)
+                    .ok_or("Truncated PSF header")?
+                    .try_into()
+                    .unwrap(),
+            ))
+        };
+
+        let (width, height, count, glyph_size, offset, table) = if data.starts_with(&[0x36, 0x04]) {
+            let mode = *data.get(2).ok_or("Truncated PSF header")?;
+            let height = *data.get(3).ok_or("Truncated PSF header")? as u32;
+            let count = if mode & 0x01 != 0 { 512 } else { 256 };
+            (8, height, count, height as usize, 4, mode & 0x06 != 0)
+        } else if data.starts_with(&[0x72, 0xb5, 0x4a, 0x86]) {
+            let offset = u32_at(8)? as usize;
+            let flags = u32_at(12)?;
+            let count = u32_at(16)? as usize;
+            let glyph_size = u32_at(20)? as usize;
+            let height = u32_at(24)?;
+            let width = u32_at(28)?;
+            (width, height, count, glyph_size, offset, flags & 0x01 != 0)
+        } else {
+            Err("Unknown font format")?
+        };
+
+        let row_size = width.div_ceil(8) as usize;
+        if width == 0
+            || height == 0
+            || row_size
+                .checked_mul(height as usize)
+                .is_none_or(|size| glyph_size < size)
+        {
+            Err(format!("Invalid font size {width}x{height}"))?;
+        }
+
+        // header values are untrusted
+        let end = count
+            .checked_mul(glyph_size)
+            .and_then(|size| size.checked_add(offset))
+            .ok_or("Truncated font glyphs")?;
+        let bitmaps = data.get(offset..end).ok_or("Truncated font glyphs")?;
+
+        let chars = if table {
+            let table = &data[end..];
+            if data[0] == 0x36 {
+                psf1_chars(table, count)
+            } else {
+                psf2_chars(table, count)
+            }
+        } else {
+            // glyphs are in code page order which is ASCII compatible
+            (0..count)
+                .map(|index| {
+                    (index < 0x80)
+                        .then_some(index as u8 as char)
+                        .into_iter()
+                        .collect()
+                })
+                .collect()
+        };
+
+        let glyphs = bitmaps
+            .chunks_exact(glyph_size)
+            .zip(chars)
+            .filter_map(|(bitmap, chars)| {
+                let char = chars
+                    .into_iter()
+                    .find(|char: &char| !char.is_control() && !char.is_whitespace())?;
+                let bits = (0..height as usize)
+                    .flat_map(|y| {
+                        let row = &bitmap[y * row_size..][..row_size];
+                        (0..width as usize).map(move |x| row[x / 8] & (0x80 >> (x % 8)) != 0)
+                    })
+                    .collect::<Vec<_>>();
+                // blank cells are recognized as spaces
+                bits.contains(&true).then_some(Glyph { char, bits })
+            })
+            .collect::<Vec<_>>();
+
+        if glyphs.is_empty() {
+            Err("Font has no printable glyphs")?;
+        }
+
+        Ok(Self {
+            width,
+            height,
+            glyphs,
+        })
+    }
+
+    /// Find best matching glyph
+    fn find(&self, bits: &[bool]) -> Option<char> {
+        let (distance, glyph) = self
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                let distance = glyph.bits.iter().zip(bits).filter(|(a, b)| a != b).count();
+                (distance, glyph)
+            })
+            .min_by_key(|(distance, _)| *distance)?;
+
+        // allow up to one eighth of pixels to differ
+        (distance <= bits.len() / 8).then_some(glyph.char)
+    }
+}
+
+/// Characters of PSF1 glyphs
+fn psf1_chars(table: &[u8], count: usize) -> Vec<Vec<char>> {
+    let mut chars = vec![Vec::new(); count];
+    let mut index = 0;
+    let mut sequence = false;
+
+    for code in table.chunks_exact(2) {
+        if index >= count {
+            break;
+        }
+        match u16::from_le_bytes([code[0], code[1]]) {
+            0xffff => {
+                index += 1;
+                sequence = false;
+            }
+            0xfffe => sequence = true,
+            code if !sequence => chars[index].extend(char::from_u32(code as _)),
+            _ => {}
+        }
+    }
+
+    chars
+}
+
+/// Characters of PSF2 glyphs
+fn psf2_chars(table: &[u8], count: usize) -> Vec<Vec<char>> {
+    table
+        .split(|byte| *byte == 0xff)
+        .take(count)
+        .map(|entry| {
+            // combining sequences follow after 0xfe
+            let singles = entry.split(|byte| *byte == 0xfe).next().unwrap_or_default();
+            core::str::from_utf8(singles)
+                .map(|chars| chars.chars().collect())
+                .unwrap_or_default()
+        })
+        .chain(core::iter::repeat(Vec::new()))
+        .take(count)
+        .collect()
+}
+
+/// Grayscale image
+struct Luma {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl Luma {
+    /// Decode JPEG frame
+    fn from_jpeg(jpeg: &[u8]) -> Result<Self> {
+        let mut decoder = Decoder::new(jpeg);
+
+        // RGB transform only interleaves components so luma is kept as is
+        decoder.set_color_transform(ColorTransform::RGB);
+
+        let data = decoder
+            .decode()
+            .map_err(|error| format!("Error when decoding JPEG: {error}"))?;
+
+        let info = decoder.info().ok_or("Missing JPEG info")?;
+
+        let data = match info.pixel_format {
+            PixelFormat::L8 => data,
+            PixelFormat::RGB24 => data.into_iter().step_by(3).collect(),
+            format => Err(format!("Unsupported JPEG pixel format: {format:?}"))?,
+        };
+
+        Ok(Self {
+            width: info.width as _,
+            height: info.height as _,
+            data,
+        })
+    }
+}
+
+/// Text screen recognizer
+#[derive(Clone)]
+pub struct Screen {
+    config: ScreenConfig,
+    font: Arc<Font>,
+}
+
+impl Screen {
+    /// Create recognizer using config
+    pub async fn new(config: &ScreenConfig) -> Result<Self> {
+        let data = tokio::fs::read(&config.font).await?;
+        let font = Font::from_psf(&data)
+            .map_err(|error| format!("Unable to load {}: {error}", config.font.display()))?;
+
+        if config.columns == 0 || config.rows == 0 {
+            Err("Invalid text screen size")?;
+        }
+
+        Ok(Self {
+            config: config.clone(),
+            font: Arc::new(font),
+        })
+    }
+
+    /// Recognize text on frame
+    pub async fn recognize(&self, frame: VideoFrame) -> Result<String> {
+        let config = self.config.clone();
+        let font = self.font.clone();
+
+        spawn_blocking(move || {
+            let luma = Luma::from_jpeg(&frame)?;
+            Ok(recognize(&config, &font, &luma))
+        })
+        .await
+        .map_err(|error| format!("Text recognition failed: {error}"))?
+    }
+}
+
+/// Recognize text screen
+///
+/// Unknown characters are replaced by `?` and trailing spaces are trimmed.
+fn recognize(config: &ScreenConfig, font: &Font, luma: &Luma) -> String {
+    if luma.width == 0 || luma.height == 0 {
+        return String::new();
+    }
+
+    let cell_width = luma.width as f32 / config.columns as f32;
+    let cell_height = luma.height as f32 / config.rows as f32;
+
+    let cell_columns = config.cell_width.unwrap_or_else(|| {
+        // VGA text modes are 720 pixels wide
+        if font.width == 8 && luma.width.is_multiple_of(config.columns as usize * 9) {
+            9
+        } else {
+            font.width
+        }
+    });
+
+    let step_x = cell_width / cell_columns.max(font.width) as f32;
+    let step_y = cell_height / font.height as f32;
+
+    let mut text = String::new();
+    let mut bits = vec![false; (font.width * font.height) as usize];
+    let mut samples = vec![0u8; bits.len()];
+
+    for row in 0..config.rows {
+        let mut line = String::new();
+
+        for column in 0..config.columns {
+            let left = column as f32 * cell_width;
+            let top = row as f32 * cell_height;
+
+            for y in 0..font.height {
+                let sy = ((top + (y as f32 + 0.5) * step_y) as usize).min(luma.height - 1);
+                for x in 0..font.width {
+                    let sx = ((left + (x as f32 + 0.5) * step_x) as usize).min(luma.width - 1);
+                    samples[(y * font.width + x) as usize] = luma.data[sy * luma.width + sx];
+                }
+            }
+
+            let min = *samples.iter().min().unwrap();
+            let max = *samples.iter().max().unwrap();
+
+            if max - min < MIN_CONTRAST {
+                line.push(' ');
+                continue;
+            }
+
+            let threshold = min + (max - min) / 2;
+            let mut count = 0;
+            for (bit, sample) in bits.iter_mut().zip(&samples) {
+                *bit = *sample > threshold;
+                count += *bit as usize;
+            }
+
+            // background covers most of cell
+            if count > bits.len() / 2 {
+                bits.iter_mut().for_each(|bit| *bit = !*bit);
+            }
+
+            line.push(font.find(&bits).unwrap_or('?'));
+        }
+
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+
+    text
+}
+
+impl Server {
+    /// Recognize text on screen
+    pub async fn screen_text(&self) -> Result<String> {
+        let screen = self.screen().ok_or("Screen recognition disabled")?;
+        let frame = self.video().ok_or("Video disabled")?.snapshot().await?;
+
+        screen.recognize(frame).await
+    }
+
+    /// Wait until text on screen matches regular expression
+    ///
+    /// Returns matched text. Returned future doesn't hold server instance.
+    pub fn wait_screen_text(
+        &self,
+        regex: &str,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<String>> + Send + 'static {
+        let screen = self.screen().cloned();
+        let frames = self.video().map(|video| video.frames());
+        let regex = Regex::new(regex);
+
+        async move {
+            let screen = screen.ok_or("Screen recognition disabled")?;
+            let mut frames = frames.ok_or("Video disabled")?;
+            let regex = regex.map_err(|error| format!("Invalid regex: {error}"))?;
+
+            time::timeout(timeout, async {
+                frames.mark_changed();
+
+                loop {
+                    frames
+                        .changed()
+                        .await
+                        .map_err(|_| "Video capturing stopped")?;
+
+                    let frame = frames.borrow_and_update().clone();
+                    if frame.is_empty() {
+                        continue;
+                    }
+
+                    let text = screen.recognize(frame).await?;
+
+                    if let Some(found) = regex.find(&text) {
+                        return Ok(found.as_str().into());
+                    }
+                }
+            })
+            .await
+            .map_err(|_| "Timeout when waiting for text")?
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEXT: &str = "login: root";
+
+    /// Distinct pseudo-random glyph for character
+    fn glyph(char: char) -> [u8; 16] {
+        let mut seed = (char as u32).wrapping_mul(2654435761);
+        core::array::from_fn(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            // sparse like real glyphs
+            (seed as u8) & ((seed >> 8) as u8)
+        })
+    }
+
+    /// PSF2 font with unicode table
+    fn psf2_font() -> Vec<u8> {
+        let chars = "loginrt: ".chars().collect::<Vec<_>>();
+        let mut data = Vec::new();
+        for value in [0x864ab572u32, 0, 32, 1, chars.len() as _, 16, 16, 8] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for char in &chars {
+            if *char == ' ' {
+                data.extend_from_slice(&[0; 16]);
+            } else {
+                data.extend_from_slice(&glyph(*char));
+            }
+        }
+        for char in &chars {
+            data.extend_from_slice(char.to_string().as_bytes());
+            data.push(0xff);
+        }
+        data
+    }
+
+    /// Render VGA text screen as luma
+    fn render(text: &str, width: usize, height: usize) -> Vec<u8> {
+        let (cell_width, cell_height) = (width / 80, height / 25);
+        let scale_x = cell_width / 9;
+        let scale_y = cell_height / 16;
+        let mut data = vec![20u8; width * height];
+        for (column, char) in text.chars().enumerate() {
+            let bitmap = if char == ' ' { [0; 16] } else { glyph(char) };
+            for y in 0..cell_height {
+                for x in 0..cell_width {
+                    let (gx, gy) = (x / scale_x, y / scale_y);
+                    if gx < 8 && bitmap[gy] & (0x80 >> gx) != 0 {
+                        data[(2 * cell_height + y) * width + column * cell_width + x] = 200;
+                    }
+                }
+            }
+        }
+        data
+    }
+
+    fn config() -> ScreenConfig {
+        ScreenConfig {
+            font: PathBuf::new(),
+            columns: 80,
+            rows: 25,
+            cell_width: None,
+        }
+    }
+
+    #[test]
+    fn load_psf2() {
+        let font = Font::from_psf(&psf2_font()).unwrap();
+        assert_eq!((font.width, font.height), (8, 16));
+        // space glyph is skipped
+        assert_eq!(font.glyphs.len(), 8);
+        assert_eq!(font.find(&font.glyphs[2].bits), Some('g'));
+
+        // glyphs beyond data
+        let mut data = psf2_font();
+        data[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Font::from_psf(&data).is_err());
+    }
+
+    #[test]
+    fn load_psf1() {
+        let mut data = vec![0x36, 0x04, 0x00, 16];
+        for index in 0..256u32 {
+            let char = char::from_u32(index).unwrap();
+            data.extend_from_slice(&if char.is_ascii_graphic() {
+                glyph(char)
+            } else {
+                [0; 16]
+            });
+        }
+        let font = Font::from_psf(&data).unwrap();
+        assert_eq!(font.glyphs.len(), 94);
+        assert_eq!(font.find(&font.glyphs[0].bits), Some('!'));
+    }
+
+    #[test]
+    fn recognize_text() {
+        let font = Font::from_psf(&psf2_font()).unwrap();
+
+        let luma = Luma {
+            width: 720,
+            height: 400,
+            data: render(TEXT, 720, 400),
+        };
+        let text = recognize(&config(), &font, &luma);
+        assert_eq!(text.lines().nth(2), Some(TEXT));
+        assert_eq!(text.lines().count(), 25);
+
+        // upscaled and compressed
+        let data = render(TEXT, 1440, 800)
+            .into_iter()
+            .flat_map(|luma| [luma, 128])
+            .collect::<Vec<_>>();
+        let geometry = crate::jpeg::RawGeometry {
+            format: crate::jpeg::RawFormat::Yuyv,
+            width: 1440,
+            height: 800,
+            stride: 2880,
+        };
+        let jpeg = crate::jpeg::encode(geometry, &data, 90).unwrap();
+        let luma = Luma::from_jpeg(&jpeg).unwrap();
+        let text = recognize(&config(), &font, &luma);
+        assert_eq!(text.lines().nth(2), Some(TEXT));
+
+        let luma = Luma {
+            width: 0,
+            height: 0,
+            data: Vec::new(),
+        };
+        assert_eq!(recognize(&config(), &font, &luma), "");
+    }
+}
@@ -0,0 +1,502 @@
+use crate::{video::VideoFrame, Result, Server};
+use jpeg_decoder::{Decoder, PixelFormat};
+use parse_display::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::task::spawn_blocking;
+
+/// Maximum screenshot width or height in pixels
+const MAX_SIZE: u32 = 8192;
+
+/// Maximum number of cached screenshots
+const CACHE_SIZE: usize = 8;
+
+/// Screenshot image format
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[serde(rename_all = "lowercase")]
+#[display(style = "lowercase")]
+pub enum ImageFormat {
+    /// Portable network graphics
+    #[default]
+    Png,
+
+    /// JPEG
+    Jpeg,
+
+    /// Lossless WebP
+    Webp,
+}
+
+impl ImageFormat {
+    /// Media type of image
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+/// Region of frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display)]
+#[display("{x},{y},{width},{height}")]
+#[serde(try_from = "String", into = "String")]
+pub struct Crop {
+    /// Left offset in pixels
+    pub x: u32,
+    /// Top offset in pixels
+    pub y: u32,
+    /// Region width in pixels
+    pub width: u32,
+    /// Region height in pixels
+    pub height: u32,
+}
+
+impl TryFrom<String> for Crop {
+    type Error = String;
+
+    fn try_from(value: String) -> core::result::Result<Self, Self::Error> {
+        value
+            .parse()
+            .map_err(|_| format!("The 'x,y,width,height' expected but given '{value}'"))
+    }
+}
+
+impl From<Crop> for String {
+    fn from(crop: Crop) -> Self {
+        crop.to_string()
+    }
+}
+
+/// Screenshot options
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScreenshotOptions {
+    /// Image format
+    #[serde(default, rename = "fmt")]
+    pub format: ImageFormat,
+
+    /// Scale to width (keeps aspect when height is omitted)
+    #[serde(default, rename = "w")]
+    pub width: Option<u32>,
+
+    /// Scale to height (keeps aspect when width is omitted)
+    #[serde(default, rename = "h")]
+    pub height: Option<u32>,
+
+    /// Crop region before scaling
+    #[serde(default)]
+    pub crop: Option<Crop>,
+
+    /// JPEG quality (1-100)
+    #[serde(default, rename = "q")]
+    pub quality: Option<u8>,
+}
+
+/// Decoded image
+struct Image {
+    width: u32,
+    height: u32,
+    /// Bytes per pixel (1 for grayscale or 3 for RGB)
+    channels: u32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    /// Decode JPEG frame
+    fn from_jpeg(jpeg: &[u8]) -> Result<Self> {
+        let mut decoder = Decoder::new(jpeg);
+
+        let data = decoder
+            .decode()
+            .map_err(|error| format!("Error when decoding JPEG: {error}"))?;
+
+        let info = decoder.info().ok_or("Missing JPEG info")?;
+
+        let channels = match info.pixel_format {
+            PixelFormat::L8 => 1,
+            PixelFormat::RGB24 => 3,
+            format => Err(format!("Unsupported JPEG pixel format: {format:?}"))?,
+        };
+
+        Ok(Self {
+            width: info.width as _,
+            height: info.height as _,
+            channels,
+            data,
+        })
+    }
+
+    /// Cut region
+    fn crop(&self, crop: &Crop) -> Result<Self> {
+        if crop.width == 0
+            || crop.height == 0
+            || crop.x.saturating_add(crop.width) > self.width
+            || crop.y.saturating_add(crop.height) > self.height
+        {
+            Err(format!(
+                "Crop region {crop} is out of {}x{} frame",
+                self.width, self.height
+            ))?;
+        }
+
+        let stride = (self.width * self.channels) as usize;
+        let line = (crop.width * self.channels) as usize;
+        let left = (crop.x * self.channels) as usize;
+
+        let data = (crop.y..crop.y + crop.height)
+            .flat_map(|y| &self.data[y as usize * stride + left..][..line])
+            .copied()
+            .collect();
+
+        Ok(Self {
+            width: crop.width,
+            height: crop.height,
+            channels: self.channels,
+            data,
+        })
+    }
+
+    /// Resize by averaging covered pixels
+    fn scale(&self, width: u32, height: u32) -> Self {
+        if width == self.width && height == self.height {
+            return Self {
+                data: self.data.clone(),
+                ..*self
+            };
+        }
+
+        let channels = self.channels as usize;
+        let stride = self.width as usize * channels;
+        let span = |index: u32, size: u32, source: u32| {
+            let start = (index as u64 * source as u64 / size as u64) as usize;
+            let end = ((index as u64 + 1) * source as u64).div_ceil(size as u64) as usize;
+            start..end.max(start + 1)
+        };
+
+        let mut data = Vec::with_capacity(width as usize * height as usize * channels);
+        let mut sums = vec![0u32; channels];
+
+        for y in 0..height {
+            let rows = span(y, height, self.height);
+            for x in 0..width {
+                let columns = span(x, width, self.width);
+                sums.fill(0);
+                for row in rows.clone() {
+                    let line = &self.data[row * stride..];
+                    for column in columns.clone() {
+                        for (sum, value) in
+                            sums.iter_mut().zip(&line[column * channels..][..channels])
+                        {
+                            *sum += *value as u32;
+                        }
+                    }
+                }
+                let count = (rows.len() * columns.len()) as u32;
+                data.extend(sums.iter().map(|sum| ((sum + count / 2) / count) as u8));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            channels: self.channels,
+            data,
+        }
+    }
+
+    /// Encode image
+    fn encode(&self, format: ImageFormat, quality: u8) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+
+        match format {
+            ImageFormat::Png => {
+                let mut encoder = png::Encoder::new(&mut output, self.width, self.height);
+                encoder.set_color(if self.channels == 1 {
+                    png::ColorType::Grayscale
+                } else {
+                    png::ColorType::Rgb
+                });
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder
+                    .write_header()
+                    .and_then(|mut writer| writer.write_image_data(&self.data))
+                    .map_err(|error| format!("Error when encoding PNG: {error}"))?;
+            }
+            ImageFormat::Jpeg => {
+                jpeg_encoder::Encoder::new(&mut output, quality)
+                    .encode(
+                        &self.data,
+                        self.width as _,
+                        self.height as _,
+                        if self.channels == 1 {
+                            jpeg_encoder::ColorType::Luma
+                        } else {
+                            jpeg_encoder::ColorType::Rgb
+                        },
+                    )
+                    .map_err(|error| format!("Error when encoding JPEG: {error}"))?;
+            }
+            ImageFormat::Webp => {
+                image_webp::WebPEncoder::new(&mut output)
+                    .encode(
+                        &self.data,
+                        self.width,
+                        self.height,
+                        if self.channels == 1 {
+                            image_webp::ColorType::L8
+                        } else {
+                            image_webp::ColorType::Rgb8
+                        },
+                    )
+                    .map_err(|error| format!("Error when encoding WebP: {error}"))?;
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+impl ScreenshotOptions {
+    /// Output size for cropped frame size
+    fn output_size(&self, width: u32, height: u32) -> Result<(u32, u32)> {
+        let scale = |size: u32, from: u32, to: u32| {
+            ((size as u64 * to as u64 + from as u64 / 2) / from as u64).max(1) as u32
+        };
+
+        let size = match (self.width, self.height) {
+            (Some(out_width), Some(out_height)) => (out_width, out_height),
+            (Some(out_width), None) => (out_width, scale(height, width, out_width)),
+            (None, Some(out_height)) => (scale(width, height, out_height), out_height),
+            (None, None) => (width, height),
+        };
+
+        if size.0 == 0 || size.1 == 0 || size.0 > MAX_SIZE || size.1 > MAX_SIZE {
+            Err(format!(
+                "Unsupported screenshot size: {}x{}",
+                size.0, size.1
+            ))?;
+        }
+
+        Ok(size)
+    }
+
+    /// Make screenshot from frame
+    fn apply(&self, frame: &[u8]) -> Result<Vec<u8>> {
+        let image = Image::from_jpeg(frame)?;
+
+        let image = if let Some(crop) = &self.crop {
+            image.crop(crop)?
+        } else {
+            image
+        };
+
+        let (width, height) = self.output_size(image.width, image.height)?;
+
+        image
+            .scale(width, height)
+            .encode(self.format, self.quality.unwrap_or(85).clamp(1, 100))
+    }
+}
+
+/// Cached screenshot
+struct CacheEntry {
+    frame: VideoFrame,
+    options: ScreenshotOptions,
+    image: Arc<Vec<u8>>,
+}
+
+/// Screenshots of latest frames
+#[derive(Default)]
+pub struct Screenshots {
+    cache: Mutex<Vec<CacheEntry>>,
+}
+
+impl Screenshots {
+    fn get(&self, frame: &VideoFrame, options: &ScreenshotOptions) -> Option<Arc<Vec<u8>>> {
+        self.cache
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|entry| Arc::ptr_eq(&entry.frame, frame) && entry.options == *options)
+            .map(|entry| entry.image.clone())
+    }
+
+    fn put(&self, frame: VideoFrame, options: ScreenshotOptions, image: Arc<Vec<u8>>) {
+        let mut cache = self.cache.lock().unwrap();
+
+        // screenshots of outdated frames aren't needed anymore
+        cache.retain(|entry| Arc::ptr_eq(&entry.frame, &frame));
+        if cache.len() >= CACHE_SIZE {
+            cache.remove(0);
+        }
+
+        cache.push(CacheEntry {
+            frame,
+            options,
+            image,
+        });
+    }
+}
+
+impl Server {
+    /// Make screenshot of latest frame
+    pub async fn screenshot(&self, options: ScreenshotOptions) -> Result<Arc<Vec<u8>>> {
+        let frame = self.video().ok_or("Video disabled")?.snapshot().await?;
+        let screenshots = self.screenshots();
+
+        if let Some(image) = screenshots.get(&frame, &options) {
+            return Ok(image);
+        }
+
+        let image = spawn_blocking({
+            let frame = frame.clone();
+            move || options.apply(&frame)
+        })
+        .await
+        .map_err(|error| format!("Screenshot failed: {error}"))??;
+
+        let image = Arc::new(image);
+        screenshots.put(frame, options, image.clone());
+
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jpeg::{self, RawFormat, RawGeometry};
+
+    fn frame() -> Vec<u8> {
+        let geometry = RawGeometry {
+            format: RawFormat::Yuyv,
+            width: 64,
+            height: 48,
+            stride: 128,
+        };
+        jpeg::encode(geometry, &vec![128; 128 * 48], 80).unwrap()
+    }
+
+    #[test]
+    fn parse_options() {
+        let crop: Crop = "10,20,300,200".parse().unwrap();
+        assert_eq!(
+            crop,
+            Crop {
+                x: 10,
+                y: 20,
+                width: 300,
+                height: 200
+            }
+        );
+        assert!("10,20,300".parse::<Crop>().is_err());
+        assert_eq!("webp".parse::<ImageFormat>().unwrap(), ImageFormat::Webp);
+    }
+
+    #[test]
+    fn output_size() {
+        let options = ScreenshotOptions {
+            width: Some(320),
+            ..Default::default()
+        };
+        assert_eq!(options.output_size(1920, 1080).unwrap(), (320, 180));
+
+        let options = ScreenshotOptions {
+            width: Some(0),
+            ..Default::default()
+        };
+        assert!(options.output_size(1920, 1080).is_err());
+    }
+
+    #[test]
+    fn scale_image() {
+        let image = Image {
+            width: 4,
+            height: 2,
+            channels: 1,
+            data: vec![0, 100, 200, 200, 0, 100, 200, 200],
+        };
+        assert_eq!(image.scale(2, 1).data, [50, 200]);
+        assert_eq!(
+            image.scale(8, 2).data[..8],
+            [0, 0, 100, 100, 200, 200, 200, 200]
+        );
+
+        let cropped = image
+            .crop(&Crop {
+                x: 1,
+                y: 1,
+                width: 2,
+                height: 1,
+            })
+            .unwrap();
+        assert_eq!(cropped.data, [100, 200]);
+        assert!(image
+            .crop(&Crop {
+                x: 3,
+                y: 0,
+                width: 2,
+                height: 1
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn encode_formats() {
+        let frame = frame();
+        let options = ScreenshotOptions {
+            width: Some(32),
+            crop: Some(Crop {
+                x: 0,
+                y: 0,
+                width: 32,
+                height: 32,
+            }),
+            ..Default::default()
+        };
+
+        let png = options.apply(&frame).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        let jpeg = ScreenshotOptions {
+            format: ImageFormat::Jpeg,
+            ..options
+        }
+        .apply(&frame)
+        .unwrap();
+        assert!(jpeg.starts_with(&[0xff, 0xd8]));
+
+        let webp = ScreenshotOptions {
+            format: ImageFormat::Webp,
+            ..options
+        }
+        .apply(&frame)
+        .unwrap();
+        assert!(webp.starts_with(b"RIFF") && &webp[8..12] == b"WEBP");
+    }
+
+    #[test]
+    fn cache_by_frame() {
+        let screenshots = Screenshots::default();
+        let options = ScreenshotOptions::default();
+        let frame = Arc::new(frame());
+        let image = Arc::new(vec![1]);
+
+        screenshots.put(frame.clone(), options, image.clone());
+        assert!(Arc::ptr_eq(
+            &screenshots.get(&frame, &options).unwrap(),
+            &image
+        ));
+
+        // same content but another frame
+        let other = Arc::new(frame.as_ref().clone());
+        assert!(screenshots.get(&other, &options).is_none());
+
+        screenshots.put(other.clone(), options, image);
+        assert!(screenshots.get(&frame, &options).is_none());
+        assert_eq!(screenshots.cache.lock().unwrap().len(), 1);
+    }
+}
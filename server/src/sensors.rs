@@ -0,0 +1,480 @@
+use crate::{log, Result, SensorKind, SensorReading, SensorStatus};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{fs, select, spawn, sync::watch, time::interval};
+
+/// Sysfs mount point
+pub(crate) const SYSFS: &str = "/sys";
+
+/// Hardware monitoring devices directory relative to sysfs
+const HWMON_CLASS: &str = "class/hwmon";
+
+/// Industrial I/O devices directory relative to sysfs
+const IIO_DEVICES: &str = "bus/iio/devices";
+
+/// Sensor configuration
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SensorConfig {
+    /// Measured quantity
+    #[serde(default)]
+    pub kind: SensorKind,
+
+    /// Attribute file path (relative paths are looked up in sysfs)
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+
+    /// Name of hwmon device (like `ina226`)
+    #[serde(default)]
+    pub hwmon: Option<String>,
+
+    /// Name of IIO device (like `ina219`)
+    #[serde(default)]
+    pub iio: Option<String>,
+
+    /// Device attribute (like `in1_input` or `in_voltage0_raw`)
+    #[serde(default)]
+    pub attr: Option<String>,
+
+    /// Multiplier to get value in units of quantity
+    ///
+    /// Defaults to hwmon or IIO units conversion.
+    #[serde(default)]
+    pub scale: Option<f64>,
+
+    /// Offset added to raw value before scaling
+    #[serde(default)]
+    pub offset: f64,
+
+    /// Reading interval in milliseconds
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+
+    /// Report low value below threshold
+    #[serde(default)]
+    pub min: Option<f64>,
+
+    /// Report high value above threshold
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+fn default_interval() -> u32 {
+    1000
+}
+
+/// Sensors configuration
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SensorsConfig {
+    /// Sensor configurations
+    #[serde(flatten)]
+    pub sensors: HashMap<String, SensorConfig>,
+}
+
+/// Sensor state report
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SensorInfo {
+    /// Measured quantity
+    pub kind: SensorKind,
+    /// Measurement units
+    pub unit: &'static str,
+    /// Current value
+    pub value: Option<f64>,
+    /// Value state
+    pub status: SensorStatus,
+    /// Low threshold
+    pub min: Option<f64>,
+    /// High threshold
+    pub max: Option<f64>,
+}
+
+/// Resolved sensor attribute
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SensorSource {
+    /// Attribute file
+    pub path: PathBuf,
+    /// Raw value offset
+    pub offset: f64,
+    /// Raw value multiplier
+    pub scale: f64,
+}
+
+impl SensorSource {
+    /// Find sensor attribute
+    pub async fn resolve(sysfs: &Path, config: &SensorConfig) -> Result<Self> {
+        // hwmon and IIO report values in milli-units (hwmon power in micro-units)
+        let milli = match config.kind {
+            SensorKind::Other => 1.0,
+            _ => 0.001,
+        };
+
+        let (path, offset, scale) = if let Some(path) = &config.path {
+            (sysfs.join(path), 0.0, 1.0)
+        } else if let Some(name) = &config.hwmon {
+            let device = find_device(&sysfs.join(HWMON_CLASS), "hwmon", name).await?;
+            let scale = if config.kind == SensorKind::Power {
+                0.000001
+            } else {
+                milli
+            };
+            (device.join(attr(config)?), 0.0, scale)
+        } else if let Some(name) = &config.iio {
+            let device = find_device(&sysfs.join(IIO_DEVICES), "IIO", name).await?;
+            let attr = attr(config)?;
+            let (offset, scale) = if let Some(prefix) = attr.strip_suffix("_raw") {
+                (
+                    iio_attr(&device, prefix, "offset").await?.unwrap_or(0.0),
+                    iio_attr(&device, prefix, "scale").await?.unwrap_or(1.0),
+                )
+            } else {
+                (0.0, 1.0)
+            };
+            (device.join(attr), offset, scale * milli)
+        } else {
+            return Err("Sensor path, hwmon or iio device should be set")?;
+        };
+
+        if fs::metadata(&path).await.is_err() {
+            return Err(format!("Sensor attribute {} not found", path.display()))?;
+        }
+
+        Ok(Self {
+            path,
+            offset: offset + config.offset,
+            scale: config.scale.unwrap_or(scale),
+        })
+    }
+
+    /// Read value
+    pub async fn read(&self) -> Result<f64> {
+        let data = fs::read_to_string(&self.path).await?;
+        let raw: f64 = data
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid sensor value {:?}", data.trim()))?;
+        Ok((raw + self.offset) * self.scale)
+    }
+}
+
+fn attr(config: &SensorConfig) -> Result<&str> {
+    Ok(config
+        .attr
+        .as_deref()
+        .ok_or("Sensor device attribute should be set")?)
+}
+
+/// Find device directory by name
+async fn find_device(class: &Path, kind: &str, name: &str) -> Result<PathBuf> {
+    let mut found = Vec::new();
+
+    if let Ok(mut dir) = fs::read_dir(class).await {
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if let Ok(data) = fs::read_to_string(path.join("name")).await {
+                if data.trim() == name {
+                    found.push(path);
+                }
+            }
+        }
+    }
+
+    found.sort();
+
+    match found.len() {
+        0 => Err(format!("{kind} device \"{name}\" not found"))?,
+        1 => Ok(found.pop().unwrap()),
+        _ => {
+            let paths = found
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>();
+            Err(format!(
+                "{kind} device \"{name}\" is ambiguous: found {}",
+                paths.join(", ")
+            ))?
+        }
+    }
+}
+
+/// Read IIO channel attribute falling back to shared one
+///
+/// For example `in_voltage0_scale` or `in_voltage_scale`.
+async fn iio_attr(device: &Path, prefix: &str, name: &str) -> Result<Option<f64>> {
+    let shared = prefix.trim_end_matches(|c: char| c.is_ascii_digit());
+
+    for prefix in [prefix, shared] {
+        if let Ok(data) = fs::read_to_string(device.join(format!("{prefix}_{name}"))).await {
+            let value = data
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid IIO {name} {:?}", data.trim()))?;
+            return Ok(Some(value));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Single sensor
+pub struct Sensor {
+    reading_receiver: watch::Receiver<SensorReading>,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Sensor {
+    /// Instantiate new sensor
+    pub async fn new(name: &str, sysfs: &Path, config: &SensorConfig) -> Result<Self> {
+        let source = SensorSource::resolve(sysfs, config)
+            .await
+            .map_err(|error| format!("Sensor {name}: {error}"))?;
+
+        let kind = config.kind;
+        let (min, max) = (config.min, config.max);
+
+        let reading = move |value: Option<f64>| SensorReading {
+            kind,
+            value,
+            status: match value {
+                None => SensorStatus::Error,
+                Some(value) if min.map(|min| value < min).unwrap_or_default() => SensorStatus::Low,
+                Some(value) if max.map(|max| value > max).unwrap_or_default() => SensorStatus::High,
+                _ => SensorStatus::Ok,
+            },
+        };
+
+        let (reading_sender, reading_receiver) = watch::channel(reading(source.read().await.ok()));
+
+        let name = name.to_string();
+        let mut ticker = interval(Duration::from_millis(config.interval.max(1) as _));
+
+        spawn(async move {
+            log::debug!("{name}: Initialize reading sensor");
+
+            loop {
+                select! {
+                    // Sensor object dropped
+                    _ = reading_sender.closed() => break,
+                    _ = ticker.tick() => {}
+                }
+
+                let value = match source.read().await {
+                    Ok(value) => Some(value),
+                    Err(error) => {
+                        if reading_sender.borrow().value.is_some() {
+                            log::warn!("{name}: Unable to read sensor: {error}");
+                        }
+                        None
+                    }
+                };
+
+                let reading = reading(value);
+
+                reading_sender.send_if_modified(|current| {
+                    if current.status != reading.status {
+                        log::info!("{name}: Sensor status changed: {}", reading);
+                    }
+                    let changed = *current != reading;
+                    *current = reading;
+                    changed
+                });
+            }
+
+            log::debug!("{name}: Finalize reading sensor");
+        });
+
+        Ok(Self {
+            reading_receiver,
+            min,
+            max,
+        })
+    }
+
+    /// Get current reading
+    pub fn reading(&self) -> SensorReading {
+        *self.reading_receiver.borrow()
+    }
+
+    /// Watch reading changes
+    pub fn watch(&self) -> watch::Receiver<SensorReading> {
+        self.reading_receiver.clone()
+    }
+
+    /// Get state report
+    pub fn info(&self) -> SensorInfo {
+        let reading = self.reading();
+        SensorInfo {
+            kind: reading.kind,
+            unit: reading.kind.unit(),
+            value: reading.value,
+            status: reading.status,
+            min: self.min,
+            max: self.max,
+        }
+    }
+}
+
+/// Sensors monitoring service
+#[derive(educe::Educe)]
+#[educe(Deref)]
+pub struct Sensors {
+    /// Sensors
+    sensors: HashMap<String, Arc<Sensor>>,
+}
+
+impl Sensors {
+    /// Create sensors monitoring service using specified config
+    pub async fn new(config: &SensorsConfig) -> Result<Self> {
+        Self::with_reused(config, HashMap::default()).await
+    }
+
+    /// Create sensors monitoring service reusing already instantiated sensors
+    pub async fn with_reused(
+        config: &SensorsConfig,
+        reused: HashMap<String, Arc<Sensor>>,
+    ) -> Result<Self> {
+        Self::with_sysfs(config, Path::new(SYSFS), reused).await
+    }
+
+    /// Create sensors monitoring service using specified sysfs mount point
+    pub(crate) async fn with_sysfs(
+        config: &SensorsConfig,
+        sysfs: &Path,
+        mut reused: HashMap<String, Arc<Sensor>>,
+    ) -> Result<Self> {
+        let mut sensors = HashMap::default();
+
+        for (name, sensor_config) in &config.sensors {
+            // names are used in D-Bus object paths
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!(
+                    "Invalid sensor name {name:?}: only letters, digits and underscore allowed"
+                ))?;
+            }
+
+            let sensor = if let Some(sensor) = reused.remove(name) {
+                sensor
+            } else {
+                Arc::new(Sensor::new(name, sysfs, sensor_config).await?)
+            };
+            sensors.insert(name.clone(), sensor);
+        }
+
+        Ok(Self { sensors })
+    }
+
+    /// Get sensors which config wasn't changed
+    pub fn unchanged(
+        &self,
+        old: &SensorsConfig,
+        new: &SensorsConfig,
+    ) -> HashMap<String, Arc<Sensor>> {
+        self.sensors
+            .iter()
+            .filter(|(name, _)| old.sensors.get(*name) == new.sensors.get(*name))
+            .map(|(name, sensor)| (name.clone(), sensor.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn write(path: PathBuf, data: &str) {
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        fs::write(path, data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_fake_sysfs() {
+        let dir = tempfile::tempdir().unwrap();
+        let sysfs = dir.path().to_owned();
+
+        let hwmon = sysfs.join("class/hwmon/hwmon3");
+        write(hwmon.join("name"), "ina226\n").await;
+        write(hwmon.join("in1_input"), "12050\n").await;
+        write(hwmon.join("curr1_input"), "1500\n").await;
+        write(sysfs.join("class/hwmon/hwmon4/name"), "cpu_thermal\n").await;
+
+        let iio = sysfs.join("bus/iio/devices/iio:device0");
+        write(iio.join("name"), "ads1015\n").await;
+        write(iio.join("in_voltage0_raw"), "1000\n").await;
+        write(iio.join("in_voltage_scale"), "2\n").await;
+
+        let config: SensorsConfig = toml::from_str(
+            r#"
+            [vin]
+            kind = "voltage"
+            hwmon = "ina226"
+            attr = "in1_input"
+            interval = 20
+
+            [iin]
+            kind = "current"
+            hwmon = "ina226"
+            attr = "curr1_input"
+            max = 1.0
+
+            [adc]
+            kind = "voltage"
+            iio = "ads1015"
+            attr = "in_voltage0_raw"
+            min = 2.5
+            "#,
+        )
+        .unwrap();
+
+        let sensors = Sensors::with_sysfs(&config, &sysfs, HashMap::default())
+            .await
+            .unwrap();
+
+        let reading = sensors.get("vin").unwrap().reading();
+        assert_eq!(reading.value, Some(12.05));
+        assert_eq!(reading.status, SensorStatus::Ok);
+
+        let reading = sensors.get("iin").unwrap().reading();
+        assert_eq!(reading.value, Some(1.5));
+        assert_eq!(reading.status, SensorStatus::High);
+
+        let reading = sensors.get("adc").unwrap().reading();
+        assert_eq!(reading.value, Some(2.0));
+        assert_eq!(reading.status, SensorStatus::Low);
+
+        // values are read periodically
+        let mut watch = sensors.get("vin").unwrap().watch();
+        fs::remove_file(hwmon.join("in1_input")).await.unwrap();
+        watch.changed().await.unwrap();
+        assert_eq!(watch.borrow().status, SensorStatus::Error);
+
+        write(hwmon.join("in1_input"), "11000\n").await;
+        watch.changed().await.unwrap();
+        assert_eq!(watch.borrow().value, Some(11.0));
+
+        // reused when unchanged
+        let mut new_config = config.clone();
+        new_config.sensors.get_mut("iin").unwrap().max = Some(2.0);
+        let reused = sensors.unchanged(&config, &new_config);
+        assert!(reused.contains_key("vin"));
+        assert!(!reused.contains_key("iin"));
+
+        let error = SensorSource::resolve(
+            &sysfs,
+            &toml::from_str("hwmon = \"ina219\"\nattr = \"in1_input\"").unwrap(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Other error: hwmon device \"ina219\" not found"
+        );
+
+        // any name may be used for sensor
+        let config: SensorsConfig = toml::from_str("[sysfs]\npath = \"power\"").unwrap();
+        assert!(config.sensors.contains_key("sysfs"));
+    }
+}
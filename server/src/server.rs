@@ -1,10 +1,18 @@
-use crate::{log, BindAddr, Buttons, ButtonsConfig, Leds, LedsConfig, Result};
+use crate::{
+    buttons::Button, leds::Led, log, sensors::Sensor, BindAddr, ButtonId, Buttons, ButtonsConfig,
+    Error, LedId, Leds, LedsConfig, Power, PowerConfig, Result, Sensors, SensorsConfig,
+};
+use core::time::Duration;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     path::Path,
     sync::{Arc, Weak},
 };
-use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::{
+    sync::{watch, Semaphore, SemaphorePermit},
+    time,
+};
 
 #[cfg(feature = "hid")]
 use crate::{Hid, HidConfig};
@@ -12,6 +20,12 @@ use crate::{Hid, HidConfig};
 #[cfg(feature = "video")]
 use crate::{Video, VideoConfig};
 
+#[cfg(feature = "record")]
+use crate::{Recorder, RecorderConfig};
+
+#[cfg(feature = "screen")]
+use crate::{Screen, ScreenConfig};
+
 #[derive(Clone, Debug)]
 pub struct GracefulShutdown {
     semaphore: Arc<Semaphore>,
@@ -36,7 +50,7 @@ impl GracefulShutdown {
         let _ = self.semaphore.acquire_many(spawns as _).await.unwrap();
     }
 
-    pub async fn shutdowned(&self) -> SemaphorePermit {
+    pub async fn shutdowned(&self) -> SemaphorePermit<'_> {
         log::debug!("Await shutdown signal");
         let lock = self.semaphore.acquire().await.unwrap();
 
@@ -46,7 +60,7 @@ impl GracefulShutdown {
 }
 
 /// Server configuration
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 pub struct ServerConfig {
     /// Service bindings
     #[serde(default)]
@@ -60,6 +74,14 @@ pub struct ServerConfig {
     #[serde(default)]
     pub leds: LedsConfig,
 
+    /// Hwmon and IIO sensors
+    #[serde(default)]
+    pub sensors: SensorsConfig,
+
+    /// Host power state inference
+    #[serde(default)]
+    pub power: PowerConfig,
+
     /// HID devices
     #[cfg(feature = "hid")]
     #[serde(default)]
@@ -69,20 +91,103 @@ pub struct ServerConfig {
     #[cfg(feature = "video")]
     #[serde(default)]
     pub video: Option<VideoConfig>,
+
+    /// Session recording
+    #[cfg(feature = "record")]
+    #[serde(default)]
+    pub record: Option<RecorderConfig>,
+
+    /// Text screen recognition
+    #[cfg(feature = "screen")]
+    #[serde(default)]
+    pub screen: Option<ScreenConfig>,
 }
 
 impl ServerConfig {
     /// Read config from file
+    ///
+    /// Errors refer to line and column of wrong value.
     pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
         let raw: Vec<u8> = tokio::fs::read(path).await?;
         let utf = core::str::from_utf8(&raw)?;
-        let cfg = toml::from_str::<serde_json::Value>(utf)?;
-        let cfg = serde_json::from_value(cfg)?;
 
-        Ok(cfg)
+        Self::from_toml(utf)
+    }
+
+    /// Parse config from TOML
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Check config values which don't depend on system
+    pub fn validate(&self) -> Result<()> {
+        for (id, config) in &self.buttons.buttons {
+            config
+                .validate()
+                .map_err(|error| format!("Button {id}: {error}"))?;
+        }
+
+        for (id, config) in &self.leds.leds {
+            config
+                .validate()
+                .map_err(|error| format!("LED {id}: {error}"))?;
+        }
+
+        #[cfg(feature = "video")]
+        if let Some(video) = &self.video {
+            video
+                .validate()
+                .map_err(|error| format!("Video: {error}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that configs differ in bindings only
+    pub fn same_state(&self, other: &Self) -> bool {
+        let same = self.buttons == other.buttons
+            && self.leds == other.leds
+            && self.sensors == other.sensors
+            && self.power == other.power;
+
+        #[cfg(feature = "hid")]
+        let same = same && self.hid == other.hid;
+
+        #[cfg(feature = "video")]
+        let same = same && self.video == other.video;
+
+        #[cfg(feature = "record")]
+        let same = same && self.record == other.record;
+
+        #[cfg(feature = "screen")]
+        let same = same && self.screen == other.screen;
+
+        same
     }
 }
 
+/// Old instance releasing timeout
+const RETIRE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Actual server instance shared between reloads
+type CurrentState = Arc<watch::Sender<Weak<ServerState>>>;
+
+/// Parts of previous instance which can be reused
+#[derive(Clone, Default)]
+struct ServerParts {
+    buttons: HashMap<ButtonId, Arc<Button>>,
+    leds: HashMap<LedId, Arc<Led>>,
+    sensors: HashMap<String, Arc<Sensor>>,
+    #[cfg(feature = "hid")]
+    hid: Option<Arc<Hid>>,
+    #[cfg(feature = "video")]
+    video: Option<Arc<Video>>,
+    #[cfg(feature = "record")]
+    recorder: Option<Arc<Recorder>>,
+    #[cfg(feature = "screen")]
+    screen: Option<Arc<Screen>>,
+}
+
 struct ServerState {
     /// Buttons
     buttons: Buttons,
@@ -90,13 +195,45 @@ struct ServerState {
     /// LEDs
     leds: Leds,
 
+    /// Sensors
+    sensors: Sensors,
+
+    /// Host power state
+    power: Power,
+
     /// HID devices
     #[cfg(feature = "hid")]
-    hid: Option<Hid>,
+    hid: Option<Arc<Hid>>,
 
     /// Video device
     #[cfg(feature = "video")]
-    video: Option<Video>,
+    video: Option<Arc<Video>>,
+
+    /// Session recorder
+    #[cfg(feature = "record")]
+    recorder: Option<Arc<Recorder>>,
+
+    /// Text screen recognizer
+    #[cfg(feature = "screen")]
+    screen: Option<Arc<Screen>>,
+
+    /// Screenshots cache
+    #[cfg(feature = "screenshot")]
+    screenshots: crate::Screenshots,
+
+    /// Actual instance
+    current: CurrentState,
+
+    /// Closed when instance dropped
+    alive: watch::Sender<()>,
+}
+
+/// Server reloading failure
+pub enum ReloadError {
+    /// New config isn't applied but previous one still works
+    Rejected(Server, Error),
+    /// Neither new nor previous config can be applied
+    Failed(Error),
 }
 
 /// Server instance
@@ -106,54 +243,232 @@ pub struct Server {
 }
 
 /// Weak reference to server
+///
+/// Follows actual instance when server reloaded.
 #[derive(Clone)]
 pub struct ServerRef {
-    state: Weak<ServerState>,
+    state: watch::Receiver<Weak<ServerState>>,
 }
 
 impl ServerRef {
     /// Try to get server instance by weak reference
     pub fn upgrade(&self) -> Result<Server> {
         Ok(Server {
-            state: self.state.upgrade().ok_or("Seems server is out of life")?,
+            state: self
+                .state
+                .borrow()
+                .upgrade()
+                .ok_or("Seems server is out of life")?,
         })
     }
+
+    /// Get server instance waiting while it reloading
+    pub async fn acquire(&mut self) -> Result<Server> {
+        loop {
+            if let Some(state) = self.state.borrow_and_update().upgrade() {
+                return Ok(Server { state });
+            }
+            self.state
+                .changed()
+                .await
+                .map_err(|_| "Seems server is out of life")?;
+        }
+    }
+
+    /// Wait until server instance replaced
+    pub async fn replaced(&mut self) {
+        if self.state.changed().await.is_err() {
+            core::future::pending().await
+        }
+    }
 }
 
 impl Server {
     /// Instantiate server using provided config
     pub async fn new(config: &ServerConfig) -> Result<Self> {
-        let buttons = Buttons::new(&config.buttons).await?;
-        let leds = Leds::new(&config.leds).await?;
+        let current = Arc::new(watch::channel(Weak::new()).0);
+        Self::build(config, ServerParts::default(), current).await
+    }
+
+    /// Apply new config
+    ///
+    /// Subsystems which config wasn't changed are moved to new instance as is.
+    /// When new config cannot be applied previous instance keeps running or
+    /// is restored using previous config which is kept in `config`.
+    pub async fn reload(
+        self,
+        config: &mut ServerConfig,
+        new_config: ServerConfig,
+    ) -> core::result::Result<Self, ReloadError> {
+        if config.same_state(&new_config) {
+            *config = new_config;
+            return Ok(self);
+        }
+
+        if let Err(error) = new_config.validate() {
+            return Err(ReloadError::Rejected(self, error));
+        }
+
+        let parts = self.unchanged_parts(config, &new_config);
+        let current = self.state.current.clone();
+        let previous = Arc::downgrade(&self.state);
+
+        self.retire().await;
+
+        let error = match Self::build(&new_config, parts.clone(), current.clone()).await {
+            Ok(server) => {
+                *config = new_config;
+                return Ok(server);
+            }
+            Err(error) => error,
+        };
+
+        // previous instance still in use so its devices are never released
+        if let Some(state) = previous.upgrade() {
+            state.current.send_replace(Arc::downgrade(&state));
+            return Err(ReloadError::Rejected(Self { state }, error));
+        }
+
+        // unchanged parts are valid for previous config too
+        match Self::build(config, parts, current).await {
+            Ok(server) => Err(ReloadError::Rejected(server, error)),
+            Err(restore_error) => {
+                log::error!("Unable to apply config due to: {error}");
+                Err(ReloadError::Failed(restore_error))
+            }
+        }
+    }
+
+    async fn build(
+        config: &ServerConfig,
+        parts: ServerParts,
+        current: CurrentState,
+    ) -> Result<Self> {
+        let buttons = Buttons::with_reused(&config.buttons, parts.buttons).await?;
+        let leds = Leds::with_reused(&config.leds, parts.leds).await?;
+        let sensors = Sensors::with_reused(&config.sensors, parts.sensors).await?;
 
         #[cfg(feature = "hid")]
-        let hid = if let Some(hid) = &config.hid {
+        let hid = if let Some(hid) = parts.hid {
+            Some(hid)
+        } else if let Some(hid) = &config.hid {
             log::info!("Setup HID input");
-            Some(Hid::new(hid).await?)
+            Some(Arc::new(Hid::new(hid).await?))
         } else {
             log::info!("No HID input");
             None
         };
 
         #[cfg(feature = "video")]
-        let video = if let Some(video) = &config.video {
+        let video = if let Some(video) = parts.video {
+            Some(video)
+        } else if let Some(video) = &config.video {
             log::info!("Setup video capturing");
-            Some(Video::new(video).await?)
+            Some(Arc::new(Video::new(video).await?))
         } else {
             log::info!("No video capturing");
             None
         };
 
-        Ok(Self {
+        #[cfg(feature = "record")]
+        let recorder = parts.recorder.or_else(|| {
+            config.record.as_ref().map(|record| {
+                log::info!("Setup session recording");
+                Arc::new(Recorder::new(record))
+            })
+        });
+
+        #[cfg(feature = "screen")]
+        let screen = if let Some(screen) = parts.screen {
+            Some(screen)
+        } else if let Some(screen) = &config.screen {
+            log::info!("Setup screen recognition");
+            Some(Arc::new(Screen::new(screen).await?))
+        } else {
+            None
+        };
+
+        // derived from other subsystems so never reused
+        let power = Power::new(
+            &config.power,
+            &leds,
+            &sensors,
+            #[cfg(feature = "hid")]
+            hid.as_deref(),
+            #[cfg(feature = "video")]
+            video.as_deref(),
+        )?;
+
+        let server = Self {
             state: Arc::new(ServerState {
                 buttons,
                 leds,
+                sensors,
+                power,
                 #[cfg(feature = "hid")]
                 hid,
                 #[cfg(feature = "video")]
                 video,
+                #[cfg(feature = "record")]
+                recorder,
+                #[cfg(feature = "screen")]
+                screen,
+                #[cfg(feature = "screenshot")]
+                screenshots: Default::default(),
+                current,
+                alive: watch::channel(()).0,
             }),
-        })
+        };
+
+        server
+            .state
+            .current
+            .send_replace(Arc::downgrade(&server.state));
+
+        Ok(server)
+    }
+
+    /// Get subsystems which config wasn't changed
+    fn unchanged_parts(&self, old: &ServerConfig, new: &ServerConfig) -> ServerParts {
+        ServerParts {
+            buttons: self.buttons().unchanged(&old.buttons, &new.buttons),
+            leds: self.leds().unchanged(&old.leds, &new.leds),
+            sensors: self.sensors().unchanged(&old.sensors, &new.sensors),
+            #[cfg(feature = "hid")]
+            hid: self.state.hid.clone().filter(|_| old.hid == new.hid),
+            #[cfg(feature = "video")]
+            video: self.state.video.clone().filter(|_| old.video == new.video),
+            #[cfg(feature = "record")]
+            recorder: self
+                .state
+                .recorder
+                .clone()
+                .filter(|_| old.record == new.record),
+            #[cfg(feature = "screen")]
+            screen: self
+                .state
+                .screen
+                .clone()
+                .filter(|_| old.screen == new.screen),
+        }
+    }
+
+    /// Release instance
+    ///
+    /// References are notified to drop instance, then it waits until
+    /// instance will be dropped so devices can be opened again.
+    async fn retire(self) {
+        let mut alive = self.state.alive.subscribe();
+
+        self.state.current.send_replace(Weak::new());
+        drop(self);
+
+        if time::timeout(RETIRE_TIMEOUT, alive.changed())
+            .await
+            .is_err()
+        {
+            log::warn!("Previous server instance still in use");
+        }
     }
 
     pub async fn spawn(
@@ -181,7 +496,7 @@ impl Server {
     /// Get weak ref to server
     pub fn downgrade(&self) -> ServerRef {
         ServerRef {
-            state: Arc::downgrade(&self.state),
+            state: self.state.current.subscribe(),
         }
     }
 
@@ -195,15 +510,194 @@ impl Server {
         &self.state.buttons
     }
 
+    /// Get sensors
+    pub fn sensors(&self) -> &Sensors {
+        &self.state.sensors
+    }
+
+    /// Get host power state tracker
+    pub fn power(&self) -> &Power {
+        &self.state.power
+    }
+
     /// Get HID devices
     #[cfg(feature = "hid")]
     pub fn hid(&self) -> Option<&Hid> {
-        self.state.hid.as_ref()
+        self.state.hid.as_deref()
     }
 
     /// Get video device
     #[cfg(feature = "video")]
     pub fn video(&self) -> Option<&Video> {
-        self.state.video.as_ref()
+        self.state.video.as_deref()
+    }
+
+    /// Get session recorder
+    #[cfg(feature = "record")]
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.state.recorder.as_deref()
+    }
+
+    /// Get text screen recognizer
+    #[cfg(feature = "screen")]
+    pub fn screen(&self) -> Option<&Screen> {
+        self.state.screen.as_deref()
+    }
+
+    /// Get screenshots cache
+    #[cfg(feature = "screenshot")]
+    pub fn screenshots(&self) -> &crate::Screenshots {
+        &self.state.screenshots
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_sample_config() {
+        let config = ServerConfig::from_toml(include_str!("../ukvm.toml")).unwrap();
+        assert_eq!(config.binds.len(), 2);
+        assert_eq!(config.buttons.buttons.len(), 2);
+    }
+
+    #[test]
+    fn config_error_position() {
+        let error = ServerConfig::from_toml("[buttons.power]\nchip = \"gpiochip0\"\nline = true\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 3"), "{error}");
+    }
+
+    async fn reload(server: Server, config: &mut ServerConfig, new_config: ServerConfig) -> Server {
+        match server.reload(config, new_config).await {
+            Ok(server) => server,
+            Err(ReloadError::Rejected(_, error) | ReloadError::Failed(error)) => panic!("{error}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_config() {
+        let mut config = ServerConfig::default();
+        let server = Server::new(&config).await.unwrap();
+        let mut server_ref = server.downgrade();
+
+        // nothing changed
+        let same_config = config.clone();
+        let server = reload(server, &mut config, same_config).await;
+        assert!(Arc::ptr_eq(
+            &server.state,
+            &server_ref.acquire().await.unwrap().state
+        ));
+
+        #[cfg(feature = "record")]
+        {
+            let new_config = ServerConfig {
+                record: Some(Default::default()),
+                ..config.clone()
+            };
+            assert!(!config.same_state(&new_config));
+
+            let server = reload(server, &mut config, new_config).await;
+
+            assert!(config.record.is_some());
+            assert!(server.recorder().is_some());
+            assert!(server_ref.acquire().await.unwrap().recorder().is_some());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reload_while_held() {
+        let mut config = ServerConfig::default();
+        let server = Server::new(&config).await.unwrap();
+        let mut server_ref = server.downgrade();
+
+        // like request in progress
+        let held = server.clone();
+
+        let new_config = ServerConfig {
+            power: PowerConfig {
+                boot_timeout: 0,
+                ..Default::default()
+            },
+            ..config.clone()
+        };
+        assert!(!config.same_state(&new_config));
+
+        let server = reload(server, &mut config, new_config.clone()).await;
+
+        assert_eq!(config, new_config);
+        assert!(!Arc::ptr_eq(&server.state, &held.state));
+        assert!(Arc::ptr_eq(
+            &server.state,
+            &server_ref.acquire().await.unwrap().state
+        ));
+    }
+
+    #[tokio::test]
+    async fn reload_invalid_config() {
+        let mut config = ServerConfig::default();
+        let server = Server::new(&config).await.unwrap();
+        let held = server.clone();
+
+        // button without output
+        let new_config = ServerConfig::from_toml("[buttons.power]\n").unwrap();
+
+        let Err(ReloadError::Rejected(server, error)) =
+            server.reload(&mut config, new_config).await
+        else {
+            panic!("Invalid config applied");
+        };
+
+        assert!(error.to_string().contains("Button"), "{error}");
+        assert_eq!(config, ServerConfig::default());
+        assert!(Arc::ptr_eq(&server.state, &held.state));
+    }
+
+    #[tokio::test]
+    async fn reload_failed_restore() {
+        let mut config = ServerConfig::default();
+        let server = Server::new(&config).await.unwrap();
+        let mut server_ref = server.downgrade();
+
+        // passes validation but fails when built
+        let new_config = ServerConfig::from_toml("[sensors.bad-name]\n").unwrap();
+
+        let Err(ReloadError::Rejected(server, _)) = server.reload(&mut config, new_config).await
+        else {
+            panic!("Invalid config applied");
+        };
+
+        assert_eq!(config, ServerConfig::default());
+        assert!(Arc::ptr_eq(
+            &server.state,
+            &server_ref.acquire().await.unwrap().state
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reload_failed_while_held() {
+        let mut config = ServerConfig::default();
+        let server = Server::new(&config).await.unwrap();
+        let mut server_ref = server.downgrade();
+
+        // like request in progress
+        let held = server.clone();
+
+        let new_config = ServerConfig::from_toml("[sensors.bad-name]\n").unwrap();
+
+        let Err(ReloadError::Rejected(server, _)) = server.reload(&mut config, new_config).await
+        else {
+            panic!("Invalid config applied");
+        };
+
+        // previous instance keeps running
+        assert_eq!(config, ServerConfig::default());
+        assert!(Arc::ptr_eq(&server.state, &held.state));
+        assert!(Arc::ptr_eq(
+            &server.state,
+            &server_ref.acquire().await.unwrap().state
+        ));
     }
 }
@@ -0,0 +1,171 @@
+use crate::{log, Result};
+use std::path::{Path, PathBuf};
+use tokio::{fs, select, spawn, sync::watch, time};
+
+pub use ukvm_core::hid::UsbState;
+
+/// USB device controllers class directory
+const UDC_CLASS: &str = "/sys/class/udc";
+
+/// State polling interval
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(500);
+
+/// USB device controller state watcher
+pub struct Udc {
+    name: String,
+    waker: UdcWaker,
+    state_receiver: watch::Receiver<UsbState>,
+}
+
+/// Remote wakeup trigger of USB device controller
+#[derive(Clone, Debug)]
+pub struct UdcWaker {
+    path: PathBuf,
+}
+
+impl UdcWaker {
+    /// Signal remote wakeup to suspended host
+    pub async fn wake(&self) -> Result<()> {
+        log::debug!("Signal remote wakeup");
+        fs::write(self.path.join("srp"), "1")
+            .await
+            .map_err(|error| format!("Unable to wake host up: {error}"))?;
+        Ok(())
+    }
+}
+
+impl Udc {
+    /// Find first available USB device controller
+    pub async fn find() -> Result<String> {
+        let mut entries = fs::read_dir(UDC_CLASS).await?;
+
+        if let Some(entry) = entries.next_entry().await? {
+            Ok(entry.file_name().to_string_lossy().into_owned())
+        } else {
+            Err("No USB device controller found")?
+        }
+    }
+
+    /// Start watching state of USB device controller
+    pub async fn new(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        let path = Path::new(UDC_CLASS).join(&name);
+        Self::with_path(name, path).await
+    }
+
+    /// Start watching state using controller sysfs directory
+    pub async fn with_path(name: String, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let waker = UdcWaker { path: path.clone() };
+        let path = path.join("state");
+
+        let state = UsbState::from_sysfs(&fs::read_to_string(&path).await?);
+
+        let (state_sender, state_receiver) = watch::channel(state);
+
+        spawn({
+            let name = name.clone();
+            async move {
+                log::debug!("{name}: Initialize watching state");
+
+                loop {
+                    select! {
+                        // Watcher dropped
+                        _ = state_sender.closed() => break,
+                        _ = time::sleep(POLL_INTERVAL) => match fs::read_to_string(&path).await {
+                            Ok(state) => {
+                                let state = UsbState::from_sysfs(&state);
+                                state_sender.send_if_modified(|old_state| {
+                                    if *old_state != state {
+                                        log::info!("{name}: USB state changed to {state}");
+                                        *old_state = state;
+                                        true
+                                    } else {
+                                        false
+                                    }
+                                });
+                            }
+                            Err(error) => {
+                                log::error!("{name}: Error when reading state: {error}");
+                                break;
+                            }
+                        },
+                    }
+                }
+
+                log::debug!("{name}: Finalize watching state");
+            }
+        });
+
+        Ok(Self {
+            name,
+            waker,
+            state_receiver,
+        })
+    }
+
+    /// Get remote wakeup trigger
+    pub fn waker(&self) -> UdcWaker {
+        self.waker.clone()
+    }
+
+    /// Wake host up when suspended
+    pub async fn wake_host(&self) -> Result<()> {
+        if self.state() == UsbState::Suspended {
+            self.waker.wake().await
+        } else {
+            log::debug!("{}: Host isn't suspended", self.name);
+            Ok(())
+        }
+    }
+
+    /// USB device controller name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get current state
+    pub fn state(&self) -> UsbState {
+        *self.state_receiver.borrow()
+    }
+
+    /// Watch state changes
+    pub fn watch(&self) -> watch::Receiver<UsbState> {
+        self.state_receiver.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn udc_state_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("state"), "not attached\n").unwrap();
+
+        let udc = Udc::with_path("dummy_udc.0".into(), root).await.unwrap();
+        assert_eq!(udc.state(), UsbState::NotAttached);
+
+        let mut state = udc.watch();
+        std::fs::write(root.join("state"), "configured\n").unwrap();
+        time::timeout(time::Duration::from_secs(2), state.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(udc.state(), UsbState::Configured);
+        assert!(udc.state().is_configured());
+
+        udc.wake_host().await.unwrap();
+        assert!(!root.join("srp").exists());
+
+        std::fs::write(root.join("state"), "suspended\n").unwrap();
+        time::timeout(time::Duration::from_secs(2), state.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        udc.wake_host().await.unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("srp")).unwrap(), "1");
+    }
+}
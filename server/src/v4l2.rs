@@ -0,0 +1,140 @@
+use crate::Result;
+use nix::{errno::Errno, ioctl_read, ioctl_readwrite, ioctl_write_ptr, libc};
+use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use tokio::io::{unix::AsyncFd, Interest};
+
+/// Source change event type
+const EVENT_SOURCE_CHANGE: u32 = 5;
+
+/// Source resolution changed flag
+const EVENT_SRC_CH_RESOLUTION: u32 = 1;
+
+#[repr(C)]
+struct EventSubscription {
+    type_: u32,
+    id: u32,
+    flags: u32,
+    reserved: [u32; 5],
+}
+
+#[repr(C)]
+struct Event {
+    type_: u32,
+    // union with 64-bit members
+    u: [u64; 8],
+    pending: u32,
+    sequence: u32,
+    timestamp: libc::timespec,
+    id: u32,
+    reserved: [u32; 8],
+}
+
+/// BT.656/BT.1120 timings
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct BtTimings {
+    pub width: u32,
+    pub height: u32,
+    pub interlaced: u32,
+    pub polarities: u32,
+    pub pixel_clock: u64,
+    porches: [u32; 9],
+    standards: u32,
+    flags: u32,
+    picture_aspect: [u32; 2],
+    cea861_vic: u8,
+    hdmi_vic: u8,
+    reserved: [u8; 46],
+}
+
+/// Digital video timings
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct DvTimings {
+    type_: u32,
+    pub bt: BtTimings,
+    reserved: [u32; 1],
+}
+
+ioctl_read!(dq_event, b'V', 89, Event);
+ioctl_write_ptr!(subscribe_event, b'V', 90, EventSubscription);
+ioctl_readwrite!(s_dv_timings, b'V', 87, DvTimings);
+ioctl_read!(query_dv_timings, b'V', 99, DvTimings);
+
+/// Query timings of detected source
+pub fn query_timings(fd: RawFd) -> nix::Result<DvTimings> {
+    let mut timings = unsafe { core::mem::zeroed::<DvTimings>() };
+    unsafe { query_dv_timings(fd, &mut timings) }?;
+    Ok(timings)
+}
+
+/// Apply timings to receiver
+pub fn set_timings(fd: RawFd, timings: &DvTimings) -> nix::Result<()> {
+    let mut timings = *timings;
+    unsafe { s_dv_timings(fd, &mut timings) }?;
+    Ok(())
+}
+
+/// Source change events
+pub struct SourceEvents {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl SourceEvents {
+    /// Subscribe to source change events
+    pub fn subscribe(fd: RawFd) -> Result<Self> {
+        let subscription = EventSubscription {
+            type_: EVENT_SOURCE_CHANGE,
+            id: 0,
+            flags: 0,
+            reserved: [0; 5],
+        };
+
+        unsafe { subscribe_event(fd, &subscription) }
+            .map_err(|error| format!("Unable to subscribe to source change events: {error}"))?;
+
+        // events are bound to open file so duplicated descriptor is fine
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+
+        Ok(Self {
+            fd: AsyncFd::with_interest(fd, Interest::PRIORITY)?,
+        })
+    }
+
+    /// Wait for next source change
+    ///
+    /// Returns `true` when source resolution changed.
+    pub async fn next(&self) -> Result<bool> {
+        loop {
+            let mut guard = self.fd.ready(Interest::PRIORITY).await?;
+
+            let mut event = unsafe { core::mem::zeroed::<Event>() };
+
+            match unsafe { dq_event(self.fd.as_raw_fd(), &mut event) } {
+                Ok(_) if event.type_ == EVENT_SOURCE_CHANGE => {
+                    // changes flags is a first member of union
+                    let bytes = event.u[0].to_ne_bytes();
+                    let changes = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    return Ok(changes & EVENT_SRC_CH_RESOLUTION != 0);
+                }
+                Ok(_) => {}
+                Err(Errno::ENOENT | Errno::EAGAIN) => guard.clear_ready(),
+                Err(error) => Err(format!("Error when dequeuing event: {error}"))?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn struct_layouts() {
+        assert_eq!(core::mem::size_of::<BtTimings>(), 124);
+        assert_eq!(core::mem::size_of::<DvTimings>(), 132);
+        assert_eq!(core::mem::size_of::<EventSubscription>(), 32);
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(core::mem::size_of::<Event>(), 136);
+    }
+}
@@ -1,10 +1,27 @@
-use crate::{log, Result};
+use crate::{
+    jpeg::{self, RawFormat, RawGeometry},
+    log,
+    v4l2::{self, SourceEvents},
+    Result,
+};
+use core::future::pending;
 use linux_video::{types::*, Device, Stream};
+use nix::errno::Errno;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::{spawn, sync::watch, time};
+use std::{
+    hash::{DefaultHasher, Hasher},
+    os::fd::{AsRawFd, RawFd},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{select, spawn, sync::watch, task::spawn_blocking, time};
 
-#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub use ukvm_core::video::{VideoHealth, VideoMode, VideoSignal};
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct VideoConfig {
     /// Device (video0)
     pub device: String,
@@ -16,6 +33,93 @@ pub struct VideoConfig {
     /// Default height
     #[serde(default = "default_height")]
     pub height: u32,
+
+    /// Default frame rate (0 means driver default)
+    #[serde(default = "default_fps")]
+    pub fps: u32,
+
+    /// Suppress frames identical to previous one
+    #[serde(default = "default_true")]
+    pub skip_unchanged: bool,
+
+    /// JPEG quality for encoding raw frames (1-100)
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+
+    /// Maximum interval between frames in milliseconds when content is unchanged
+    #[serde(default = "default_keyframe_interval")]
+    pub keyframe_interval: u32,
+
+    /// Health monitoring thresholds
+    #[serde(default)]
+    pub health: VideoHealthConfig,
+
+    /// WebRTC streaming options
+    #[cfg(feature = "webrtc")]
+    #[serde(default)]
+    pub webrtc: crate::WebRtcConfig,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            device: String::default(),
+            width: default_width(),
+            height: default_height(),
+            fps: default_fps(),
+            skip_unchanged: default_true(),
+            quality: default_quality(),
+            keyframe_interval: default_keyframe_interval(),
+            health: VideoHealthConfig::default(),
+            #[cfg(feature = "webrtc")]
+            webrtc: crate::WebRtcConfig::default(),
+        }
+    }
+}
+
+impl VideoConfig {
+    /// Check option values
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(1..=100).contains(&self.quality) {
+            return Err(format!("JPEG quality {} out of range 1-100", self.quality))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Video health monitoring thresholds
+///
+/// Capturing is kept running while any threshold is set.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Default)]
+pub struct VideoHealthConfig {
+    /// Report stalled video when no frames received for given milliseconds (0 disables)
+    #[serde(default)]
+    pub stall_timeout: u32,
+
+    /// Report frozen screen when content unchanged for given milliseconds (0 disables)
+    #[serde(default)]
+    pub frozen_timeout: u32,
+}
+
+impl VideoHealthConfig {
+    /// Any threshold is set
+    pub fn is_enabled(&self) -> bool {
+        self.stall_timeout > 0 || self.frozen_timeout > 0
+    }
+}
+
+/// Video health report
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct VideoHealthInfo {
+    /// Input signal state
+    pub signal: VideoSignal,
+    /// Health state
+    pub health: VideoHealth,
+    /// Milliseconds since last captured frame
+    pub last_frame_age: Option<u64>,
+    /// Milliseconds since last frame content change
+    pub frozen_for: Option<u64>,
 }
 
 fn default_width() -> u32 {
@@ -26,12 +130,255 @@ fn default_height() -> u32 {
     1080
 }
 
+fn default_fps() -> u32 {
+    30
+}
+
+fn default_quality() -> u8 {
+    80
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_keyframe_interval() -> u32 {
+    5000
+}
+
+/// Every Nth row of raw frame is hashed to detect changes
+const HASH_ROW_STEP: usize = 4;
+
+/// Frame content change detector
+struct ChangeDetector {
+    skip_unchanged: bool,
+    keyframe_interval: Duration,
+    last_hash: Option<u64>,
+    last_sent: Option<Instant>,
+    last_change: Option<Instant>,
+}
+
+impl ChangeDetector {
+    fn new(config: &VideoConfig) -> Self {
+        Self {
+            skip_unchanged: config.skip_unchanged,
+            keyframe_interval: Duration::from_millis(config.keyframe_interval as _),
+            last_hash: None,
+            last_sent: None,
+            last_change: None,
+        }
+    }
+
+    /// Reset state when stream restarted
+    ///
+    /// Content hash is kept to track changes across restarts.
+    fn reset(&mut self) {
+        self.last_sent = None;
+    }
+
+    /// Check that frame should be sent
+    ///
+    /// Raw frames are sampled by rows to keep hashing cheap.
+    fn check(&mut self, data: &[u8], stride: Option<usize>, now: Instant) -> bool {
+        let mut hasher = DefaultHasher::new();
+        match stride {
+            Some(stride) if stride > 0 => {
+                hasher.write_usize(data.len());
+                for row in data.chunks(stride).step_by(HASH_ROW_STEP) {
+                    hasher.write(row);
+                }
+            }
+            // compressed data changes as a whole
+            _ => hasher.write(data),
+        }
+        let hash = hasher.finish();
+
+        let changed = self.last_hash != Some(hash);
+        if changed {
+            self.last_hash = Some(hash);
+            self.last_change = Some(now);
+        }
+
+        if !self.skip_unchanged {
+            return true;
+        }
+
+        let expired = self
+            .last_sent
+            .map(|last_sent| now.duration_since(last_sent) >= self.keyframe_interval)
+            .unwrap_or(true);
+
+        if changed || expired {
+            self.last_sent = Some(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Capture times of frames
+#[derive(Clone, Copy, Default)]
+struct FrameTimes {
+    /// Last captured frame
+    frame: Option<Instant>,
+    /// Last content change
+    change: Option<Instant>,
+}
+
+/// Health checking interval
+const HEALTH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Evaluate health state using frame times
+///
+/// Missing times are counted from start of monitoring.
+fn check_health(
+    config: &VideoHealthConfig,
+    times: FrameTimes,
+    started: Instant,
+    now: Instant,
+) -> VideoHealth {
+    let exceeds = |time: Option<Instant>, timeout: u32| {
+        timeout > 0
+            && now.duration_since(time.unwrap_or(started)) >= Duration::from_millis(timeout as _)
+    };
+
+    if exceeds(times.frame, config.stall_timeout) {
+        VideoHealth::Stalled
+    } else if exceeds(times.change, config.frozen_timeout) {
+        VideoHealth::Frozen
+    } else {
+        VideoHealth::Ok
+    }
+}
+
+/// DV timings polling interval when source change events unsupported
+const SOURCE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Source state reported by DV timings query
+type SourceState = core::result::Result<(u32, u32, u64), Errno>;
+
+/// Source change tracking
+enum SourceTracker {
+    /// Driver notifies about changes
+    Events(SourceEvents),
+    /// DV timings are queried periodically
+    Poll {
+        fd: RawFd,
+        interval: time::Interval,
+        last: SourceState,
+    },
+    /// Changes cannot be detected
+    None,
+}
+
+impl SourceTracker {
+    fn new(fd: RawFd) -> Self {
+        let error = match SourceEvents::subscribe(fd) {
+            Ok(events) => return Self::Events(events),
+            Err(error) => error,
+        };
+
+        match Self::query(fd) {
+            // DV timings unsupported
+            Err(Errno::ENOTTY | Errno::ENODATA | Errno::EINVAL) => {
+                log::debug!("Source change tracking unavailable: {error}");
+                Self::None
+            }
+            last => {
+                log::debug!("Source change events unavailable, polling DV timings: {error}");
+                Self::Poll {
+                    fd,
+                    interval: time::interval(SOURCE_POLL_INTERVAL),
+                    last,
+                }
+            }
+        }
+    }
+
+    fn query(fd: RawFd) -> SourceState {
+        v4l2::query_timings(fd).map(|timings| {
+            let bt = timings.bt;
+            (bt.width, bt.height, bt.pixel_clock)
+        })
+    }
+
+    /// Wait for next source change
+    ///
+    /// Returns `true` when source resolution changed.
+    async fn changed(&mut self) -> Result<bool> {
+        match self {
+            Self::Events(events) => events.next().await,
+            Self::Poll { fd, interval, last } => loop {
+                interval.tick().await;
+                let state = Self::query(*fd);
+                if state != *last {
+                    *last = state;
+                    return Ok(true);
+                }
+            },
+            Self::None => pending().await,
+        }
+    }
+}
+
+/// Snapshot waiting timeout
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Mode changing timeout
+const MODE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Find supported frame size closest to requested
+///
+/// Exact match wins, otherwise the nearest size is chosen preferring larger one.
+fn best_size(sizes: &[(u32, u32)], width: u32, height: u32) -> Option<(u32, u32)> {
+    sizes.iter().copied().min_by_key(|&(w, h)| {
+        (
+            w.abs_diff(width) + h.abs_diff(height),
+            u64::MAX - w as u64 * h as u64,
+        )
+    })
+}
+
+/// Find supported frame interval closest to requested frame rate
+fn best_interval(intervals: &[Fract], fps: u32) -> Option<Fract> {
+    intervals
+        .iter()
+        .copied()
+        .filter(|interval| interval.numerator() > 0)
+        .min_by(|a, b| {
+            let a = (fract_fps(*a) - fps as f64).abs();
+            let b = (fract_fps(*b) - fps as f64).abs();
+            a.total_cmp(&b)
+        })
+}
+
+/// Convert frame interval to frame rate
+fn fract_fps(interval: Fract) -> f64 {
+    interval.denominator() as f64 / interval.numerator() as f64
+}
+
+/// Make frame interval from frame rate
+fn fps_fract(fps: u32) -> Fract {
+    let mut interval = Fract::default();
+    interval.set_numerator(1).set_denominator(fps);
+    interval
+}
+
 pub type VideoFrame = Arc<Vec<u8>>;
 
 pub type VideoSource = watch::Receiver<VideoFrame>;
 
 pub struct Video {
     frame_receiver: VideoSource,
+    capturing: Arc<AtomicBool>,
+    mode_receiver: watch::Receiver<VideoMode>,
+    signal_receiver: watch::Receiver<VideoSignal>,
+    request_sender: watch::Sender<VideoMode>,
+    times: Arc<Mutex<FrameTimes>>,
+    health_receiver: watch::Receiver<VideoHealth>,
+    #[cfg(feature = "webrtc")]
+    webrtc: crate::WebRtcConfig,
 }
 
 impl Video {
@@ -40,77 +387,461 @@ impl Video {
         self.frame_receiver.clone()
     }
 
+    /// Get actual frame
+    ///
+    /// Waits for the next frame when capturing isn't running.
+    pub async fn snapshot(&self) -> Result<VideoFrame> {
+        let mut frames = self.frames();
+
+        if !self.capturing.load(Ordering::Relaxed) || frames.borrow().is_empty() {
+            frames.mark_unchanged();
+            time::timeout(SNAPSHOT_TIMEOUT, frames.changed())
+                .await
+                .map_err(|_| "Timeout when capturing video")?
+                .map_err(|_| "Video capturing stopped")?;
+        }
+
+        let frame = frames.borrow().clone();
+        Ok(frame)
+    }
+
+    /// Get negotiated capture mode
+    pub fn mode(&self) -> VideoMode {
+        *self.mode_receiver.borrow()
+    }
+
+    /// Watch capture mode changes
+    pub fn watch_mode(&self) -> watch::Receiver<VideoMode> {
+        self.mode_receiver.clone()
+    }
+
+    /// Change capture mode
+    ///
+    /// Capturing will be restarted using new mode.
+    /// Returns actually negotiated mode which may differ from requested.
+    pub async fn set_mode(&self, mode: VideoMode) -> Result<VideoMode> {
+        if mode.width == 0 || mode.height == 0 {
+            Err(format!("Invalid video mode: {mode}"))?;
+        }
+
+        let mut modes = self.watch_mode();
+        modes.mark_unchanged();
+
+        self.request_sender.send_replace(mode);
+
+        time::timeout(MODE_TIMEOUT, modes.changed())
+            .await
+            .map_err(|_| "Timeout when changing video mode")?
+            .map_err(|_| "Video capturing stopped")?;
+
+        let mode = *modes.borrow();
+        Ok(mode)
+    }
+
+    /// Get input signal state
+    pub fn signal(&self) -> VideoSignal {
+        *self.signal_receiver.borrow()
+    }
+
+    /// Watch input signal state changes
+    pub fn watch_signal(&self) -> watch::Receiver<VideoSignal> {
+        self.signal_receiver.clone()
+    }
+
+    /// Time since last captured frame
+    pub fn last_frame_age(&self) -> Option<Duration> {
+        let frame = self.times.lock().unwrap().frame;
+        frame.map(|time| time.elapsed())
+    }
+
+    /// Time since last frame content change
+    pub fn frozen_for(&self) -> Option<Duration> {
+        let change = self.times.lock().unwrap().change;
+        change.map(|time| time.elapsed())
+    }
+
+    /// Get health state
+    pub fn health(&self) -> VideoHealth {
+        *self.health_receiver.borrow()
+    }
+
+    /// Watch health state changes
+    pub fn watch_health(&self) -> watch::Receiver<VideoHealth> {
+        self.health_receiver.clone()
+    }
+
+    /// Get health report
+    pub fn health_info(&self) -> VideoHealthInfo {
+        VideoHealthInfo {
+            signal: self.signal(),
+            health: self.health(),
+            last_frame_age: self.last_frame_age().map(|age| age.as_millis() as _),
+            frozen_for: self.frozen_for().map(|age| age.as_millis() as _),
+        }
+    }
+
+    /// Start streaming to WebRTC peer
+    ///
+    /// Returns peer and session answer.
+    #[cfg(feature = "webrtc")]
+    pub async fn webrtc(&self, offer: String) -> Result<(crate::WebRtcPeer, String)> {
+        crate::WebRtcPeer::accept(&self.webrtc, self.frames(), self.mode().fps, offer).await
+    }
+
     /// Create video input from config
     pub async fn new(config: &VideoConfig) -> Result<Self> {
+        config.validate()?;
+
         let (frame_sender, frame_receiver) = watch::channel(Arc::new(Vec::default()));
 
-        let device = Self::prepare(config).await?;
+        let (device, pixel_format) = Self::open(config).await?;
+
+        let mut source = SourceTracker::new(device.as_raw_fd());
+
+        let requested = VideoMode {
+            width: config.width,
+            height: config.height,
+            fps: config.fps,
+        };
+
+        let (signal, configured) = Self::configure(&device, pixel_format, requested).await?;
+
+        let (mode, mut raw) = configured.unwrap_or((requested, None));
+
+        if raw.is_some() {
+            log::info!("Encode frames to JPEG with quality {}", config.quality);
+        }
+
+        let (mode_sender, mode_receiver) = watch::channel(mode);
+        let (signal_sender, signal_receiver) = watch::channel(signal);
+        let (request_sender, mut request_receiver) = watch::channel(requested);
+
+        let quality = config.quality;
+
+        let mut detector = ChangeDetector::new(config);
+
+        let capturing = Arc::new(AtomicBool::new(false));
+
+        let times = Arc::new(Mutex::new(FrameTimes::default()));
+
+        let (health_sender, health_receiver) = watch::channel(VideoHealth::Ok);
 
-        spawn(async move {
-            log::info!("Initialize capturing video");
+        if config.health.is_enabled() {
+            spawn(Self::monitor_health(
+                config.health.clone(),
+                times.clone(),
+                health_sender,
+                frame_receiver.clone(),
+            ));
+        }
+
+        spawn({
+            let capturing = capturing.clone();
+            let times = times.clone();
+            async move {
+                log::info!("Initialize capturing video");
 
-            let mut stream: Option<Stream<In, Mmap>> = None;
+                let mut stream: Option<Stream<In, Mmap>> = None;
 
-            while !frame_sender.is_closed() {
-                if stream.is_some() {
-                    // video stream started
-                    match stream.as_ref().unwrap().next().await {
-                        Ok(buffer) => {
-                            let buffer = buffer.lock();
-                            let data: &[u8] = buffer.as_ref();
-                            if data.len() > 4 {
-                                let _ = frame_sender.send(Arc::new(data.to_vec()));
+                while !frame_sender.is_closed() {
+                    capturing.store(stream.is_some(), Ordering::Relaxed);
+                    let restart = if let Some(stm) = &stream {
+                        // video stream started
+                        let frame = select! {
+                            result = stm.next() => Some(result),
+                            result = request_receiver.changed() => {
+                                if result.is_err() {
+                                    break;
+                                }
+                                None
+                            }
+                            result = source.changed() => {
+                                if let Err(error) = result {
+                                    log::error!("{error}");
+                                }
+                                None
+                            }
+                        };
+                        let restart = frame.is_none();
+                        match frame {
+                            Some(Ok(buffer)) => {
+                                let data = {
+                                    let buffer = buffer.lock();
+                                    let data: &[u8] = buffer.as_ref();
+                                    if data.len() > 4 {
+                                        let now = Instant::now();
+                                        let stride = raw.map(|raw| raw.stride as usize);
+                                        let send = detector.check(data, stride, now);
+                                        *times.lock().unwrap() = FrameTimes {
+                                            frame: Some(now),
+                                            change: detector.last_change,
+                                        };
+                                        send.then(|| data.to_vec())
+                                    } else {
+                                        None
+                                    }
+                                };
+                                if let Some(data) = data {
+                                    if let Some(raw) = raw {
+                                        // encode raw frame outside of runtime
+                                        match spawn_blocking(move || {
+                                            jpeg::encode(raw, &data, quality)
+                                        })
+                                        .await
+                                        {
+                                            Ok(Ok(frame)) => {
+                                                let _ = frame_sender.send(Arc::new(frame));
+                                            }
+                                            Ok(Err(error)) => {
+                                                log::warn!("Error when encoding frame: {error}");
+                                            }
+                                            Err(error) => {
+                                                log::error!("Encoding task failed: {error}");
+                                            }
+                                        }
+                                    } else {
+                                        let _ = frame_sender.send(Arc::new(data));
+                                    }
+                                }
                             }
+                            Some(Err(error)) => {
+                                // Stop streaming on error
+                                log::error!("Error when capturing video: {error}");
+                                stream = None;
+                            }
+                            None => {}
                         }
-                        Err(error) => {
-                            // Stop streaming on error
-                            log::error!("Error when capturing video: {error}");
+                        if frame_sender.receiver_count() < 2 {
+                            // Stop streaming when no sinks
+                            log::info!("Stop capturing video");
                             stream = None;
                         }
-                    }
-                    if frame_sender.receiver_count() < 2 {
-                        // Stop streaming when no sinks
-                        log::info!("Stop capturing video");
+                        restart
+                    } else {
+                        // video stream stopped
+                        if frame_sender.receiver_count() > 1 && signal_sender.borrow().is_present()
+                        {
+                            // receiver attached, start streaming
+                            match device.stream::<In, Mmap>(ContentType::Video, 5) {
+                                Ok(stm) => {
+                                    log::info!("Start capturing video");
+                                    detector.reset();
+                                    stream = Some(stm);
+                                }
+                                Err(error) => {
+                                    log::error!("Unable to capture video due to: {error}")
+                                }
+                            }
+                            false
+                        } else {
+                            select! {
+                                _ = time::sleep(time::Duration::from_millis(500)) => false,
+                                result = request_receiver.changed() => {
+                                    if result.is_err() {
+                                        break;
+                                    }
+                                    true
+                                }
+                                result = source.changed() => {
+                                    if let Err(error) = result {
+                                        log::error!("{error}");
+                                    }
+                                    true
+                                }
+                            }
+                        }
+                    };
+
+                    if restart {
+                        // format cannot be changed while streaming
                         stream = None;
-                    }
-                } else {
-                    // video stream stopped
-                    if frame_sender.receiver_count() > 1 {
-                        // receiver attached, start streaming
-                        match device.stream::<In, Mmap>(ContentType::Video, 5) {
-                            Ok(stm) => {
-                                log::info!("Start capturing video");
-                                stream = Some(stm);
+
+                        let requested = *request_receiver.borrow_and_update();
+                        let current = *mode_sender.borrow();
+
+                        log::info!("Reconfigure video capturing for {requested}");
+
+                        let result = match Self::configure(&device, pixel_format, requested).await {
+                            Ok(result) => Ok(result),
+                            Err(error) => {
+                                log::error!("Unable to change video mode due to: {error}");
+                                // try to restore previous mode
+                                Self::configure(&device, pixel_format, current).await
+                            }
+                        };
+
+                        match result {
+                            Ok((signal, configured)) => {
+                                signal_sender.send_if_modified(|old_signal| {
+                                    if *old_signal != signal {
+                                        log::info!("Video signal changed to {signal}");
+                                        *old_signal = signal;
+                                        true
+                                    } else {
+                                        false
+                                    }
+                                });
+                                if let Some((mode, new_raw)) = configured {
+                                    raw = new_raw;
+                                    mode_sender.send_replace(mode);
+                                } else {
+                                    // notify waiters anyway
+                                    mode_sender.send_replace(current);
+                                }
+                            }
+                            Err(error) => {
+                                log::error!("Unable to restore video mode due to: {error}");
+                                break;
                             }
-                            Err(error) => log::error!("Unable to capture video due to: {error}"),
                         }
-                    } else {
-                        time::sleep(time::Duration::from_millis(500)).await
                     }
                 }
+                log::info!("Finalize capturing video");
             }
-            log::info!("Finalize capturing video");
         });
 
-        Ok(Self { frame_receiver })
+        Ok(Self {
+            frame_receiver,
+            capturing,
+            mode_receiver,
+            signal_receiver,
+            request_sender,
+            times,
+            health_receiver,
+            #[cfg(feature = "webrtc")]
+            webrtc: config.webrtc.clone(),
+        })
+    }
+
+    /// Check frame times against thresholds
+    ///
+    /// Holds frame receiver to keep capturing running.
+    async fn monitor_health(
+        config: VideoHealthConfig,
+        times: Arc<Mutex<FrameTimes>>,
+        health_sender: watch::Sender<VideoHealth>,
+        _frames: VideoSource,
+    ) {
+        let started = Instant::now();
+        let mut interval = time::interval(HEALTH_INTERVAL);
+
+        loop {
+            select! {
+                _ = interval.tick() => {}
+                _ = health_sender.closed() => break,
+            }
+
+            let times = *times.lock().unwrap();
+            let health = check_health(&config, times, started, Instant::now());
+
+            health_sender.send_if_modified(|old_health| {
+                if *old_health != health {
+                    log::info!("Video health changed to {health}");
+                    *old_health = health;
+                    true
+                } else {
+                    false
+                }
+            });
+        }
     }
 
-    async fn has_mjpeg(device: &Device) -> Result<bool> {
+    /// Find best supported capture format
+    ///
+    /// MJPEG is preferred because it can be passed through as is.
+    async fn find_format(device: &Device) -> Result<FourCc> {
         let mut formats = device.formats(BufferType::VideoCapture);
+        let mut found = Vec::new();
 
         while let Some(format) = formats.fetch_next().await? {
-            if format.type_() == BufferType::VideoCapture
-                && format.flags().contains(FmtFlag::Compressed)
-                && format.pixel_format() == FourCc::Mjpeg
-            {
-                return Ok(true);
+            if format.type_() == BufferType::VideoCapture {
+                found.push(format.pixel_format());
+            }
+        }
+
+        [FourCc::Mjpeg, FourCc::Yuyv, FourCc::Nv12]
+            .into_iter()
+            .find(|format| found.contains(format))
+            .ok_or_else(|| {
+                format!("Device hasn't support any of MJPEG, YUYV or NV12: {found:?}").into()
+            })
+    }
+
+    /// Find supported frame size closest to requested
+    async fn find_size(
+        device: &Device,
+        pixel_format: FourCc,
+        width: u32,
+        height: u32,
+    ) -> Result<(u32, u32)> {
+        let mut sizes = device.sizes(pixel_format);
+        let mut found = Vec::new();
+
+        while let Some(size) = sizes.fetch_next().await? {
+            if let Some(stepwise) = size.try_ref::<FrmSizeStepwise>() {
+                // align requested size to supported range
+                let align = |value: u32, min: u32, max: u32, step: u32| {
+                    let value = value.clamp(min, max);
+                    min + (value - min) / step.max(1) * step.max(1)
+                };
+                found.push((
+                    align(
+                        width,
+                        stepwise.min_width(),
+                        stepwise.max_width(),
+                        stepwise.step_width(),
+                    ),
+                    align(
+                        height,
+                        stepwise.min_height(),
+                        stepwise.max_height(),
+                        stepwise.step_height(),
+                    ),
+                ));
+            } else if let Some(discrete) = size.try_ref::<FrmSizeDiscrete>() {
+                found.push((discrete.width(), discrete.height()));
+            } else {
+                // continuous sizes: let driver adjust requested one
+                found.push((width, height));
             }
         }
 
-        Ok(false)
+        // driver may not support enumerating sizes
+        Ok(best_size(&found, width, height).unwrap_or((width, height)))
     }
 
-    async fn prepare(config: &VideoConfig) -> Result<Device> {
+    /// Find supported frame interval closest to requested frame rate
+    async fn find_interval(
+        device: &Device,
+        pixel_format: FourCc,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> Result<Fract> {
+        let mut intervals = device.intervals(pixel_format, width, height);
+        let mut found = Vec::new();
+
+        while let Some(interval) = intervals.fetch_next().await? {
+            if let Some(stepwise) = interval.try_ref::<FrmIvalStepwise>() {
+                let (min, max) = (fract_fps(stepwise.max()), fract_fps(stepwise.min()));
+                found.push(if (fps as f64) < min {
+                    stepwise.max()
+                } else if (fps as f64) > max {
+                    stepwise.min()
+                } else {
+                    fps_fract(fps)
+                });
+            } else if let Some(discrete) = interval.try_ref::<FrmIvalDiscrete>() {
+                found.push(*discrete);
+            } else {
+                found.push(fps_fract(fps));
+            }
+        }
+
+        Ok(best_interval(&found, fps).unwrap_or_else(|| fps_fract(fps)))
+    }
+
+    async fn open(config: &VideoConfig) -> Result<(Device, FourCc)> {
         let device = Device::open(&config.device).await?;
 
         let caps = device.capabilities().await?;
@@ -119,24 +850,252 @@ impl Video {
             Err("Device doesn't capable for capturing video")?;
         }
 
-        if !Self::has_mjpeg(&device).await? {
-            Err("Device hasn't support MJPEG for capturing video")?;
-        }
+        let pixel_format = Self::find_format(&device).await?;
+
+        Ok((device, pixel_format))
+    }
+
+    /// Detect source and negotiate capture mode
+    ///
+    /// Frame size of source detected using DV timings takes precedence over requested one.
+    /// Mode isn't negotiated when no usable signal.
+    async fn configure(
+        device: &Device,
+        pixel_format: FourCc,
+        requested: VideoMode,
+    ) -> Result<(VideoSignal, Option<(VideoMode, Option<RawGeometry>)>)> {
+        let fd = device.as_raw_fd();
+
+        let requested = match v4l2::query_timings(fd) {
+            Ok(timings) => {
+                let (width, height) = (timings.bt.width, timings.bt.height);
+                log::info!("Detected source {width}x{height}");
+                v4l2::set_timings(fd, &timings)
+                    .map_err(|error| format!("Unable to apply DV timings: {error}"))?;
+                VideoMode {
+                    width,
+                    height,
+                    ..requested
+                }
+            }
+            // no signal
+            Err(Errno::ENOLINK) => return Ok((VideoSignal::NoSignal, None)),
+            // unstable signal
+            Err(Errno::ENOLCK) => return Ok((VideoSignal::Unstable, None)),
+            // timings out of range
+            Err(Errno::ERANGE) => return Ok((VideoSignal::OutOfRange, None)),
+            // DV timings unsupported
+            Err(_) => requested,
+        };
+
+        let configured = Self::negotiate(device, pixel_format, requested).await?;
+
+        Ok((VideoSignal::Present, Some(configured)))
+    }
+
+    /// Negotiate capture mode with driver
+    ///
+    /// Returns actually accepted mode and raw frame geometry when encoding is needed.
+    async fn negotiate(
+        device: &Device,
+        pixel_format: FourCc,
+        requested: VideoMode,
+    ) -> Result<(VideoMode, Option<RawGeometry>)> {
+        let (width, height) =
+            Self::find_size(device, pixel_format, requested.width, requested.height).await?;
 
         let mut format = device.format(BufferType::VideoCapture).await?;
         let pixfmt = format.try_mut::<PixFormat>().unwrap();
-        let bits = 16;
 
         pixfmt
-            .set_width(config.width)
-            .set_height(config.height)
-            .set_bytes_per_line(config.width * bits / 8)
-            .set_size_image(config.width * config.height * bits / 8)
-            .set_pixel_format(FourCc::Mjpeg)
-            .set_color_space(ColorSpace::Jpeg);
+            .set_width(width)
+            .set_height(height)
+            // let driver choose line length and image size
+            .set_bytes_per_line(0)
+            .set_size_image(0)
+            .set_pixel_format(pixel_format);
+
+        if pixel_format == FourCc::Mjpeg {
+            pixfmt.set_color_space(ColorSpace::Jpeg);
+        }
 
         device.set_format(&mut format).await?;
 
-        Ok(device)
+        let pixfmt = format.try_ref::<PixFormat>().unwrap();
+
+        log::info!("Capture format: {pixfmt}");
+
+        let raw = match pixfmt.pixel_format() {
+            FourCc::Mjpeg => None,
+            format @ (FourCc::Yuyv | FourCc::Nv12) => Some(RawGeometry {
+                format: if format == FourCc::Yuyv {
+                    RawFormat::Yuyv
+                } else {
+                    RawFormat::Nv12
+                },
+                width: pixfmt.width(),
+                height: pixfmt.height(),
+                stride: pixfmt.bytes_per_line(),
+            }),
+            format => Err(format!("Driver has chosen unsupported format: {format}"))?,
+        };
+
+        let (width, height) = (pixfmt.width(), pixfmt.height());
+
+        let mut param = device.param(BufferType::VideoCapture).await?;
+        let capture = param.try_mut::<CaptureParm>().unwrap();
+
+        if requested.fps > 0 && capture.capability().contains(IoCapabilityFlag::TemperFrame) {
+            let interval =
+                Self::find_interval(device, pixel_format, width, height, requested.fps).await?;
+            capture.set_time_per_frame(interval);
+            device.set_param(&mut param).await?;
+        }
+
+        let interval = param.try_ref::<CaptureParm>().unwrap().time_per_frame();
+
+        let fps = if interval.numerator() > 0 {
+            fract_fps(interval).round() as u32
+        } else {
+            0
+        };
+
+        let mode = VideoMode { width, height, fps };
+
+        log::info!("Capture mode: {mode}");
+
+        Ok((mode, raw))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_config() {
+        let config: VideoConfig = toml::from_str("device = \"\"").unwrap();
+        assert_eq!(config, VideoConfig::default());
+        assert!(config.validate().is_ok());
+
+        let config = VideoConfig {
+            quality: 0,
+            ..config
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn change_detector() {
+        let config = VideoConfig {
+            keyframe_interval: 1000,
+            ..VideoConfig::default()
+        };
+        let mut detector = ChangeDetector::new(&config);
+        let now = Instant::now();
+
+        assert!(detector.check(b"frame-a", None, now));
+        assert!(!detector.check(b"frame-a", None, now + Duration::from_millis(100)));
+        assert!(detector.check(b"frame-b", None, now + Duration::from_millis(200)));
+        assert!(!detector.check(b"frame-b", None, now + Duration::from_millis(1100)));
+        // keyframe
+        assert!(detector.check(b"frame-b", None, now + Duration::from_millis(1200)));
+
+        detector.reset();
+        assert!(detector.check(b"frame-b", None, now + Duration::from_millis(1300)));
+
+        let mut detector = ChangeDetector::new(&VideoConfig {
+            skip_unchanged: false,
+            ..config
+        });
+        assert!(detector.check(b"frame-a", None, now));
+        assert!(detector.check(b"frame-a", None, now));
+    }
+
+    #[test]
+    fn change_detector_rows() {
+        let mut detector = ChangeDetector::new(&VideoConfig::default());
+        let now = Instant::now();
+        let stride = Some(4);
+        let mut frame = [0u8; 64];
+
+        assert!(detector.check(&frame, stride, now));
+        // row isn't sampled
+        frame[4] = 1;
+        assert!(!detector.check(&frame, stride, now));
+        // row is sampled
+        frame[16 + 1] = 1;
+        assert!(detector.check(&frame, stride, now));
+    }
+
+    #[test]
+    fn health_check() {
+        let config = VideoHealthConfig {
+            stall_timeout: 1000,
+            frozen_timeout: 5000,
+        };
+        let started = Instant::now();
+        let at = |ms| started + Duration::from_millis(ms);
+
+        let mut detector = ChangeDetector::new(&VideoConfig::default());
+        let mut capture = |data: &[u8], ms| {
+            detector.check(data, None, at(ms));
+            FrameTimes {
+                frame: Some(at(ms)),
+                change: detector.last_change,
+            }
+        };
+
+        assert_eq!(
+            check_health(&config, FrameTimes::default(), started, at(500)),
+            VideoHealth::Ok
+        );
+        assert_eq!(
+            check_health(&config, FrameTimes::default(), started, at(1000)),
+            VideoHealth::Stalled
+        );
+
+        let times = capture(b"frame-a", 1000);
+        assert_eq!(
+            check_health(&config, times, started, at(1500)),
+            VideoHealth::Ok
+        );
+        let times = capture(b"frame-a", 5500);
+        assert_eq!(
+            check_health(&config, times, started, at(6000)),
+            VideoHealth::Frozen
+        );
+        assert_eq!(
+            check_health(&config, times, started, at(6500)),
+            VideoHealth::Stalled
+        );
+        let times = capture(b"frame-b", 7000);
+        assert_eq!(
+            check_health(&config, times, started, at(7500)),
+            VideoHealth::Ok
+        );
+
+        let disabled = VideoHealthConfig::default();
+        assert!(!disabled.is_enabled());
+        assert_eq!(
+            check_health(&disabled, FrameTimes::default(), started, at(60000)),
+            VideoHealth::Ok
+        );
+    }
+
+    #[test]
+    fn mode_selection() {
+        let sizes = [(640, 480), (1280, 720), (1920, 1080)];
+        assert_eq!(best_size(&sizes, 1280, 720), Some((1280, 720)));
+        assert_eq!(best_size(&sizes, 1366, 768), Some((1280, 720)));
+        assert_eq!(best_size(&sizes, 4096, 2160), Some((1920, 1080)));
+        assert_eq!(best_size(&[], 640, 480), None);
+
+        let mut ntsc = Fract::default();
+        ntsc.set_numerator(1001).set_denominator(30000);
+        let intervals = [fps_fract(60), ntsc, fps_fract(15)];
+        assert_eq!(best_interval(&intervals, 30).unwrap().numerator(), 1001);
+        assert_eq!(best_interval(&intervals, 50).unwrap().denominator(), 60);
+        assert_eq!(best_interval(&intervals, 1).unwrap().denominator(), 15);
     }
 }
@@ -1,5 +1,8 @@
 pub use hidg_core::{Button, Key, Led, MouseInput};
+use parse_display::{Display, FromStr};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "zbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct KeyboardState {
@@ -28,3 +31,84 @@ impl From<&MouseInput> for MouseState {
         }
     }
 }
+
+/// Mouse pointer mode
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[serde(rename_all = "kebab-case")]
+#[display(style = "kebab-case")]
+pub enum MouseMode {
+    /// Absolute pointer (tablet-like)
+    #[default]
+    Absolute,
+
+    /// Relative motion (classic mouse)
+    Relative,
+}
+
+/// USB device controller state
+///
+/// See `usb_state_string()` in Linux kernel for the sysfs representation.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[cfg_attr(feature = "zbus", derive(Type, Value, OwnedValue))]
+#[cfg_attr(feature = "zbus", zvariant(signature = "s"))]
+#[serde(rename_all = "kebab-case")]
+#[display(style = "kebab-case")]
+pub enum UsbState {
+    /// State is unknown
+    #[default]
+    Unknown = 0,
+
+    /// Host isn't connected
+    NotAttached = 1,
+
+    /// Cable attached
+    Attached = 2,
+
+    /// Powered by host
+    Powered = 3,
+
+    /// Reconnecting
+    Reconnecting = 4,
+
+    /// Unauthenticated (wireless)
+    Unauthenticated = 5,
+
+    /// Reset by host
+    Default = 6,
+
+    /// Address assigned by host
+    Addressed = 7,
+
+    /// Enumerated and configured by host
+    Configured = 8,
+
+    /// Suspended by host
+    Suspended = 9,
+}
+
+impl UsbState {
+    /// Parse sysfs representation
+    pub fn from_sysfs(state: &str) -> Self {
+        match state.trim() {
+            "not attached" => Self::NotAttached,
+            "attached" => Self::Attached,
+            "powered" => Self::Powered,
+            "reconnecting" => Self::Reconnecting,
+            "unauthenticated" => Self::Unauthenticated,
+            "default" => Self::Default,
+            "addressed" => Self::Addressed,
+            "configured" => Self::Configured,
+            "suspended" => Self::Suspended,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Host is able to receive reports
+    pub fn is_configured(&self) -> bool {
+        matches!(self, Self::Configured)
+    }
+}
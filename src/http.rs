@@ -1,6 +1,5 @@
 use crate::{
-    addr::socket_addr_parse,
-    ButtonId, LedId,
+    addr::socket_addr_parse, ButtonId, LedActivity, LedId, LedPattern, PowerState, SensorReading,
 };
 use core::str::FromStr;
 use serde::{Deserialize, Serialize};
@@ -11,7 +10,10 @@ use std::{
 };
 
 #[cfg(feature = "hid")]
-use crate::hid::{Button, Key, KeyboardState, Led, MouseState};
+use crate::hid::{Button, Key, KeyboardState, Led, MouseMode, MouseState, UsbState};
+
+#[cfg(feature = "video")]
+use crate::video::{VideoHealth, VideoMode, VideoSignal};
 
 #[cfg(feature = "video")]
 use std::sync::Arc;
@@ -136,16 +138,47 @@ pub enum SocketInput {
     #[serde(rename = "p")]
     MousePointer { x: i16, y: i16 },
     #[cfg(feature = "hid")]
-    #[serde(rename = "p")]
+    #[serde(rename = "r")]
+    MouseMotion { x: i16, y: i16 },
+    #[cfg(feature = "hid")]
+    #[serde(rename = "w")]
     MouseWheel {
         #[serde(rename = "w")]
         wheel: i8,
     },
+    #[cfg(feature = "hid")]
+    #[serde(rename = "o")]
+    MouseMode {
+        #[serde(rename = "o")]
+        mode: MouseMode,
+    },
+    #[cfg(feature = "hid")]
+    #[serde(rename = "u")]
+    WakeHost,
+    #[cfg(feature = "video")]
+    #[serde(rename = "v")]
+    VideoMode {
+        #[serde(rename = "v")]
+        mode: VideoMode,
+    },
+    /// WebRTC session offer
+    #[cfg(feature = "webrtc")]
+    #[serde(rename = "c")]
+    WebRtcOffer {
+        #[serde(rename = "s")]
+        sdp: String,
+    },
+    /// WebRTC session close
+    #[cfg(feature = "webrtc")]
+    #[serde(rename = "x")]
+    WebRtcClose,
 }
 
 /// Outgoing message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "$")]
+// initial state is sent once per session so boxing doesn't pay off
+#[allow(clippy::large_enum_variant)]
 pub enum SocketOutput {
     /// Initial state
     #[serde(rename = "s")]
@@ -153,6 +186,18 @@ pub enum SocketOutput {
         /// Led states
         #[serde(rename = "l")]
         leds: HashMap<LedId, bool>,
+        /// Derived LED states
+        #[serde(rename = "d")]
+        led_patterns: HashMap<LedId, LedPattern>,
+        /// Activity of sampled LEDs
+        #[serde(rename = "a")]
+        led_activities: HashMap<LedId, LedActivity>,
+        /// Sensor readings
+        #[serde(rename = "r")]
+        sensors: HashMap<String, SensorReading>,
+        /// Host power state
+        #[serde(rename = "e")]
+        power: PowerState,
         /// Button states
         #[serde(rename = "b")]
         buttons: HashMap<ButtonId, bool>,
@@ -164,6 +209,26 @@ pub enum SocketOutput {
         #[cfg(feature = "hid")]
         #[serde(rename = "m")]
         mouse: Option<MouseState>,
+        /// Mouse mode
+        #[cfg(feature = "hid")]
+        #[serde(rename = "o")]
+        mouse_mode: Option<MouseMode>,
+        /// USB host connection state
+        #[cfg(feature = "hid")]
+        #[serde(rename = "u")]
+        usb_state: Option<UsbState>,
+        /// Video capture mode
+        #[cfg(feature = "video")]
+        #[serde(rename = "v")]
+        video_mode: Option<VideoMode>,
+        /// Video input signal state
+        #[cfg(feature = "video")]
+        #[serde(rename = "n")]
+        video_signal: Option<VideoSignal>,
+        /// Video health state
+        #[cfg(feature = "video")]
+        #[serde(rename = "h")]
+        video_health: Option<VideoHealth>,
     },
     /// LED state change
     #[serde(rename = "l")]
@@ -173,6 +238,36 @@ pub enum SocketOutput {
         #[serde(rename = "s")]
         state: bool,
     },
+    /// Derived LED state change
+    #[serde(rename = "d")]
+    LedPattern {
+        #[serde(rename = "l")]
+        led: LedId,
+        #[serde(rename = "d")]
+        pattern: LedPattern,
+    },
+    /// Sampled LED activity
+    #[serde(rename = "a")]
+    LedActivity {
+        #[serde(rename = "l")]
+        led: LedId,
+        #[serde(rename = "a")]
+        activity: LedActivity,
+    },
+    /// Host power state change
+    #[serde(rename = "e")]
+    Power {
+        #[serde(rename = "s")]
+        state: PowerState,
+    },
+    /// Sensor reading change
+    #[serde(rename = "r")]
+    Sensor {
+        #[serde(rename = "n")]
+        name: String,
+        #[serde(rename = "r")]
+        reading: SensorReading,
+    },
     /// Button state change
     #[serde(rename = "b")]
     Button {
@@ -214,12 +309,47 @@ pub enum SocketOutput {
     MousePointer { x: i16, y: i16 },
     /// Mouse wheel change
     #[cfg(feature = "hid")]
-    #[serde(rename = "p")]
+    #[serde(rename = "w")]
     MouseWheel {
         #[serde(rename = "w")]
         wheel: i8,
     },
-    /// Video frames
+    /// USB host connection state change
+    #[cfg(feature = "hid")]
+    #[serde(rename = "u")]
+    UsbState {
+        #[serde(rename = "s")]
+        state: UsbState,
+    },
+    /// Video capture mode change
+    #[cfg(feature = "video")]
+    #[serde(rename = "g")]
+    VideoMode {
+        #[serde(rename = "v")]
+        mode: VideoMode,
+    },
+    /// Video input signal state change
+    #[cfg(feature = "video")]
+    #[serde(rename = "n")]
+    VideoSignal {
+        #[serde(rename = "s")]
+        signal: VideoSignal,
+    },
+    /// Video health state change
+    #[cfg(feature = "video")]
+    #[serde(rename = "h")]
+    VideoHealth {
+        #[serde(rename = "s")]
+        health: VideoHealth,
+    },
+    /// WebRTC session answer
+    #[cfg(feature = "webrtc")]
+    #[serde(rename = "c")]
+    WebRtcAnswer {
+        #[serde(rename = "s")]
+        sdp: String,
+    },
+    /// Video frame (sent over dedicated video socket)
     #[cfg(feature = "video")]
     #[serde(rename = "v")]
     VideoFrame {
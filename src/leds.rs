@@ -32,3 +32,80 @@ pub enum LedId {
     /// Ethernet usage LED
     Ether = 3,
 }
+
+/// LED behavior derived from edges
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[cfg_attr(feature = "zbus", derive(Type, Value, OwnedValue))]
+#[cfg_attr(feature = "zbus", zvariant(signature = "s"))]
+#[serde(rename_all = "kebab-case")]
+#[display(style = "kebab-case")]
+pub enum LedMode {
+    /// Steady off
+    #[default]
+    Off = 0,
+
+    /// Steady on
+    On = 1,
+
+    /// Regular blinking
+    Blinking = 2,
+
+    /// Irregular flickering
+    Activity = 3,
+}
+
+/// Derived LED state
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LedPattern {
+    /// Behavior
+    #[serde(rename = "m")]
+    pub mode: LedMode,
+    /// Blinking period in milliseconds (0 when not blinking)
+    #[serde(rename = "p")]
+    pub period: u32,
+}
+
+impl core::fmt::Display for LedPattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.mode)?;
+        if self.period > 0 {
+            write!(f, " {}ms", self.period)?;
+        }
+        Ok(())
+    }
+}
+
+/// LED activity over sampling interval
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LedActivity {
+    /// Percent of time LED was on
+    #[serde(rename = "d")]
+    pub duty: u8,
+    /// Number of edges
+    #[serde(rename = "e")]
+    pub edges: u32,
+}
+
+impl core::fmt::Display for LedActivity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}% {} edges", self.duty, self.edges)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn led_pattern_display() {
+        let pattern = LedPattern {
+            mode: LedMode::Blinking,
+            period: 1000,
+        };
+        assert_eq!(pattern.to_string(), "blinking 1000ms");
+        assert_eq!(LedPattern::default().to_string(), "off");
+        assert_eq!("activity".parse::<LedMode>().unwrap(), LedMode::Activity);
+    }
+}
@@ -1,5 +1,7 @@
 mod buttons;
 mod leds;
+mod power;
+mod sensors;
 
 #[cfg(feature = "dbus")]
 mod dbus;
@@ -13,8 +15,16 @@ mod addr;
 #[cfg(feature = "hid")]
 pub mod hid;
 
+#[cfg(feature = "video")]
+pub mod video;
+
+#[cfg(feature = "record")]
+pub mod record;
+
 pub use buttons::ButtonId;
-pub use leds::LedId;
+pub use leds::{LedActivity, LedId, LedMode, LedPattern};
+pub use power::PowerState;
+pub use sensors::{SensorKind, SensorReading, SensorStatus};
 
 #[cfg(feature = "dbus")]
 pub use dbus::DBusAddr;
@@ -0,0 +1,40 @@
+use parse_display::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "zbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+/// Host power state
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[cfg_attr(feature = "zbus", derive(Type, Value, OwnedValue))]
+#[cfg_attr(feature = "zbus", zvariant(signature = "s"))]
+#[serde(rename_all = "kebab-case")]
+#[display(style = "kebab-case")]
+pub enum PowerState {
+    /// Not enough signals to decide
+    #[default]
+    Unknown = 0,
+
+    /// Host is powered off
+    Off = 1,
+
+    /// Host is sleeping
+    Standby = 2,
+
+    /// Host is powered but operating system isn't up yet
+    Booting = 3,
+
+    /// Host is running
+    On = 4,
+
+    /// Host is powered but doesn't respond
+    Hung = 5,
+}
+
+impl PowerState {
+    /// Host is powered
+    pub fn is_powered(&self) -> bool {
+        matches!(self, Self::Booting | Self::On | Self::Hung)
+    }
+}
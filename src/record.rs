@@ -0,0 +1,396 @@
+use crate::{ButtonId, LedId, SocketInput};
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+/// Record file magic
+const MAGIC: &[u8; 8] = b"UKVMREC\0";
+
+/// Index trailer magic
+const INDEX_MAGIC: &[u8; 8] = b"UKVMIDX\0";
+
+/// Record format version
+const VERSION: u32 = 1;
+
+/// Header size in bytes
+const HEADER_SIZE: u64 = 8 + 4 + 8;
+
+/// Chunk header size in bytes
+const CHUNK_HEADER_SIZE: u64 = 1 + 8 + 4;
+
+/// Trailer size in bytes
+const TRAILER_SIZE: u64 = 8 + 8;
+
+/// Video frame chunk
+const CHUNK_FRAME: u8 = b'F';
+
+/// Event chunk
+const CHUNK_EVENT: u8 = b'E';
+
+/// Frame index chunk
+const CHUNK_INDEX: u8 = b'X';
+
+/// Recorded event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "$")]
+pub enum RecordEvent {
+    /// Input from client
+    #[serde(rename = "i")]
+    Input {
+        #[serde(rename = "i")]
+        input: SocketInput,
+    },
+    /// Button state change
+    #[serde(rename = "b")]
+    Button {
+        #[serde(rename = "b")]
+        button: ButtonId,
+        #[serde(rename = "s")]
+        state: bool,
+    },
+    /// LED state change
+    #[serde(rename = "l")]
+    Led {
+        #[serde(rename = "l")]
+        led: LedId,
+        #[serde(rename = "s")]
+        state: bool,
+    },
+}
+
+/// Record chunk data
+#[derive(Debug, Clone)]
+pub enum RecordData {
+    /// JPEG video frame
+    Frame(Vec<u8>),
+    /// Event
+    Event(RecordEvent),
+}
+
+/// Record chunk
+#[derive(Debug, Clone)]
+pub struct RecordChunk {
+    /// Time since start of recording
+    pub time: Duration,
+    /// Chunk data
+    pub data: RecordData,
+}
+
+/// Session record writer
+///
+/// Record consists of header followed by timestamped chunks. Frame index
+/// is appended when finishing so incomplete records remain readable.
+pub struct RecordWriter<W> {
+    writer: W,
+    offset: u64,
+    index: Vec<(u64, u64)>,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Start new record
+    ///
+    /// Start time is in milliseconds since UNIX epoch.
+    pub fn new(mut writer: W, start_time: u64) -> Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&start_time.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            offset: HEADER_SIZE,
+            index: Vec::new(),
+        })
+    }
+
+    /// Write video frame
+    pub fn write_frame(&mut self, time: Duration, frame: &[u8]) -> Result<()> {
+        self.index.push((time.as_millis() as _, self.offset));
+        self.write_chunk(CHUNK_FRAME, time.as_millis() as _, frame)
+    }
+
+    /// Write event
+    pub fn write_event(&mut self, time: Duration, event: &RecordEvent) -> Result<()> {
+        let data = serde_json::to_vec(event)?;
+        self.write_chunk(CHUNK_EVENT, time.as_millis() as _, &data)
+    }
+
+    /// Write frame index and flush
+    pub fn finish(mut self) -> Result<W> {
+        let index_offset = self.offset;
+        let data = self
+            .index
+            .iter()
+            .flat_map(|(time, offset)| time.to_le_bytes().into_iter().chain(offset.to_le_bytes()))
+            .collect::<Vec<_>>();
+        let time = self.index.last().map(|(time, _)| *time).unwrap_or_default();
+
+        self.write_chunk(CHUNK_INDEX, time, &data)?;
+        self.writer.write_all(&index_offset.to_le_bytes())?;
+        self.writer.write_all(INDEX_MAGIC)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write_chunk(&mut self, kind: u8, time: u64, data: &[u8]) -> Result<()> {
+        let len = u32::try_from(data.len()).map_err(|_| ErrorKind::InvalidInput)?;
+
+        self.writer.write_all(&[kind])?;
+        self.writer.write_all(&time.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(data)?;
+
+        self.offset += CHUNK_HEADER_SIZE + data.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Session record reader
+pub struct RecordReader<R> {
+    reader: R,
+    start_time: u64,
+    index: Vec<(u64, u64)>,
+}
+
+impl<R: Read + Seek> RecordReader<R> {
+    /// Open record
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; HEADER_SIZE as usize];
+        reader.read_exact(&mut header)?;
+
+        if &header[..8] != MAGIC {
+            return Err(invalid_data("Not a session record"));
+        }
+
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported record version {version}"
+            )));
+        }
+
+        let start_time = u64::from_le_bytes(header[12..].try_into().unwrap());
+
+        let mut this = Self {
+            reader,
+            start_time,
+            index: Vec::new(),
+        };
+
+        // record may be incomplete so missing index isn't an error
+        if let Some(index) = this.read_index()? {
+            this.index = index;
+        }
+
+        this.reader.seek(SeekFrom::Start(HEADER_SIZE))?;
+
+        Ok(this)
+    }
+
+    /// Start time in milliseconds since UNIX epoch
+    pub fn start_time(&self) -> u64 {
+        self.start_time
+    }
+
+    /// Frame index as time and offset pairs
+    ///
+    /// Empty when record wasn't finished properly.
+    pub fn index(&self) -> &[(u64, u64)] {
+        &self.index
+    }
+
+    /// Seek to last frame at or before time
+    pub fn seek(&mut self, time: Duration) -> Result<()> {
+        let time = time.as_millis() as u64;
+        let offset = match self
+            .index
+            .partition_point(|(frame_time, _)| *frame_time <= time)
+        {
+            0 => HEADER_SIZE,
+            n => self.index[n - 1].1,
+        };
+        self.reader.seek(SeekFrom::Start(offset))?;
+        Ok(())
+    }
+
+    /// Read next chunk
+    ///
+    /// Returns `None` at the end of record.
+    pub fn next_chunk(&mut self) -> Result<Option<RecordChunk>> {
+        loop {
+            let mut header = [0u8; CHUNK_HEADER_SIZE as usize];
+            match self.reader.read_exact(&mut header) {
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(error) => return Err(error),
+            }
+
+            let kind = header[0];
+            let time = Duration::from_millis(u64::from_le_bytes(header[1..9].try_into().unwrap()));
+            let len = u32::from_le_bytes(header[9..].try_into().unwrap());
+
+            let data = match kind {
+                CHUNK_INDEX => return Ok(None),
+                CHUNK_FRAME | CHUNK_EVENT => {
+                    let mut data = vec![0u8; len as usize];
+                    match self.reader.read_exact(&mut data) {
+                        Ok(_) => data,
+                        // truncated chunk of interrupted recording
+                        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                        Err(error) => return Err(error),
+                    }
+                }
+                _ => {
+                    // skip unknown chunks
+                    self.reader.seek(SeekFrom::Current(len as _))?;
+                    continue;
+                }
+            };
+
+            let data = if kind == CHUNK_FRAME {
+                RecordData::Frame(data)
+            } else {
+                RecordData::Event(serde_json::from_slice(&data)?)
+            };
+
+            return Ok(Some(RecordChunk { time, data }));
+        }
+    }
+
+    fn read_index(&mut self) -> Result<Option<Vec<(u64, u64)>>> {
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        if end < HEADER_SIZE + CHUNK_HEADER_SIZE + TRAILER_SIZE {
+            return Ok(None);
+        }
+
+        let mut trailer = [0u8; TRAILER_SIZE as usize];
+        self.reader.seek(SeekFrom::Start(end - TRAILER_SIZE))?;
+        self.reader.read_exact(&mut trailer)?;
+
+        if &trailer[8..] != INDEX_MAGIC {
+            return Ok(None);
+        }
+
+        let offset = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+        if offset < HEADER_SIZE || offset + CHUNK_HEADER_SIZE + TRAILER_SIZE > end {
+            return Err(invalid_data("Invalid index offset"));
+        }
+
+        let mut header = [0u8; CHUNK_HEADER_SIZE as usize];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut header)?;
+
+        let len = u32::from_le_bytes(header[9..].try_into().unwrap()) as u64;
+        if header[0] != CHUNK_INDEX || !len.is_multiple_of(16) {
+            return Err(invalid_data("Invalid index chunk"));
+        }
+
+        let mut data = vec![0u8; len as usize];
+        self.reader.read_exact(&mut data)?;
+
+        Ok(Some(
+            data.chunks_exact(16)
+                .map(|entry| {
+                    (
+                        u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                        u64::from_le_bytes(entry[8..].try_into().unwrap()),
+                    )
+                })
+                .collect(),
+        ))
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn write_record(finish: bool) -> Vec<u8> {
+        let mut writer = RecordWriter::new(Cursor::new(Vec::new()), 1_700_000_000_000).unwrap();
+        writer
+            .write_frame(Duration::from_millis(0), b"frame0")
+            .unwrap();
+        writer
+            .write_event(
+                Duration::from_millis(10),
+                &RecordEvent::Button {
+                    button: ButtonId::Power,
+                    state: true,
+                },
+            )
+            .unwrap();
+        writer
+            .write_frame(Duration::from_millis(40), b"frame1")
+            .unwrap();
+
+        if finish {
+            writer.finish().unwrap().into_inner()
+        } else {
+            writer.writer.into_inner()
+        }
+    }
+
+    fn frame(chunk: RecordChunk) -> (u64, Vec<u8>) {
+        match chunk.data {
+            RecordData::Frame(frame) => (chunk.time.as_millis() as _, frame),
+            data => panic!("Frame expected but got {data:?}"),
+        }
+    }
+
+    #[test]
+    fn write_read() {
+        let mut reader = RecordReader::new(Cursor::new(write_record(true))).unwrap();
+
+        assert_eq!(reader.start_time(), 1_700_000_000_000);
+        assert_eq!(reader.index().len(), 2);
+        assert_eq!(reader.index()[0], (0, HEADER_SIZE));
+        assert_eq!(reader.index()[1].0, 40);
+
+        assert_eq!(
+            frame(reader.next_chunk().unwrap().unwrap()),
+            (0, b"frame0".to_vec())
+        );
+        let chunk = reader.next_chunk().unwrap().unwrap();
+        assert_eq!(chunk.time, Duration::from_millis(10));
+        assert!(matches!(
+            chunk.data,
+            RecordData::Event(RecordEvent::Button {
+                button: ButtonId::Power,
+                state: true
+            })
+        ));
+        assert_eq!(
+            frame(reader.next_chunk().unwrap().unwrap()),
+            (40, b"frame1".to_vec())
+        );
+        assert!(reader.next_chunk().unwrap().is_none());
+
+        reader.seek(Duration::from_millis(45)).unwrap();
+        assert_eq!(frame(reader.next_chunk().unwrap().unwrap()).0, 40);
+    }
+
+    #[test]
+    fn read_unfinished() {
+        let mut data = write_record(false);
+        // interrupted in the middle of last frame
+        data.truncate(data.len() - 2);
+
+        let mut reader = RecordReader::new(Cursor::new(data)).unwrap();
+
+        assert!(reader.index().is_empty());
+        assert_eq!(frame(reader.next_chunk().unwrap().unwrap()).0, 0);
+        assert!(reader.next_chunk().unwrap().is_some());
+        assert!(reader.next_chunk().unwrap().is_none());
+    }
+
+    #[test]
+    fn reject_garbage() {
+        assert!(RecordReader::new(Cursor::new(b"not a record at all".to_vec())).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use parse_display::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "zbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+/// Sensor quantity
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[cfg_attr(feature = "zbus", derive(Type, Value, OwnedValue))]
+#[cfg_attr(feature = "zbus", zvariant(signature = "s"))]
+#[serde(rename_all = "kebab-case")]
+#[display(style = "kebab-case")]
+pub enum SensorKind {
+    /// Voltage in volts
+    Voltage = 0,
+
+    /// Current in amperes
+    Current = 1,
+
+    /// Power in watts
+    Power = 2,
+
+    /// Temperature in degrees Celsius
+    Temperature = 3,
+
+    /// Other value without units
+    #[default]
+    Other = 4,
+}
+
+impl SensorKind {
+    /// Measurement units
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::Voltage => "V",
+            Self::Current => "A",
+            Self::Power => "W",
+            Self::Temperature => "°C",
+            Self::Other => "",
+        }
+    }
+}
+
+/// Sensor value state
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[cfg_attr(feature = "zbus", derive(Type, Value, OwnedValue))]
+#[cfg_attr(feature = "zbus", zvariant(signature = "s"))]
+#[serde(rename_all = "kebab-case")]
+#[display(style = "kebab-case")]
+pub enum SensorStatus {
+    /// Value within thresholds
+    #[default]
+    Ok = 0,
+
+    /// Value below minimum threshold
+    Low = 1,
+
+    /// Value above maximum threshold
+    High = 2,
+
+    /// Value cannot be read
+    Error = 3,
+}
+
+impl SensorStatus {
+    /// Value is within thresholds
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+}
+
+/// Sensor reading
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SensorReading {
+    /// Quantity
+    #[serde(rename = "k")]
+    pub kind: SensorKind,
+    /// Value in units of quantity
+    #[serde(rename = "v")]
+    pub value: Option<f64>,
+    /// Value state
+    #[serde(rename = "s")]
+    pub status: SensorStatus,
+}
+
+impl core::fmt::Display for SensorReading {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if let Some(value) = self.value {
+            write!(f, "{value:.3}{}", self.kind.unit())?;
+        } else {
+            f.write_str("-")?;
+        }
+        if !self.status.is_ok() {
+            write!(f, " ({})", self.status)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sensor_reading_display() {
+        let reading = SensorReading {
+            kind: SensorKind::Voltage,
+            value: Some(12.0512),
+            status: SensorStatus::Ok,
+        };
+        assert_eq!(reading.to_string(), "12.051V");
+
+        let reading = SensorReading {
+            kind: SensorKind::Temperature,
+            value: None,
+            status: SensorStatus::Error,
+        };
+        assert_eq!(reading.to_string(), "- (error)");
+    }
+}
@@ -0,0 +1,98 @@
+use parse_display::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "zbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+/// Video capture mode
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[display("{width}x{height}@{fps}")]
+pub struct VideoMode {
+    /// Frame width in pixels
+    #[serde(rename = "w")]
+    pub width: u32,
+    /// Frame height in pixels
+    #[serde(rename = "h")]
+    pub height: u32,
+    /// Frames per second (0 means driver default)
+    #[serde(rename = "f")]
+    pub fps: u32,
+}
+
+/// Video input signal state
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[cfg_attr(feature = "zbus", derive(Type, Value, OwnedValue))]
+#[cfg_attr(feature = "zbus", zvariant(signature = "s"))]
+#[serde(rename_all = "kebab-case")]
+#[display(style = "kebab-case")]
+pub enum VideoSignal {
+    /// Stable signal detected
+    #[default]
+    Present = 0,
+
+    /// No signal on input
+    NoSignal = 1,
+
+    /// Signal isn't stable
+    Unstable = 2,
+
+    /// Signal out of supported range
+    OutOfRange = 3,
+}
+
+impl VideoSignal {
+    /// Signal is usable for capturing
+    pub fn is_present(&self) -> bool {
+        matches!(self, Self::Present)
+    }
+}
+
+/// Video health state
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, FromStr, Display,
+)]
+#[cfg_attr(feature = "zbus", derive(Type, Value, OwnedValue))]
+#[cfg_attr(feature = "zbus", zvariant(signature = "s"))]
+#[serde(rename_all = "kebab-case")]
+#[display(style = "kebab-case")]
+pub enum VideoHealth {
+    /// Frames are coming and content changes
+    #[default]
+    Ok = 0,
+
+    /// No frames received for too long
+    Stalled = 1,
+
+    /// Screen content unchanged for too long
+    Frozen = 2,
+}
+
+impl VideoHealth {
+    /// Video is healthy
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn video_mode_parse() {
+        let mode: VideoMode = "1280x720@30".parse().unwrap();
+        assert_eq!(
+            mode,
+            VideoMode {
+                width: 1280,
+                height: 720,
+                fps: 30
+            }
+        );
+        assert_eq!(mode.to_string(), "1280x720@30");
+        assert!("1280x720".parse::<VideoMode>().is_err());
+    }
+}